@@ -1,232 +1,48 @@
 // src/abi.rs
 use ethers::prelude::*;
 
+// Generated from the EigenLayerRestaking forge artifact, so the custom errors
+// of the middleware are bound as well and reverts can be decoded.
+abigen!(EigenLayerRestaking, "./src/abii.json");
+
+// EigenLayer AVSDirectory, used to build operator registration signatures
+abigen!(
+    AVSDirectory,
+    r#"[
+        function calculateOperatorAVSRegistrationDigestHash(address operator, address avs, bytes32 salt, uint256 expiry) external view returns (bytes32)
+        function operatorSaltIsSpent(address operator, bytes32 salt) external view returns (bool)
+        function domainSeparator() external view returns (bytes32)
+    ]"#,
+);
+
+// Minimal surface shared by every Ownable UUPS proxy of the middleware
+// (EigenLayerRestaking, SymbioticRestaking, Slashing, Parameters, Validator)
+abigen!(
+    UUPSUpgradeable,
+    r#"[
+        function proxiableUUID() external view returns (bytes32)
+        function upgradeToAndCall(address newImplementation, bytes data) external payable
+        function owner() external view returns (address)
+        function UPGRADE_INTERFACE_VERSION() external view returns (string)
+        event Upgraded(address indexed implementation)
+        error AddressEmptyCode(address target)
+        error ERC1967InvalidImplementation(address implementation)
+        error ERC1967NonPayable()
+        error FailedCall()
+        error OwnableUnauthorizedAccount(address account)
+        error UUPSUnauthorizedCallContext()
+        error UUPSUnsupportedProxiableUUID(bytes32 slot)
+    ]"#,
+);
+
+// Token metadata used to format collateral amounts
 abigen!(
-    EigenLayerRestaking,
+    ERC20,
     r#"[
-        {
-            "inputs": [],
-            "name": "UPGRADE_INTERFACE_VERSION",
-            "outputs": [{"internalType": "string", "name": "", "type": "string"}],
-            "stateMutability": "view",
-            "type": "function"
-        },
-        {
-            "inputs": [],
-            "name": "avsDirectory",
-            "outputs": [{"internalType": "address", "name": "", "type": "address"}],
-            "stateMutability": "view",
-            "type": "function"
-        },
-        {
-            "inputs": [{"internalType": "address", "name": "operator", "type": "address"}],
-            "name": "deregisterOperatorFromAVS",
-            "outputs": [],
-            "stateMutability": "nonpayable",
-            "type": "function"
-        },
-        {
-            "inputs": [{"internalType": "address", "name": "strategy", "type": "address"}],
-            "name": "deregisterStrategy",
-            "outputs": [],
-            "stateMutability": "nonpayable",
-            "type": "function"
-        },
-        {
-            "inputs": [],
-            "name": "getCurrentPeriod",
-            "outputs": [{"internalType": "uint48", "name": "periodIndex", "type": "uint48"}],
-            "stateMutability": "view",
-            "type": "function"
-        },
-        {
-            "inputs": [{"internalType": "address", "name": "operator", "type": "address"}],
-            "name": "getOperatorRestakedStrategies",
-            "outputs": [{"internalType": "address[]", "name": "", "type": "address[]"}],
-            "stateMutability": "view",
-            "type": "function"
-        },
-        {
-            "inputs": [
-                {"internalType": "address", "name": "operator", "type": "address"},
-                {"internalType": "address", "name": "collateral", "type": "address"},
-                {"internalType": "uint48", "name": "timestamp", "type": "uint48"}
-            ],
-            "name": "getOperatorStakeAt",
-            "outputs": [{"internalType": "uint256", "name": "amount", "type": "uint256"}],
-            "stateMutability": "view",
-            "type": "function"
-        },
-        {
-            "inputs": [
-                {"internalType": "address", "name": "operator", "type": "address"},
-                {"internalType": "address", "name": "collateral", "type": "address"}
-            ],
-            "name": "getProviderCollateral",
-            "outputs": [{"internalType": "uint256", "name": "amount", "type": "uint256"}],
-            "stateMutability": "view",
-            "type": "function"
-        },
-        {
-            "inputs": [{"internalType": "address", "name": "operator", "type": "address"}],
-            "name": "getProviderCollateralTokens",
-            "outputs": [
-                {"internalType": "address[]", "name": "", "type": "address[]"},
-                {"internalType": "uint256[]", "name": "", "type": "uint256[]"}
-            ],
-            "stateMutability": "view",
-            "type": "function"
-        },
-        {
-            "inputs": [],
-            "name": "getRestakeableStrategies",
-            "outputs": [{"internalType": "address[]", "name": "", "type": "address[]"}],
-            "stateMutability": "view",
-            "type": "function"
-        },
-        {
-            "inputs": [],
-            "name": "getWhitelistedStrategies",
-            "outputs": [{"internalType": "address[]", "name": "", "type": "address[]"}],
-            "stateMutability": "view",
-            "type": "function"
-        },
-        {
-            "inputs": [
-                {"internalType": "address", "name": "_owner", "type": "address"},
-                {"internalType": "address", "name": "_parameters", "type": "address"},
-                {"internalType": "address", "name": "_eigenlayerAVSDirectory", "type": "address"},
-                {"internalType": "address", "name": "_eigenlayerDelegationManager", "type": "address"},
-                {"internalType": "address", "name": "_eigenlayerStrategyManager", "type": "address"},
-                {"internalType": "address", "name": "_restakingHelper", "type": "address"}
-            ],
-            "name": "initialize",
-            "outputs": [],
-            "stateMutability": "nonpayable",
-            "type": "function"
-        },
-        {
-            "inputs": [{"internalType": "address", "name": "strategy", "type": "address"}],
-            "name": "isStrategyEnabled",
-            "outputs": [{"internalType": "bool", "name": "", "type": "bool"}],
-            "stateMutability": "view",
-            "type": "function"
-        },
-        {
-            "inputs": [],
-            "name": "owner",
-            "outputs": [{"internalType": "address", "name": "", "type": "address"}],
-            "stateMutability": "view",
-            "type": "function"
-        },
-        {
-            "inputs": [],
-            "name": "pauseStrategy",
-            "outputs": [],
-            "stateMutability": "nonpayable",
-            "type": "function"
-        },
-        {
-            "inputs": [],
-            "name": "proxiableUUID",
-            "outputs": [{"internalType": "bytes32", "name": "", "type": "bytes32"}],
-            "stateMutability": "view",
-            "type": "function"
-        },
-        {
-            "inputs": [
-                {"internalType": "string", "name": "rpc", "type": "string"},
-                {"internalType": "string", "name": "rpc1", "type": "string"},
-                {"internalType": "string", "name": "rpc2", "type": "string"},
-                {
-                    "components": [
-                        {"internalType": "bytes", "name": "signature", "type": "bytes"},
-                        {"internalType": "bytes32", "name": "salt", "type": "bytes32"},
-                        {"internalType": "uint256", "name": "expiry", "type": "uint256"}
-                    ],
-                    "internalType": "struct ISignatureUtils.SignatureWithSaltAndExpiry",
-                    "name": "operatorSignature",
-                    "type": "tuple"
-                }
-            ],
-            "name": "registerOperator",
-            "outputs": [],
-            "stateMutability": "nonpayable",
-            "type": "function"
-        },
-        {
-            "inputs": [
-                {"internalType": "address", "name": "operator", "type": "address"},
-                {
-                    "components": [
-                        {"internalType": "bytes", "name": "signature", "type": "bytes"},
-                        {"internalType": "bytes32", "name": "salt", "type": "bytes32"},
-                        {"internalType": "uint256", "name": "expiry", "type": "uint256"}
-                    ],
-                    "internalType": "struct ISignatureUtils.SignatureWithSaltAndExpiry",
-                    "name": "operatorSignature",
-                    "type": "tuple"
-                }
-            ],
-            "name": "registerOperatorToAVS",
-            "outputs": [],
-            "stateMutability": "nonpayable",
-            "type": "function"
-        },
-        {
-            "inputs": [{"internalType": "address", "name": "strategy", "type": "address"}],
-            "name": "registerStrategy",
-            "outputs": [],
-            "stateMutability": "nonpayable",
-            "type": "function"
-        },
-        {
-            "inputs": [],
-            "name": "renounceOwnership",
-            "outputs": [],
-            "stateMutability": "nonpayable",
-            "type": "function"
-        },
-        {
-            "inputs": [],
-            "name": "restakingHelper",
-            "outputs": [{"internalType": "address", "name": "", "type": "address"}],
-            "stateMutability": "view",
-            "type": "function"
-        },
-        {
-            "inputs": [{"internalType": "address", "name": "newOwner", "type": "address"}],
-            "name": "transferOwnership",
-            "outputs": [],
-            "stateMutability": "nonpayable",
-            "type": "function"
-        },
-        {
-            "inputs": [],
-            "name": "unpauseStrategy",
-            "outputs": [],
-            "stateMutability": "nonpayable",
-            "type": "function"
-        },
-        {
-            "inputs": [{"internalType": "string", "name": "metadataURI", "type": "string"}],
-            "name": "updateAVSMetadataURI",
-            "outputs": [],
-            "stateMutability": "nonpayable",
-            "type": "function"
-        },
-        {
-            "inputs": [
-                {"internalType": "address", "name": "newImplementation", "type": "address"},
-                {"internalType": "bytes", "name": "data", "type": "bytes"}
-            ],
-            "name": "upgradeToAndCall",
-            "outputs": [],
-            "stateMutability": "payable",
-            "type": "function"
-        }
-    ]"#
+        function decimals() external view returns (uint8)
+        function symbol() external view returns (string)
+    ]"#,
 );
 
 // Re-export the automatically generated SignatureWithSaltAndExpiry type
-pub use eigen_layer_restaking::SignatureWithSaltAndExpiry;
\ No newline at end of file
+pub use eigen_layer_restaking::SignatureWithSaltAndExpiry;
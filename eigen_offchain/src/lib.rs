@@ -1,21 +1,35 @@
 mod abi;
-use crate::abi::{EigenLayerRestaking};
+pub mod multicall;
+pub mod output;
+pub mod signature;
+pub mod tx;
+pub mod upgrade;
+use crate::abi::{EigenLayerRestaking, EigenLayerRestakingErrors, EigenLayerRestakingEvents, ERC20};
 // use crate::abi::SignatureWithSaltAndExpiry;
 use crate::abi::SignatureWithSaltAndExpiry;
+use crate::output::{OutputFormat, TokenAmount};
+use crate::tx::{SendMode, WriteResult, DEFAULT_CONFIRMATIONS};
+use crate::upgrade::UpgradePlan;
 use anyhow::Result;
 use ethers::{
     prelude::*,
-    types::{Address, Bytes, H256, U256},
+    types::{Address, Bytes, U256},
     middleware::SignerMiddleware,
 };
-use std::{env, sync::Arc, str::FromStr};
+use std::{env, sync::Arc};
 
 // mod abi;
 // Import the type directly from the module
 // use crate::abi::EigenLayerRestaking;
 
+type SignerClient = SignerMiddleware<Provider<Http>, LocalWallet>;
+
 pub struct EigenLayerClient {
-    contract: EigenLayerRestaking<SignerMiddleware<Provider<Http>, LocalWallet>>,
+    contract: EigenLayerRestaking<SignerClient>,
+    confirmations: usize,
+    mode: SendMode,
+    sender: Option<Address>,
+    output: OutputFormat,
 }
 
 impl EigenLayerClient {
@@ -42,7 +56,118 @@ impl EigenLayerClient {
             Arc::new(client),
         );
 
-        Ok(Self { contract })
+        Ok(Self {
+            contract,
+            confirmations: DEFAULT_CONFIRMATIONS,
+            mode: SendMode::Broadcast,
+            sender: None,
+            output: OutputFormat::Plain,
+        })
+    }
+
+    // Number of confirmations write calls wait for (0 returns right after submission)
+    pub fn with_confirmations(mut self, confirmations: usize) -> Self {
+        self.confirmations = confirmations;
+        self
+    }
+
+    // Whether write calls are broadcast, simulated or only encoded
+    pub fn with_mode(mut self, mode: SendMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    // Simulate or encode write calls as sent by another account (e.g. an owner Safe)
+    pub fn with_sender(mut self, sender: Option<Address>) -> Self {
+        self.sender = sender;
+        self
+    }
+
+    // How write call results are printed
+    pub fn with_output(mut self, output: OutputFormat) -> Self {
+        self.output = output;
+        self
+    }
+
+    // Look up decimals and symbol of a token amount, for table output. Contracts
+    // that are not ERC-20 tokens (e.g. strategies) keep the raw amount.
+    pub async fn token_amount(&self, token: Address, amount: U256) -> TokenAmount {
+        let mut value = TokenAmount::new(token, amount);
+        if self.output == OutputFormat::Table {
+            let erc20 = ERC20::new(token, self.contract.client());
+            value.decimals = erc20.decimals().call().await.ok();
+            value.symbol = erc20.symbol().call().await.ok();
+        }
+        value
+    }
+
+    // Look up decimals and symbol of several token amounts in batched calls at
+    // `block`, for table output. Tokens that do not answer keep the raw amount.
+    pub async fn token_amounts(
+        &self,
+        amounts: Vec<(Address, U256)>,
+        block: u64,
+    ) -> Result<Vec<TokenAmount>> {
+        let mut values: Vec<TokenAmount> = amounts
+            .into_iter()
+            .map(|(token, amount)| TokenAmount::new(token, amount))
+            .collect();
+        if self.output == OutputFormat::Table {
+            let client = self.contract.client();
+            let tokens: Vec<_> = values
+                .iter()
+                .map(|value| ERC20::new(value.token, client.clone()))
+                .collect();
+            let decimals = multicall::call_all(
+                client.clone(),
+                block,
+                tokens.iter().map(|erc20| erc20.decimals()).collect(),
+            )
+            .await?;
+            let symbols = multicall::call_all(
+                client,
+                block,
+                tokens.iter().map(|erc20| erc20.symbol()).collect(),
+            )
+            .await?;
+            for ((value, decimals), symbol) in values.iter_mut().zip(decimals).zip(symbols) {
+                value.decimals = decimals;
+                value.symbol = symbol;
+            }
+        }
+        Ok(values)
+    }
+
+    // Latest block number, which listing commands read their snapshot at
+    pub async fn block_number(&self) -> Result<u64> {
+        Ok(self.contract.client().get_block_number().await?.as_u64())
+    }
+
+    // Run a write call in the configured mode and report the result
+    async fn send<D: ethers::abi::Detokenize + std::fmt::Debug>(
+        &self,
+        call: ContractCall<SignerClient, D>,
+        action: &str,
+    ) -> Result<WriteResult> {
+        let call = call.from(self.sender.unwrap_or_else(|| self.signer_address()));
+        let result = tx::execute::<_, _, EigenLayerRestakingErrors, EigenLayerRestakingEvents>(
+            call,
+            self.mode,
+            self.confirmations,
+            self.signer().chain_id(),
+        )
+        .await?;
+        tx::report(action, self.mode, self.output, result)
+    }
+
+    // Get the configured signer
+    pub fn signer(&self) -> &LocalWallet {
+        self.contract.client_ref().signer()
+    }
+
+    // Get the address of the configured signer
+    pub fn signer_address(&self) -> Address {
+        self.signer().address()
     }
 
     // Get the interface version
@@ -56,19 +181,15 @@ impl EigenLayerClient {
     }
 
     // Deregister an operator from AVS
-    pub async fn deregister_operator_from_avs(&self, operator: Address) -> Result<()> {
+    pub async fn deregister_operator_from_avs(&self, operator: Address) -> Result<WriteResult> {
         let tx = self.contract.deregister_operator_from_avs(operator);
-        let pending_tx = tx.send().await?;
-        println!("Operator deregistered: {:?}", pending_tx.tx_hash());
-        Ok(())
+        self.send(tx, "Operator deregistered").await
     }
 
     // Deregister a strategy
-    pub async fn deregister_strategy(&self, strategy: Address) -> Result<()> {
+    pub async fn deregister_strategy(&self, strategy: Address) -> Result<WriteResult> {
         let tx = self.contract.deregister_strategy(strategy);
-        let pending_tx = tx.send().await?;
-        println!("Strategy deregistered: {:?}", pending_tx.tx_hash());
-        Ok(())
+        self.send(tx, "Strategy deregistered").await
     }
 
     // Get current period
@@ -89,7 +210,7 @@ impl EigenLayerClient {
         timestamp: u64,
     ) -> Result<U256> {
         Ok(self.contract
-            .get_operator_stake_at(operator, collateral, timestamp as u64)
+            .get_operator_stake_at(operator, collateral, timestamp)
             .call()
             .await?)
     }
@@ -106,13 +227,15 @@ impl EigenLayerClient {
             .await?)
     }
 
-    // Get provider collateral tokens
+    // Get provider collateral tokens as of `block`
     pub async fn get_provider_collateral_tokens(
         &self,
         operator: Address,
+        block: u64,
     ) -> Result<(Vec<Address>, Vec<U256>)> {
         Ok(self.contract
             .get_provider_collateral_tokens(operator)
+            .block(block)
             .call()
             .await?)
     }
@@ -122,9 +245,9 @@ impl EigenLayerClient {
         Ok(self.contract.get_restakeable_strategies().call().await?)
     }
 
-    // Get whitelisted strategies
-    pub async fn get_whitelisted_strategies(&self) -> Result<Vec<Address>> {
-        Ok(self.contract.get_whitelisted_strategies().call().await?)
+    // Get whitelisted strategies as of `block`
+    pub async fn get_whitelisted_strategies(&self, block: u64) -> Result<Vec<Address>> {
+        Ok(self.contract.get_whitelisted_strategies().block(block).call().await?)
     }
 
     // Initialize the contract
@@ -136,7 +259,7 @@ impl EigenLayerClient {
         eigenlayer_delegation_manager: Address,
         eigenlayer_strategy_manager: Address,
         restaking_helper: Address,
-    ) -> Result<()> {
+    ) -> Result<WriteResult> {
         let tx = self.contract.initialize(
             owner,
             parameters,
@@ -145,9 +268,7 @@ impl EigenLayerClient {
             eigenlayer_strategy_manager,
             restaking_helper,
         );
-        let pending_tx = tx.send().await?;
-        println!("Contract initialized: {:?}", pending_tx.tx_hash());
-        Ok(())
+        self.send(tx, "Contract initialized").await
     }
 
     // Check if a strategy is enabled
@@ -155,17 +276,35 @@ impl EigenLayerClient {
         Ok(self.contract.is_strategy_enabled(strategy).call().await?)
     }
 
+    // Check if several strategies are enabled in batched calls at `block`
+    pub async fn are_strategies_enabled(
+        &self,
+        strategies: &[Address],
+        block: u64,
+    ) -> Result<Vec<bool>> {
+        let calls = strategies
+            .iter()
+            .map(|strategy| self.contract.is_strategy_enabled(*strategy))
+            .collect();
+        multicall::call_all(self.contract.client(), block, calls)
+            .await?
+            .into_iter()
+            .zip(strategies)
+            .map(|(enabled, strategy)| {
+                enabled.ok_or_else(|| anyhow::anyhow!("isStrategyEnabled reverted for {:?}", strategy))
+            })
+            .collect()
+    }
+
     // Get the contract owner
     pub async fn get_owner(&self) -> Result<Address> {
         Ok(self.contract.owner().call().await?)
     }
 
     // Pause strategy
-    pub async fn pause_strategy(&self) -> Result<()> {
+    pub async fn pause_strategy(&self) -> Result<WriteResult> {
         let tx = self.contract.pause_strategy();
-        let pending_tx = tx.send().await?;
-        println!("Strategy paused: {:?}", pending_tx.tx_hash());
-        Ok(())
+        self.send(tx, "Strategy paused").await
     }
 
     // Get proxiable UUID
@@ -182,7 +321,7 @@ impl EigenLayerClient {
         signature: Vec<u8>,
         salt: [u8; 32],
         expiry: U256,
-    ) -> Result<()> {
+    ) -> Result<WriteResult> {
         let operator_signature = SignatureWithSaltAndExpiry {
             signature: Bytes::from(signature),
             salt,
@@ -190,9 +329,7 @@ impl EigenLayerClient {
         };
 
         let tx = self.contract.register_operator(rpc, rpc1, rpc2, operator_signature);
-        let pending_tx = tx.send().await?;
-        println!("Operator registered: {:?}", pending_tx.tx_hash());
-        Ok(())
+        self.send(tx, "Operator registered").await
     }
 
     // Register operator to AVS
@@ -202,7 +339,7 @@ impl EigenLayerClient {
         signature: Vec<u8>,
         salt: [u8; 32],
         expiry: U256,
-    ) -> Result<()> {
+    ) -> Result<WriteResult> {
         let operator_signature = SignatureWithSaltAndExpiry {
             signature: Bytes::from(signature),
             salt,
@@ -210,25 +347,19 @@ impl EigenLayerClient {
         };
 
         let tx = self.contract.register_operator_to_avs(operator, operator_signature);
-        let pending_tx = tx.send().await?;
-        println!("Operator registered to AVS: {:?}", pending_tx.tx_hash());
-        Ok(())
+        self.send(tx, "Operator registered to AVS").await
     }
 
     // Register strategy
-    pub async fn register_strategy(&self, strategy: Address) -> Result<()> {
+    pub async fn register_strategy(&self, strategy: Address) -> Result<WriteResult> {
         let tx = self.contract.register_strategy(strategy);
-        let pending_tx = tx.send().await?;
-        println!("Strategy registered: {:?}", pending_tx.tx_hash());
-        Ok(())
+        self.send(tx, "Strategy registered").await
     }
 
     // Renounce ownership
-    pub async fn renounce_ownership(&self) -> Result<()> {
+    pub async fn renounce_ownership(&self) -> Result<WriteResult> {
         let tx = self.contract.renounce_ownership();
-        let pending_tx = tx.send().await?;
-        println!("Ownership renounced: {:?}", pending_tx.tx_hash());
-        Ok(())
+        self.send(tx, "Ownership renounced").await
     }
 
     // Get restaking helper address
@@ -237,43 +368,58 @@ impl EigenLayerClient {
     }
 
     // Transfer ownership
-    pub async fn transfer_ownership(&self, new_owner: Address) -> Result<()> {
+    pub async fn transfer_ownership(&self, new_owner: Address) -> Result<WriteResult> {
         let tx = self.contract.transfer_ownership(new_owner);
-        let pending_tx = tx.send().await?;
-        println!("Ownership transferred: {:?}", pending_tx.tx_hash());
-        Ok(())
+        self.send(tx, "Ownership transferred").await
     }
 
     // Unpause strategy
-    pub async fn unpause_strategy(&self) -> Result<()> {
+    pub async fn unpause_strategy(&self) -> Result<WriteResult> {
         let tx = self.contract.unpause_strategy();
-        let pending_tx = tx.send().await?;
-        println!("Strategy unpaused: {:?}", pending_tx.tx_hash());
-        Ok(())
+        self.send(tx, "Strategy unpaused").await
     }
 
     // Update AVS metadata URI
-    pub async fn update_avs_metadata_uri(&self, metadata_uri: String) -> Result<()> {
+    pub async fn update_avs_metadata_uri(&self, metadata_uri: String) -> Result<WriteResult> {
         let tx = self.contract.update_avs_metadata_uri(metadata_uri);
-        let pending_tx = tx.send().await?;
-        println!("AVS metadata URI updated: {:?}", pending_tx.tx_hash());
-        Ok(())
+        self.send(tx, "AVS metadata URI updated").await
     }
 
-    // Upgrade to new implementation
-    pub async fn upgrade_to_and_call(
+    // Check and simulate an upgrade of `proxy` (this contract by default)
+    pub async fn plan_upgrade(
         &self,
+        proxy: Option<Address>,
         new_implementation: Address,
         data: Vec<u8>,
-    ) -> Result<()> {
-        let tx = self.contract.upgrade_to_and_call(
+    ) -> Result<UpgradePlan> {
+        upgrade::plan_upgrade(
+            self.contract.client(),
+            proxy.unwrap_or_else(|| self.contract.address()),
             new_implementation,
             Bytes::from(data),
-        );
-        
-        // Note: This is a payable function, you might need to specify value
-        let pending_tx = tx.send().await?;
-        println!("Contract upgraded: {:?}", pending_tx.tx_hash());
-        Ok(())
+        )
+        .await
+    }
+
+    // Run a checked upgrade plan in the configured mode
+    pub async fn execute_upgrade(&self, plan: &UpgradePlan) -> Result<WriteResult> {
+        let result = match self.mode {
+            SendMode::Broadcast => WriteResult::Sent(
+                upgrade::execute_upgrade(self.contract.client(), plan, self.confirmations).await?,
+            ),
+            SendMode::DryRun => WriteResult::Simulated(plan.simulation()),
+            SendMode::CalldataOnly | SendMode::Safe => WriteResult::Prepared(plan.prepared()),
+        };
+        tx::report("Contract upgraded", self.mode, self.output, result)
+    }
+
+    // Upgrade to new implementation, after checking and simulating it
+    pub async fn upgrade_to_and_call(
+        &self,
+        new_implementation: Address,
+        data: Vec<u8>,
+    ) -> Result<WriteResult> {
+        let plan = self.plan_upgrade(None, new_implementation, data).await?;
+        self.execute_upgrade(&plan).await
     }
 }
\ No newline at end of file
@@ -1,19 +1,48 @@
-mod abi;
-// mod lib;
-
+use eigen_offchain::signature::{
+    sign_operator_avs_registration, RegistrationParams, DEFAULT_SIGNATURE_EXPIRY_SECS,
+};
+use eigen_offchain::output::{
+    self, AddressList, AvsDirectory, CollateralBalance, CollateralTokens, InterfaceVersion,
+    OperatorStake, OperatorStrategies, OutputFormat, SignedRegistration, StrategyStatus,
+    StrategyStatuses,
+};
+use eigen_offchain::tx::{SendMode, DEFAULT_CONFIRMATIONS};
 use eigen_offchain::EigenLayerClient;
-use ethers::prelude::*;
 
 use anyhow::Result;
 use clap::{Parser, Subcommand};
 use ethers::types::{Address, H256, U256};
 
-use std::{str::FromStr, sync::Arc};
+use std::str::FromStr;
 
 #[derive(Parser)]
 #[command(name = "EigenLayer Restaking CLI")]
 #[command(version = "1.0")]
 struct Cli {
+    /// Output format of command results
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Plain)]
+    output: OutputFormat,
+
+    /// Confirmations to wait for after sending a transaction (0 to only submit it)
+    #[arg(long, global = true, default_value_t = DEFAULT_CONFIRMATIONS)]
+    confirmations: usize,
+
+    /// Simulate write commands with eth_call and eth_estimateGas instead of broadcasting
+    #[arg(long, global = true, conflicts_with_all = ["calldata_only", "safe"])]
+    dry_run: bool,
+
+    /// Print target, value and calldata of write commands instead of broadcasting
+    #[arg(long, global = true, conflicts_with = "safe")]
+    calldata_only: bool,
+
+    /// Print write commands as a Safe transaction-builder batch instead of broadcasting
+    #[arg(long, global = true)]
+    safe: bool,
+
+    /// Sender to simulate or encode write commands for (e.g. the owner Safe)
+    #[arg(long, global = true)]
+    from: Option<String>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -28,7 +57,11 @@ enum Commands {
     
     
     /// Get whitelisted strategies
-    GetWhitelistedStrategies,
+    GetWhitelistedStrategies {
+        /// Also show whether each strategy is enabled, read at the same block
+        #[arg(long)]
+        status: bool,
+    },
     
     /// Get restakeable strategies
     GetRestakeableStrategies,
@@ -71,22 +104,53 @@ enum Commands {
         strategy: String,
     },
     
-    /// Register an operator
+    /// Register an operator (the signature is generated unless all of signature, salt and expiry are given)
     RegisterOperator {
         rpc: String,
         rpc1: String,
         rpc2: String,
-        signature: String,
-        salt: String,
-        expiry: String,
+        #[arg(long)]
+        signature: Option<String>,
+        #[arg(long)]
+        salt: Option<String>,
+        #[arg(long)]
+        expiry: Option<String>,
+        /// Lifetime of a generated signature in seconds
+        #[arg(long, default_value_t = DEFAULT_SIGNATURE_EXPIRY_SECS)]
+        expiry_secs: u64,
     },
     
-    /// Register an operator to AVS
+    /// Register an operator to AVS (the signature is generated unless all of signature, salt and expiry are given)
     RegisterOperatorToAVS {
         operator: String,
-        signature: String,
-        salt: String,
-        expiry: String,
+        #[arg(long)]
+        signature: Option<String>,
+        #[arg(long)]
+        salt: Option<String>,
+        #[arg(long)]
+        expiry: Option<String>,
+        /// Lifetime of a generated signature in seconds
+        #[arg(long, default_value_t = DEFAULT_SIGNATURE_EXPIRY_SECS)]
+        expiry_secs: u64,
+    },
+
+    /// Sign an operator AVS registration and print the SignatureWithSaltAndExpiry without sending it.
+    /// Works fully offline when the AVS directory, AVS, chain id and expiry are all given.
+    SignAvsRegistration {
+        /// Lifetime of the signature in seconds
+        #[arg(long, default_value_t = DEFAULT_SIGNATURE_EXPIRY_SECS)]
+        expiry_secs: u64,
+        #[arg(long)]
+        avs_directory: Option<String>,
+        #[arg(long)]
+        avs: Option<String>,
+        #[arg(long)]
+        chain_id: Option<u64>,
+        /// Absolute expiry timestamp
+        #[arg(long)]
+        expiry: Option<String>,
+        #[arg(long)]
+        salt: Option<String>,
     },
     
     /// Deregister an operator from AVS
@@ -110,6 +174,17 @@ enum Commands {
         new_owner: String,
     },
     
+    /// Upgrade a UUPS proxy after checking the new implementation and simulating the call
+    Upgrade {
+        new_implementation: String,
+        /// Proxy to upgrade (defaults to CONTRACT_ADDRESS); any of the middleware proxies works
+        #[arg(long)]
+        proxy: Option<String>,
+        /// Calldata of the initializer to run after the upgrade
+        #[arg(long)]
+        init_data: Option<String>,
+    },
+
     /// Initialize the contract
     Initialize {
         owner: String,
@@ -128,17 +203,35 @@ async fn main() -> Result<()> {
     let cli = Cli::parse();
 
     let contract_address = dotenv::var("CONTRACT_ADDRESS")?.parse()?;
-    let client = EigenLayerClient::new(contract_address)?;
+    let mode = if cli.dry_run {
+        SendMode::DryRun
+    } else if cli.calldata_only {
+        SendMode::CalldataOnly
+    } else if cli.safe {
+        SendMode::Safe
+    } else {
+        SendMode::Broadcast
+    };
+    let sender = cli.from.as_deref().map(Address::from_str).transpose()?;
+    if sender.is_some() && mode == SendMode::Broadcast {
+        anyhow::bail!("--from only applies with --dry-run, --calldata-only or --safe");
+    }
+
+    let client = EigenLayerClient::new(contract_address)?
+        .with_confirmations(cli.confirmations)
+        .with_mode(mode)
+        .with_sender(sender)
+        .with_output(cli.output);
 
     match cli.command {
         Commands::GetInterfaceVersion => {
             let version = client.get_upgrade_interface_version().await?;
-            println!("Interface Version: {}", version);
+            output::print(cli.output, &InterfaceVersion { version })?;
         }
         
         Commands::GetAvsDirectory => {
-            let directory = client.get_avs_directory().await?;
-            println!("AVS Directory: {}", directory);
+            let avs_directory = client.get_avs_directory().await?;
+            output::print(cli.output, &AvsDirectory { avs_directory })?;
         }
         
         // Commands::GetCurrentPeriod => {
@@ -146,156 +239,213 @@ async fn main() -> Result<()> {
         //     println!("Current Period: {}", period);
         // }
         
-        Commands::GetWhitelistedStrategies => {
-            let strategies = client.get_whitelisted_strategies().await?;
-            println!("Whitelisted Strategies:");
-            for (i, strategy) in strategies.iter().enumerate() {
-                println!("  {}: {}", i + 1, strategy);
+        Commands::GetWhitelistedStrategies { status } => {
+            let block = client.block_number().await?;
+            let strategies = client.get_whitelisted_strategies(block).await?;
+            if status {
+                let enabled = client.are_strategies_enabled(&strategies, block).await?;
+                output::print(cli.output, &StrategyStatuses {
+                    block,
+                    strategies: strategies
+                        .into_iter()
+                        .zip(enabled)
+                        .map(|(strategy, enabled)| StrategyStatus { strategy, enabled })
+                        .collect(),
+                })?;
+            } else {
+                output::print(cli.output, &AddressList {
+                    title: "Whitelisted Strategies",
+                    key: "strategies",
+                    column: "strategy",
+                    addresses: strategies,
+                })?;
             }
         }
         
         Commands::GetRestakeableStrategies => {
             let strategies = client.get_restakeable_strategies().await?;
-            println!("Restakeable Strategies:");
-            for (i, strategy) in strategies.iter().enumerate() {
-                println!("  {}: {}", i + 1, strategy);
-            }
+            output::print(cli.output, &AddressList {
+                title: "Restakeable Strategies",
+                key: "strategies",
+                column: "strategy",
+                addresses: strategies,
+            })?;
         }
         
         Commands::GetProviderCollateral { operator, collateral } => {
-            let amount = client
-                .get_provider_collateral(
-                    Address::from_str(&operator)?,
-                    Address::from_str(&collateral)?,
-                )
-                .await?;
-            println!("Provider Collateral: {}", amount);
+            let operator = Address::from_str(&operator)?;
+            let collateral = Address::from_str(&collateral)?;
+            let amount = client.get_provider_collateral(operator, collateral).await?;
+            output::print(cli.output, &CollateralBalance {
+                operator,
+                collateral: client.token_amount(collateral, amount).await,
+            })?;
         }
         
         Commands::GetProviderCollateralTokens { operator } => {
-            let (tokens, amounts) = client
-                .get_provider_collateral_tokens(Address::from_str(&operator)?)
-                .await?;
-            
-            println!("Provider Collateral Tokens:");
-            for (i, (token, amount)) in tokens.iter().zip(amounts.iter()).enumerate() {
-                println!("  {}: {} - {}", i + 1, token, amount);
-            }
+            let operator = Address::from_str(&operator)?;
+            let block = client.block_number().await?;
+            let (tokens, amounts) = client.get_provider_collateral_tokens(operator, block).await?;
+
+            output::print(cli.output, &CollateralTokens {
+                operator,
+                block,
+                tokens: client
+                    .token_amounts(tokens.into_iter().zip(amounts).collect(), block)
+                    .await?,
+            })?;
         }
         
         Commands::GetOperatorRestakedStrategies { operator } => {
-            let strategies = client
-                .get_operator_restaked_strategies(Address::from_str(&operator)?)
-                .await?;
-            
-            println!("Operator Restaked Strategies:");
-            for (i, strategy) in strategies.iter().enumerate() {
-                println!("  {}: {}", i + 1, strategy);
-            }
+            let operator = Address::from_str(&operator)?;
+            let strategies = client.get_operator_restaked_strategies(operator).await?;
+            output::print(cli.output, &OperatorStrategies { operator, strategies })?;
         }
         
         Commands::GetOperatorStakeAt { operator, collateral, timestamp } => {
+            let operator = Address::from_str(&operator)?;
+            let collateral = Address::from_str(&collateral)?;
             let amount = client
-                .get_operator_stake_at(
-                    Address::from_str(&operator)?,
-                    Address::from_str(&collateral)?,
-                    timestamp,
-                )
+                .get_operator_stake_at(operator, collateral, timestamp)
                 .await?;
-            println!("Operator Stake: {}", amount);
+            output::print(cli.output, &OperatorStake {
+                operator,
+                timestamp,
+                stake: client.token_amount(collateral, amount).await,
+            })?;
         }
         
         Commands::IsStrategyEnabled { strategy } => {
-            let enabled = client
-                .is_strategy_enabled(Address::from_str(&strategy)?)
-                .await?;
-            println!("Strategy Enabled: {}", enabled);
+            let strategy = Address::from_str(&strategy)?;
+            let enabled = client.is_strategy_enabled(strategy).await?;
+            output::print(cli.output, &StrategyStatus { strategy, enabled })?;
         }
         
         Commands::RegisterStrategy { strategy } => {
             client
                 .register_strategy(Address::from_str(&strategy)?)
                 .await?;
-            println!("Strategy registered successfully");
         }
         
         Commands::DeregisterStrategy { strategy } => {
             client
                 .deregister_strategy(Address::from_str(&strategy)?)
                 .await?;
-            println!("Strategy deregistered successfully");
         }
         
-        Commands::RegisterOperator { rpc, rpc1, rpc2, signature, salt, expiry } => {
-            // Parse the signature as hex string
-            let signature_bytes = hex::decode(signature.trim_start_matches("0x"))?;
-            
-            // Parse the salt as 32-byte array
-            let salt_bytes = H256::from_str(&salt)?;
-            let mut salt_array = [0u8; 32];
-            salt_array.copy_from_slice(salt_bytes.as_bytes());
-            
-            // Parse the expiry as U256
-            let expiry_value = U256::from_dec_str(&expiry)?;
-            
-            client
-                .register_operator(rpc, rpc1, rpc2, signature_bytes, salt_array, expiry_value)
-                .await?;
-            println!("Operator registered successfully");
+        Commands::RegisterOperator { rpc, rpc1, rpc2, signature, salt, expiry, expiry_secs } => {
+            match (signature, salt, expiry) {
+                (Some(signature), Some(salt), Some(expiry)) => {
+                    let (signature_bytes, salt_array, expiry_value) =
+                        parse_operator_signature(&signature, &salt, &expiry)?;
+                    client
+                        .register_operator(rpc, rpc1, rpc2, signature_bytes, salt_array, expiry_value)
+                        .await?;
+                }
+                (None, None, None) => {
+                    client
+                        .register_operator_signed(rpc, rpc1, rpc2, expiry_secs)
+                        .await?;
+                }
+                _ => anyhow::bail!("--signature, --salt and --expiry must be given together"),
+            }
         }
         
-        Commands::RegisterOperatorToAVS { operator, signature, salt, expiry } => {
-            // Parse the signature as hex string
-            let signature_bytes = hex::decode(signature.trim_start_matches("0x"))?;
-            
-            // Parse the salt as 32-byte array
-            let salt_bytes = H256::from_str(&salt)?;
-            let mut salt_array = [0u8; 32];
-            salt_array.copy_from_slice(salt_bytes.as_bytes());
-            
-            // Parse the expiry as U256
-            let expiry_value = U256::from_dec_str(&expiry)?;
-            
-            client
-                .register_operator_to_avs(
-                    Address::from_str(&operator)?,
-                    signature_bytes,
-                    salt_array,
-                    expiry_value
-                )
-                .await?;
-            println!("Operator registered to AVS successfully");
+        Commands::RegisterOperatorToAVS { operator, signature, salt, expiry, expiry_secs } => {
+            match (signature, salt, expiry) {
+                (Some(signature), Some(salt), Some(expiry)) => {
+                    let (signature_bytes, salt_array, expiry_value) =
+                        parse_operator_signature(&signature, &salt, &expiry)?;
+                    client
+                        .register_operator_to_avs(
+                            Address::from_str(&operator)?,
+                            signature_bytes,
+                            salt_array,
+                            expiry_value
+                        )
+                        .await?;
+                }
+                (None, None, None) => {
+                    if Address::from_str(&operator)? != client.signer_address() {
+                        anyhow::bail!("A signature can only be generated for the configured signer; pass --signature, --salt and --expiry for other operators");
+                    }
+                    client.register_operator_to_avs_signed(expiry_secs).await?;
+                }
+                _ => anyhow::bail!("--signature, --salt and --expiry must be given together"),
+            }
+        }
+
+        Commands::SignAvsRegistration { expiry_secs, avs_directory, avs, chain_id, expiry, salt } => {
+            let signature = match (avs_directory, avs, chain_id, expiry) {
+                (Some(avs_directory), Some(avs), Some(chain_id), Some(expiry)) => {
+                    let salt = match salt {
+                        Some(salt) => H256::from_str(&salt)?.to_fixed_bytes(),
+                        None => H256::random().to_fixed_bytes(),
+                    };
+                    let params = RegistrationParams {
+                        chain_id,
+                        avs_directory: Address::from_str(&avs_directory)?,
+                        operator: client.signer_address(),
+                        avs: Address::from_str(&avs)?,
+                        salt,
+                        expiry: U256::from_dec_str(&expiry)?,
+                    };
+                    sign_operator_avs_registration(client.signer(), &params)?
+                }
+                _ => client.sign_avs_registration(expiry_secs).await?,
+            };
+
+            output::print(cli.output, &SignedRegistration {
+                operator: client.signer_address(),
+                signature: signature.signature,
+                salt: H256::from(signature.salt),
+                expiry: signature.expiry,
+            })?;
         }
         
         Commands::DeregisterOperatorFromAVS { operator } => {
             client
                 .deregister_operator_from_avs(Address::from_str(&operator)?)
                 .await?;
-            println!("Operator deregistered from AVS successfully");
         }
         
         Commands::PauseStrategy => {
             client.pause_strategy().await?;
-            println!("Strategy paused successfully");
         }
         
         Commands::UnpauseStrategy => {
             client.unpause_strategy().await?;
-            println!("Strategy unpaused successfully");
         }
         
         Commands::UpdateAVSMetadataURI { metadata_uri } => {
             client.update_avs_metadata_uri(metadata_uri).await?;
-            println!("AVS metadata URI updated successfully");
         }
         
         Commands::TransferOwnership { new_owner } => {
             client
                 .transfer_ownership(Address::from_str(&new_owner)?)
                 .await?;
-            println!("Ownership transferred successfully");
         }
         
+        Commands::Upgrade { new_implementation, proxy, init_data } => {
+            let proxy = proxy.map(|p| Address::from_str(&p)).transpose()?;
+            let init_data = match init_data {
+                Some(data) => hex::decode(data.trim_start_matches("0x"))?,
+                None => Vec::new(),
+            };
+            let plan = client
+                .plan_upgrade(proxy, Address::from_str(&new_implementation)?, init_data)
+                .await?;
+
+            // JSON output only carries the result of the upgrade itself
+            if (mode == SendMode::Broadcast || mode == SendMode::DryRun)
+                && cli.output != OutputFormat::Json
+            {
+                output::print(cli.output, &plan)?;
+            }
+            client.execute_upgrade(&plan).await?;
+        }
+
         Commands::Initialize { owner, parameters, avs_directory, delegation_manager, strategy_manager, restaking_helper } => {
             client
                 .initialize(
@@ -307,9 +457,22 @@ async fn main() -> Result<()> {
                     Address::from_str(&restaking_helper)?,
                 )
                 .await?;
-            println!("Contract initialized successfully");
         }
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+// Parse a pre-computed SignatureWithSaltAndExpiry given on the command line
+fn parse_operator_signature(signature: &str, salt: &str, expiry: &str) -> Result<(Vec<u8>, [u8; 32], U256)> {
+    // Parse the signature as hex string
+    let signature_bytes = hex::decode(signature.trim_start_matches("0x"))?;
+
+    // Parse the salt as 32-byte array
+    let salt_array = H256::from_str(salt)?.to_fixed_bytes();
+
+    // Parse the expiry as U256
+    let expiry_value = U256::from_dec_str(expiry)?;
+
+    Ok((signature_bytes, salt_array, expiry_value))
+}
@@ -0,0 +1,387 @@
+use anyhow::Result;
+use ethers::{
+    types::{Address, Bytes, H256, U256},
+    utils::format_units,
+};
+use serde::{ser::SerializeMap, Serialize, Serializer};
+use std::fmt;
+
+/// How command results are printed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable lines
+    #[default]
+    Plain,
+    /// Pretty-printed JSON with stable field names
+    Json,
+    /// Aligned columns, with token amounts formatted using the token decimals
+    Table,
+}
+
+/// A command result that can be printed in every `OutputFormat`.
+pub trait Render: Serialize {
+    fn plain(&self) -> String;
+    fn table(&self) -> Table;
+}
+
+/// Print `value` in the requested format.
+pub fn print<T: Render>(format: OutputFormat, value: &T) -> Result<()> {
+    match format {
+        OutputFormat::Plain => println!("{}", value.plain()),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(value)?),
+        OutputFormat::Table => println!("{}", value.table()),
+    }
+    Ok(())
+}
+
+/// Serialize a `U256` as a decimal string instead of the default hex quantity.
+pub fn serialize_decimal<S: Serializer>(
+    value: &U256,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(&value.to_string())
+}
+
+/// Rows printed with aligned columns under a header.
+#[derive(Debug, Clone, Default)]
+pub struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(headers: &[&str]) -> Self {
+        Self {
+            headers: headers.iter().map(|h| h.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Two-column table of named fields.
+    pub fn fields(fields: Vec<(&str, String)>) -> Self {
+        let mut table = Self::new(&["field", "value"]);
+        for (name, value) in fields {
+            table.push(vec![name.to_string(), value]);
+        }
+        table
+    }
+
+    pub fn push(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut widths: Vec<usize> = self.headers.iter().map(|h| h.len()).collect();
+        for row in &self.rows {
+            for (i, cell) in row.iter().enumerate() {
+                if i < widths.len() {
+                    widths[i] = widths[i].max(cell.chars().count());
+                }
+            }
+        }
+
+        let line = |cells: &[String]| {
+            cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = *width))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        };
+
+        writeln!(f, "{}", line(&self.headers))?;
+        let separator: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+        write!(f, "{}", line(&separator))?;
+        for row in &self.rows {
+            write!(f, "\n{}", line(row))?;
+        }
+        Ok(())
+    }
+}
+
+/// An amount of a token. Decimals and symbol are only looked up for table
+/// output; JSON always carries the raw integer amount.
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenAmount {
+    pub token: Address,
+    #[serde(serialize_with = "serialize_decimal")]
+    pub amount: U256,
+    #[serde(skip)]
+    pub decimals: Option<u8>,
+    #[serde(skip)]
+    pub symbol: Option<String>,
+}
+
+impl TokenAmount {
+    pub fn new(token: Address, amount: U256) -> Self {
+        Self {
+            token,
+            amount,
+            decimals: None,
+            symbol: None,
+        }
+    }
+
+    /// The amount in whole tokens when the decimals are known, raw otherwise.
+    pub fn formatted(&self) -> String {
+        let amount = match self.decimals.map(|d| format_units(self.amount, d as u32)) {
+            Some(Ok(units)) if units.contains('.') => {
+                units.trim_end_matches('0').trim_end_matches('.').to_string()
+            }
+            Some(Ok(units)) => units,
+            _ => self.amount.to_string(),
+        };
+        match &self.symbol {
+            Some(symbol) => format!("{} {}", amount, symbol),
+            None => amount,
+        }
+    }
+}
+
+/// Balance of one collateral token held by an operator.
+#[derive(Debug, Clone, Serialize)]
+pub struct CollateralBalance {
+    pub operator: Address,
+    pub collateral: TokenAmount,
+}
+
+impl Render for CollateralBalance {
+    fn plain(&self) -> String {
+        format!("Collateral amount: {}", self.collateral.amount)
+    }
+
+    fn table(&self) -> Table {
+        Table::fields(vec![
+            ("operator", format!("{:?}", self.operator)),
+            ("collateral", format!("{:?}", self.collateral.token)),
+            ("amount", self.collateral.formatted()),
+        ])
+    }
+}
+
+/// Every collateral token of an operator with its balance.
+#[derive(Debug, Clone, Serialize)]
+pub struct CollateralTokens {
+    pub operator: Address,
+    /// Block the balances and token metadata were read at
+    pub block: u64,
+    pub tokens: Vec<TokenAmount>,
+}
+
+impl Render for CollateralTokens {
+    fn plain(&self) -> String {
+        let mut lines = vec![format!("Provider Collateral Tokens at block {}:", self.block)];
+        for (i, token) in self.tokens.iter().enumerate() {
+            lines.push(format!("  {}: {:?} - {}", i + 1, token.token, token.amount));
+        }
+        lines.join("\n")
+    }
+
+    fn table(&self) -> Table {
+        let mut table = Table::new(&["token", "amount"]);
+        for token in &self.tokens {
+            table.push(vec![format!("{:?}", token.token), token.formatted()]);
+        }
+        table
+    }
+}
+
+/// Stake of an operator in one collateral at a point in time.
+#[derive(Debug, Clone, Serialize)]
+pub struct OperatorStake {
+    pub operator: Address,
+    pub timestamp: u64,
+    pub stake: TokenAmount,
+}
+
+impl Render for OperatorStake {
+    fn plain(&self) -> String {
+        format!("Operator Stake: {}", self.stake.amount)
+    }
+
+    fn table(&self) -> Table {
+        Table::fields(vec![
+            ("operator", format!("{:?}", self.operator)),
+            ("collateral", format!("{:?}", self.stake.token)),
+            ("timestamp", self.timestamp.to_string()),
+            ("stake", self.stake.formatted()),
+        ])
+    }
+}
+
+/// A titled list of contract addresses (strategies, vaults, ...), serialized
+/// as `{ "<key>": [...] }`.
+#[derive(Debug, Clone)]
+pub struct AddressList {
+    pub title: &'static str,
+    pub key: &'static str,
+    pub column: &'static str,
+    pub addresses: Vec<Address>,
+}
+
+impl Serialize for AddressList {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(self.key, &self.addresses)?;
+        map.end()
+    }
+}
+
+impl Render for AddressList {
+    fn plain(&self) -> String {
+        let mut lines = vec![format!("{}:", self.title)];
+        for (i, address) in self.addresses.iter().enumerate() {
+            lines.push(format!("  {}: {:?}", i + 1, address));
+        }
+        lines.join("\n")
+    }
+
+    fn table(&self) -> Table {
+        let mut table = Table::new(&["#", self.column]);
+        for (i, address) in self.addresses.iter().enumerate() {
+            table.push(vec![(i + 1).to_string(), format!("{:?}", address)]);
+        }
+        table
+    }
+}
+
+/// Version string of the upgrade interface.
+#[derive(Debug, Clone, Serialize)]
+pub struct InterfaceVersion {
+    pub version: String,
+}
+
+impl Render for InterfaceVersion {
+    fn plain(&self) -> String {
+        format!("Interface Version: {}", self.version)
+    }
+
+    fn table(&self) -> Table {
+        Table::fields(vec![("version", self.version.clone())])
+    }
+}
+
+/// Address of the EigenLayer AVS directory the middleware uses.
+#[derive(Debug, Clone, Serialize)]
+pub struct AvsDirectory {
+    pub avs_directory: Address,
+}
+
+impl Render for AvsDirectory {
+    fn plain(&self) -> String {
+        format!("AVS Directory: {:?}", self.avs_directory)
+    }
+
+    fn table(&self) -> Table {
+        Table::fields(vec![("avs_directory", format!("{:?}", self.avs_directory))])
+    }
+}
+
+/// Strategies an operator has restaked in.
+#[derive(Debug, Clone, Serialize)]
+pub struct OperatorStrategies {
+    pub operator: Address,
+    pub strategies: Vec<Address>,
+}
+
+impl Render for OperatorStrategies {
+    fn plain(&self) -> String {
+        let mut lines = vec!["Operator Restaked Strategies:".to_string()];
+        for (i, strategy) in self.strategies.iter().enumerate() {
+            lines.push(format!("  {}: {:?}", i + 1, strategy));
+        }
+        lines.join("\n")
+    }
+
+    fn table(&self) -> Table {
+        let mut table = Table::new(&["operator", "strategy"]);
+        for strategy in &self.strategies {
+            table.push(vec![format!("{:?}", self.operator), format!("{:?}", strategy)]);
+        }
+        table
+    }
+}
+
+/// Whether a strategy is enabled in the middleware.
+#[derive(Debug, Clone, Serialize)]
+pub struct StrategyStatus {
+    pub strategy: Address,
+    pub enabled: bool,
+}
+
+impl Render for StrategyStatus {
+    fn plain(&self) -> String {
+        format!("Strategy Enabled: {}", self.enabled)
+    }
+
+    fn table(&self) -> Table {
+        Table::fields(vec![
+            ("strategy", format!("{:?}", self.strategy)),
+            ("enabled", self.enabled.to_string()),
+        ])
+    }
+}
+
+/// Whitelisted strategies with their status, all read at the same block.
+#[derive(Debug, Clone, Serialize)]
+pub struct StrategyStatuses {
+    pub block: u64,
+    pub strategies: Vec<StrategyStatus>,
+}
+
+impl Render for StrategyStatuses {
+    fn plain(&self) -> String {
+        let mut lines = vec![format!("Whitelisted Strategies at block {}:", self.block)];
+        for (i, strategy) in self.strategies.iter().enumerate() {
+            let status = if strategy.enabled { "enabled" } else { "disabled" };
+            lines.push(format!("  {}: {:?} ({})", i + 1, strategy.strategy, status));
+        }
+        lines.join("\n")
+    }
+
+    fn table(&self) -> Table {
+        let mut table = Table::new(&["#", "strategy", "enabled"]);
+        for (i, strategy) in self.strategies.iter().enumerate() {
+            table.push(vec![
+                (i + 1).to_string(),
+                format!("{:?}", strategy.strategy),
+                strategy.enabled.to_string(),
+            ]);
+        }
+        table
+    }
+}
+
+/// An operator signature for `registerOperatorToAVS`.
+#[derive(Debug, Clone, Serialize)]
+pub struct SignedRegistration {
+    pub operator: Address,
+    pub signature: Bytes,
+    pub salt: H256,
+    #[serde(serialize_with = "serialize_decimal")]
+    pub expiry: U256,
+}
+
+impl Render for SignedRegistration {
+    fn plain(&self) -> String {
+        format!(
+            "SignatureWithSaltAndExpiry:\n  signature: 0x{}\n  salt: {:?}\n  expiry: {}",
+            hex::encode(&self.signature),
+            self.salt,
+            self.expiry
+        )
+    }
+
+    fn table(&self) -> Table {
+        Table::fields(vec![
+            ("operator", format!("{:?}", self.operator)),
+            ("signature", format!("0x{}", hex::encode(&self.signature))),
+            ("salt", format!("{:?}", self.salt)),
+            ("expiry", self.expiry.to_string()),
+        ])
+    }
+}
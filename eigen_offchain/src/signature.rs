@@ -0,0 +1,286 @@
+use anyhow::Result;
+use ethers::{
+    abi::{encode, Token},
+    prelude::*,
+    types::{Address, Bytes, H256, U256},
+    utils::keccak256,
+};
+
+use crate::abi::{SignatureWithSaltAndExpiry, AVSDirectory};
+use crate::tx::WriteResult;
+use crate::EigenLayerClient;
+
+/// Default lifetime of a generated registration signature, in seconds.
+pub const DEFAULT_SIGNATURE_EXPIRY_SECS: u64 = 3600;
+
+const DOMAIN_TYPEHASH: &str = "EIP712Domain(string name,uint256 chainId,address verifyingContract)";
+const OPERATOR_AVS_REGISTRATION_TYPEHASH: &str =
+    "OperatorAVSRegistration(address operator,address avs,bytes32 salt,uint256 expiry)";
+
+/// Everything `AVSDirectory.registerOperatorToAVS` needs to check an operator signature.
+#[derive(Debug, Clone, Copy)]
+pub struct RegistrationParams {
+    pub chain_id: u64,
+    pub avs_directory: Address,
+    pub operator: Address,
+    pub avs: Address,
+    pub salt: [u8; 32],
+    pub expiry: U256,
+}
+
+/// Digest signed by the operator, computed exactly as
+/// `AVSDirectory.calculateOperatorAVSRegistrationDigestHash` does.
+pub fn operator_avs_registration_digest(params: &RegistrationParams) -> [u8; 32] {
+    let domain_separator = keccak256(encode(&[
+        Token::FixedBytes(keccak256(DOMAIN_TYPEHASH).to_vec()),
+        Token::FixedBytes(keccak256("EigenLayer").to_vec()),
+        Token::Uint(params.chain_id.into()),
+        Token::Address(params.avs_directory),
+    ]));
+
+    let struct_hash = keccak256(encode(&[
+        Token::FixedBytes(keccak256(OPERATOR_AVS_REGISTRATION_TYPEHASH).to_vec()),
+        Token::Address(params.operator),
+        Token::Address(params.avs),
+        Token::FixedBytes(params.salt.to_vec()),
+        Token::Uint(params.expiry),
+    ]));
+
+    let mut preimage = Vec::with_capacity(66);
+    preimage.extend_from_slice(b"\x19\x01");
+    preimage.extend_from_slice(&domain_separator);
+    preimage.extend_from_slice(&struct_hash);
+    keccak256(preimage)
+}
+
+/// Sign the registration digest with the operator key, without any network access.
+pub fn sign_operator_avs_registration(
+    wallet: &LocalWallet,
+    params: &RegistrationParams,
+) -> Result<SignatureWithSaltAndExpiry> {
+    if wallet.address() != params.operator {
+        anyhow::bail!(
+            "Registration must be signed by the operator {:?}, but the configured signer is {:?}",
+            params.operator,
+            wallet.address()
+        );
+    }
+
+    let digest = operator_avs_registration_digest(params);
+    let signature = wallet.sign_hash(H256::from(digest))?;
+
+    Ok(SignatureWithSaltAndExpiry {
+        signature: Bytes::from(signature.to_vec()),
+        salt: params.salt,
+        expiry: params.expiry,
+    })
+}
+
+impl EigenLayerClient {
+    // Collect the registration parameters from chain: the AVS directory, the
+    // AVS (the restaking helper, which is the account calling the directory),
+    // an unused random salt and an expiry relative to the latest block
+    pub async fn avs_registration_params(
+        &self,
+        operator: Address,
+        expiry_secs: u64,
+    ) -> Result<RegistrationParams> {
+        let client = self.contract.client();
+        let avs_directory = self.get_avs_directory().await?;
+        let avs = self.get_restaking_helper().await?;
+        let chain_id = client.get_chainid().await?.as_u64();
+
+        let directory = AVSDirectory::new(avs_directory, client.clone());
+        let salt = loop {
+            let salt = H256::random().to_fixed_bytes();
+            if !directory.operator_salt_is_spent(operator, salt).call().await? {
+                break salt;
+            }
+        };
+
+        let now = client
+            .get_block(BlockNumber::Latest)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Latest block not found"))?
+            .timestamp;
+
+        let params = RegistrationParams {
+            chain_id,
+            avs_directory,
+            operator,
+            avs,
+            salt,
+            expiry: now + U256::from(expiry_secs),
+        };
+
+        // Cross-check the local digest against the directory's own computation
+        let expected = directory
+            .calculate_operator_avs_registration_digest_hash(operator, avs, salt, params.expiry)
+            .call()
+            .await?;
+        if expected != operator_avs_registration_digest(&params) {
+            anyhow::bail!("Local registration digest does not match the AVS directory");
+        }
+
+        Ok(params)
+    }
+
+    // Build a signed SignatureWithSaltAndExpiry for the configured signer
+    pub async fn sign_avs_registration(&self, expiry_secs: u64) -> Result<SignatureWithSaltAndExpiry> {
+        let wallet = self.contract.client().signer().clone();
+        let params = self
+            .avs_registration_params(wallet.address(), expiry_secs)
+            .await?;
+        sign_operator_avs_registration(&wallet, &params)
+    }
+
+    // Register the configured signer as operator with a freshly generated signature
+    pub async fn register_operator_signed(
+        &self,
+        rpc: String,
+        rpc1: String,
+        rpc2: String,
+        expiry_secs: u64,
+    ) -> Result<WriteResult> {
+        let signature = self.sign_avs_registration(expiry_secs).await?;
+        self.register_operator(
+            rpc,
+            rpc1,
+            rpc2,
+            signature.signature.to_vec(),
+            signature.salt,
+            signature.expiry,
+        )
+        .await
+    }
+
+    // Register the configured signer to the AVS with a freshly generated signature
+    pub async fn register_operator_to_avs_signed(&self, expiry_secs: u64) -> Result<WriteResult> {
+        let operator = self.contract.client().address();
+        let signature = self.sign_avs_registration(expiry_secs).await?;
+        self.register_operator_to_avs(
+            operator,
+            signature.signature.to_vec(),
+            signature.salt,
+            signature.expiry,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::{
+        transaction::eip712::{Eip712, TypedData},
+        Signature,
+    };
+    use serde_json::json;
+
+    fn wallet(n: u64) -> LocalWallet {
+        format!("{n:064x}").parse().unwrap()
+    }
+
+    fn params(operator: Address) -> RegistrationParams {
+        RegistrationParams {
+            chain_id: 17000,
+            avs_directory: Address::repeat_byte(0xd1),
+            operator,
+            avs: Address::repeat_byte(0xa5),
+            salt: [7; 32],
+            expiry: U256::from(1_700_000_000u64),
+        }
+    }
+
+    #[test]
+    fn digest_matches_eip712_typed_data() {
+        let params = params(wallet(1).address());
+        let typed: TypedData = serde_json::from_value(json!({
+            "types": {
+                "EIP712Domain": [
+                    { "name": "name", "type": "string" },
+                    { "name": "chainId", "type": "uint256" },
+                    { "name": "verifyingContract", "type": "address" },
+                ],
+                "OperatorAVSRegistration": [
+                    { "name": "operator", "type": "address" },
+                    { "name": "avs", "type": "address" },
+                    { "name": "salt", "type": "bytes32" },
+                    { "name": "expiry", "type": "uint256" },
+                ],
+            },
+            "primaryType": "OperatorAVSRegistration",
+            "domain": {
+                "name": "EigenLayer",
+                "chainId": params.chain_id,
+                "verifyingContract": format!("{:?}", params.avs_directory),
+            },
+            "message": {
+                "operator": format!("{:?}", params.operator),
+                "avs": format!("{:?}", params.avs),
+                "salt": format!("0x{}", hex::encode(params.salt)),
+                "expiry": params.expiry.to_string(),
+            },
+        }))
+        .unwrap();
+
+        assert_eq!(
+            operator_avs_registration_digest(&params),
+            typed.encode_eip712().unwrap()
+        );
+    }
+
+    #[test]
+    fn digest_binds_every_field() {
+        let base = params(wallet(1).address());
+        let digest = operator_avs_registration_digest(&base);
+        let changed = [
+            RegistrationParams {
+                chain_id: 1,
+                ..base
+            },
+            RegistrationParams {
+                avs_directory: Address::repeat_byte(1),
+                ..base
+            },
+            RegistrationParams {
+                operator: wallet(2).address(),
+                ..base
+            },
+            RegistrationParams {
+                avs: Address::repeat_byte(2),
+                ..base
+            },
+            RegistrationParams {
+                salt: [8; 32],
+                ..base
+            },
+            RegistrationParams {
+                expiry: base.expiry + 1,
+                ..base
+            },
+        ];
+        for params in changed {
+            assert_ne!(operator_avs_registration_digest(&params), digest);
+        }
+    }
+
+    #[test]
+    fn signature_recovers_to_the_operator() {
+        let wallet = wallet(1);
+        let params = params(wallet.address());
+        let signed = sign_operator_avs_registration(&wallet, &params).unwrap();
+
+        assert_eq!(signed.salt, params.salt);
+        assert_eq!(signed.expiry, params.expiry);
+        let signature = Signature::try_from(signed.signature.as_ref()).unwrap();
+        let digest = H256::from(operator_avs_registration_digest(&params));
+        assert_eq!(signature.recover(digest).unwrap(), wallet.address());
+    }
+
+    #[test]
+    fn rejects_signers_other_than_the_operator() {
+        let params = params(wallet(1).address());
+        assert!(sign_operator_avs_registration(&wallet(2), &params).is_err());
+    }
+}
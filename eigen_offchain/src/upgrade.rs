@@ -0,0 +1,246 @@
+use anyhow::Result;
+use ethers::{
+    prelude::*,
+    types::{transaction::eip2718::TypedTransaction, Address, Bytes, H256, U256},
+};
+use serde::Serialize;
+use std::sync::Arc;
+
+use crate::abi::uups_upgradeable::UpgradedFilter;
+use crate::abi::{UUPSUpgradeable, UUPSUpgradeableErrors};
+use crate::output::{serialize_decimal, Render, Table};
+use crate::tx::{self, PreparedTx, Simulation, TxOutcome};
+
+/// ERC-1967 implementation slot: `bytes32(uint256(keccak256("eip1967.proxy.implementation")) - 1)`.
+pub const IMPLEMENTATION_SLOT: H256 = H256([
+    0x36, 0x08, 0x94, 0xa1, 0x3b, 0xa1, 0xa3, 0x21, 0x06, 0x67, 0xc8, 0x28, 0x49, 0x2d, 0xb9, 0x8d,
+    0xca, 0x3e, 0x20, 0x76, 0xcc, 0x37, 0x35, 0xa9, 0x20, 0xa3, 0xca, 0x50, 0x5d, 0x38, 0x2b, 0xbc,
+]);
+
+/// A checked and simulated `upgradeToAndCall` on a UUPS proxy.
+#[derive(Debug, Clone, Serialize)]
+pub struct UpgradePlan {
+    pub chain_id: u64,
+    pub proxy: Address,
+    pub owner: Address,
+    pub current_implementation: Address,
+    pub new_implementation: Address,
+    pub current_version: Option<String>,
+    pub new_version: Option<String>,
+    pub init_data: Bytes,
+    pub calldata: Bytes,
+    #[serde(serialize_with = "serialize_decimal")]
+    pub gas_estimate: U256,
+}
+
+impl UpgradePlan {
+    /// Before/after summary of the upgrade.
+    pub fn summary(&self) -> String {
+        let version = |v: &Option<String>| v.clone().unwrap_or_else(|| "unknown".to_string());
+        let init = if self.init_data.is_empty() {
+            "none".to_string()
+        } else {
+            format!(
+                "0x{} ({} bytes)",
+                hex::encode(&self.init_data[..4.min(self.init_data.len())]),
+                self.init_data.len()
+            )
+        };
+        format!(
+            "Proxy:          {:?}\n\
+             Owner:          {:?}\n\
+             Implementation: {:?} -> {:?}\n\
+             Interface:      {} -> {}\n\
+             Initializer:    {}\n\
+             Gas estimate:   {}",
+            self.proxy,
+            self.owner,
+            self.current_implementation,
+            self.new_implementation,
+            version(&self.current_version),
+            version(&self.new_version),
+            init,
+            self.gas_estimate
+        )
+    }
+
+    /// The `upgradeToAndCall` transaction, to be sent by the owner.
+    pub fn prepared(&self) -> PreparedTx {
+        PreparedTx {
+            function: "upgradeToAndCall".to_string(),
+            chain_id: self.chain_id,
+            from: Some(self.owner),
+            to: self.proxy,
+            value: U256::zero(),
+            calldata: self.calldata.clone(),
+        }
+    }
+
+    /// The simulation the plan was checked with.
+    pub fn simulation(&self) -> Simulation {
+        Simulation {
+            function: "upgradeToAndCall".to_string(),
+            from: Some(self.owner),
+            to: Some(self.proxy),
+            returned: None,
+            gas_estimate: Some(self.gas_estimate),
+            revert_reason: None,
+        }
+    }
+}
+
+impl Render for UpgradePlan {
+    fn plain(&self) -> String {
+        self.summary()
+    }
+
+    fn table(&self) -> Table {
+        let version = |v: &Option<String>| v.clone().unwrap_or_else(|| "unknown".to_string());
+        Table::fields(vec![
+            ("proxy", format!("{:?}", self.proxy)),
+            ("owner", format!("{:?}", self.owner)),
+            ("current_implementation", format!("{:?}", self.current_implementation)),
+            ("new_implementation", format!("{:?}", self.new_implementation)),
+            ("current_version", version(&self.current_version)),
+            ("new_version", version(&self.new_version)),
+            ("init_data", format!("0x{}", hex::encode(&self.init_data))),
+            ("gas_estimate", self.gas_estimate.to_string()),
+        ])
+    }
+}
+
+/// Read the implementation a proxy currently delegates to.
+pub async fn get_implementation<M: Middleware>(client: &M, proxy: Address) -> Result<Address> {
+    let slot = client
+        .get_storage_at(proxy, IMPLEMENTATION_SLOT, None)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to read implementation slot: {}", e))?;
+    Ok(Address::from_slice(&slot.as_bytes()[12..]))
+}
+
+/// Check that `new_implementation` is a UUPS implementation for the ERC-1967
+/// slot, then simulate `upgradeToAndCall(new_implementation, init_data)` from the
+/// proxy owner, so the plan also holds when the owner is a multisig.
+pub async fn plan_upgrade<M: Middleware + 'static>(
+    client: Arc<M>,
+    proxy: Address,
+    new_implementation: Address,
+    init_data: Bytes,
+) -> Result<UpgradePlan> {
+    let chain_id = client
+        .get_chainid()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to fetch chain id: {}", e))?
+        .as_u64();
+
+    let current_implementation = get_implementation(client.as_ref(), proxy).await?;
+    if current_implementation.is_zero() {
+        anyhow::bail!("{:?} is not an ERC-1967 proxy (implementation slot is empty)", proxy);
+    }
+    if current_implementation == new_implementation {
+        anyhow::bail!("{:?} already points to {:?}", proxy, new_implementation);
+    }
+
+    let code = client
+        .get_code(new_implementation, None)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to fetch implementation code: {}", e))?;
+    if code.is_empty() {
+        anyhow::bail!("New implementation {:?} has no code", new_implementation);
+    }
+
+    let new_contract = UUPSUpgradeable::new(new_implementation, client.clone());
+    let uuid = new_contract
+        .proxiable_uuid()
+        .call()
+        .await
+        .map_err(|e| anyhow::anyhow!("New implementation is not UUPS (proxiableUUID failed): {}", e))?;
+    if H256::from(uuid) != IMPLEMENTATION_SLOT {
+        anyhow::bail!(
+            "proxiableUUID of {:?} is {:?}, expected the ERC-1967 implementation slot",
+            new_implementation,
+            H256::from(uuid)
+        );
+    }
+
+    let proxy_contract = UUPSUpgradeable::new(proxy, client.clone());
+    let owner = proxy_contract.owner().call().await?;
+    let current_version = proxy_contract.upgrade_interface_version().call().await.ok();
+    let new_version = new_contract.upgrade_interface_version().call().await.ok();
+
+    let calldata = proxy_contract
+        .upgrade_to_and_call(new_implementation, init_data.clone())
+        .calldata()
+        .ok_or_else(|| anyhow::anyhow!("Failed to encode upgradeToAndCall"))?;
+
+    // Simulate from the owner so the authorization check and initializer run as they would on chain
+    let tx: TypedTransaction = TransactionRequest::new()
+        .from(owner)
+        .to(proxy)
+        .data(calldata.clone())
+        .into();
+    client.call(&tx, None).await.map_err(|e| {
+        let reason = e
+            .as_error_response()
+            .and_then(|resp| resp.as_revert_data())
+            .and_then(|data| UUPSUpgradeableErrors::decode_with_selector(&data))
+            .map(|decoded| format!("{:?}", decoded))
+            .unwrap_or_else(|| e.to_string());
+        anyhow::anyhow!("Upgrade simulation reverted: {}", reason)
+    })?;
+    let gas_estimate = client
+        .estimate_gas(&tx, None)
+        .await
+        .map_err(|e| anyhow::anyhow!("Gas estimation failed: {}", e))?;
+
+    Ok(UpgradePlan {
+        chain_id,
+        proxy,
+        owner,
+        current_implementation,
+        new_implementation,
+        current_version,
+        new_version,
+        init_data,
+        calldata,
+        gas_estimate,
+    })
+}
+
+/// Broadcast a plan from the configured signer and check the implementation slot afterwards.
+/// The plan was simulated from the owner, so any other signer is refused up front.
+pub async fn execute_upgrade<M: Middleware + 'static>(
+    client: Arc<M>,
+    plan: &UpgradePlan,
+    confirmations: usize,
+) -> Result<TxOutcome> {
+    let signer = client.default_sender().unwrap_or_default();
+    if signer != plan.owner {
+        anyhow::bail!(
+            "Upgrades must be sent by the proxy owner {:?}, but the signer is {:?}; \
+             use --safe or --calldata-only to prepare the transaction for the owner",
+            plan.owner,
+            signer
+        );
+    }
+
+    let proxy_contract = UUPSUpgradeable::new(plan.proxy, client.clone());
+    let call = proxy_contract.upgrade_to_and_call(plan.new_implementation, plan.init_data.clone());
+    let outcome = tx::send_and_confirm::<_, _, UUPSUpgradeableErrors, UpgradedFilter>(
+        call,
+        confirmations,
+    )
+    .await?;
+
+    if outcome.success == Some(true) {
+        let implementation = get_implementation(client.as_ref(), plan.proxy).await?;
+        if implementation != plan.new_implementation {
+            anyhow::bail!(
+                "Upgrade transaction mined but the proxy points to {:?}",
+                implementation
+            );
+        }
+    }
+
+    Ok(outcome)
+}
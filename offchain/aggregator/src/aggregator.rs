@@ -1,15 +1,26 @@
-use alloy::primitives::Address;
-use axum::{extract::State, routing::post, Json, Router};
+use alloy::primitives::{Address, Bytes, Signature};
+use axum::{
+    extract::State,
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::Utc;
 use eyre::Result;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashSet,
-    sync::{Arc, RwLock},
+    collections::{BTreeSet, HashMap, HashSet},
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
 };
 use tracing::info;
 use url::Url;
 
-use crate::error::AppError;
+use crate::{
+    discovery::ChainOperator,
+    error::AppError,
+    limits::OperatorRateLimit,
+    liveness::{LivenessPolicy, Observation, OperatorHealth, OperatorStatus},
+};
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -32,32 +43,207 @@ impl Operator {
     }
 }
 
-#[derive(Clone, Debug, Default)]
+/// An operator announcement signed by the operator key. The signature covers
+/// `registration_message`, which binds the endpoint to this aggregator and chain.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedRegistration {
+    #[serde(flatten)]
+    pub operator: Operator,
+    /// Unix time of signing, in seconds
+    pub timestamp: u64,
+    pub signature: Bytes,
+}
+
+/// Message an operator signs (EIP-191) to announce its endpoint.
+pub fn registration_message(
+    public_key: &Address,
+    url: &Url,
+    chain_id: u64,
+    aggregator: &Address,
+    timestamp: u64,
+) -> String {
+    format!(
+        "Register operator {public_key} at {url} with aggregator {aggregator} on chain {chain_id} at {timestamp}"
+    )
+}
+
+/// Time a signed timestamp may be ahead of the aggregator clock, in seconds.
+pub const MAX_CLOCK_SKEW: u64 = 30;
+
+/// Checks the timestamp of a signed request against `now`: no older than
+/// `max_age` seconds and no more than `MAX_CLOCK_SKEW` seconds ahead. Future
+/// timestamps are refused on their own, since accepting one would block the
+/// signer's later requests until the clock catches up.
+pub fn check_timestamp(what: &str, timestamp: u64, now: u64, max_age: u64) -> Result<()> {
+    if timestamp > now.saturating_add(MAX_CLOCK_SKEW) {
+        eyre::bail!(
+            "{what} timestamp {timestamp} is {}s ahead of the aggregator clock",
+            timestamp - now
+        );
+    }
+    if now.saturating_sub(timestamp) > max_age {
+        eyre::bail!("{what} timestamp {timestamp} is older than {max_age}s");
+    }
+    Ok(())
+}
+
+/// Verifies signed registrations. A registration is accepted only when it is
+/// signed by the operator it names, for this chain and aggregator, no older
+/// than `max_age` seconds nor ahead of the clock, and newer than the last one
+/// accepted for the operator, so a captured payload cannot be replayed.
+#[derive(Debug)]
+pub struct RegistrationAuth {
+    chain_id: u64,
+    aggregator: Address,
+    max_age: u64,
+    last_timestamp: Mutex<HashMap<Address, u64>>,
+}
+
+impl RegistrationAuth {
+    pub fn new(chain_id: u64, aggregator: Address, max_age: u64) -> Self {
+        Self {
+            chain_id,
+            aggregator,
+            max_age,
+            last_timestamp: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Operator that signed `registration`, when it is the operator named in it.
+    pub fn signer(&self, registration: &SignedRegistration) -> Result<Address> {
+        let operator = &registration.operator;
+        let signature = Signature::try_from(registration.signature.as_ref())
+            .map_err(|e| eyre::eyre!("Invalid registration signature: {e}"))?;
+        let message = registration_message(
+            &operator.public_key,
+            &operator.url,
+            self.chain_id,
+            &self.aggregator,
+            registration.timestamp,
+        );
+        let signer = signature
+            .recover_address_from_msg(message)
+            .map_err(|e| eyre::eyre!("Invalid registration signature: {e}"))?;
+        if signer != operator.public_key {
+            eyre::bail!(
+                "Registration for {} is signed by {}",
+                operator.public_key,
+                signer
+            );
+        }
+        Ok(signer)
+    }
+
+    pub fn verify(&self, registration: &SignedRegistration) -> Result<()> {
+        let operator = &registration.operator;
+        let now = Utc::now().timestamp().max(0) as u64;
+        check_timestamp("Registration", registration.timestamp, now, self.max_age)?;
+        self.signer(registration)?;
+
+        let mut last_timestamp = self
+            .last_timestamp
+            .lock()
+            .map_err(|_| eyre::eyre!("Could not lock"))?;
+        match last_timestamp.get(&operator.public_key) {
+            Some(last) if *last >= registration.timestamp => eyre::bail!(
+                "Registration for {} replays timestamp {} (last accepted {})",
+                operator.public_key,
+                registration.timestamp,
+                last
+            ),
+            _ => {
+                last_timestamp.insert(operator.public_key, registration.timestamp);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An operator as returned by `GET /aggregator/operators`.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperatorInfo {
+    pub public_key: Address,
+    /// Endpoint tasks are sent to, if the operator has one
+    pub url: Option<Url>,
+    pub announced_url: Option<Url>,
+    /// On-chain registrations, `None` when the operator is not registered
+    pub chain: Option<ChainOperator>,
+    pub routed: bool,
+    pub available: bool,
+    pub health: Option<OperatorHealth>,
+}
+
+/// Operators tasks are sent to. An operator is routed only while it is
+/// registered on chain and not evicted; its endpoint is the one it registered
+/// on chain, or the one it announced over HTTP when the registration carries
+/// none. Quarantined operators stay routed but are skipped by task fan-outs.
+//
+// Locks are always taken in the order announced, registered, health,
+// operators, so concurrent updates cannot deadlock.
+#[derive(Clone, Debug)]
 pub struct OperatorState {
     pub operators: Arc<RwLock<HashSet<Operator>>>,
+    announced: Arc<RwLock<HashMap<Address, Url>>>,
+    registered: Arc<RwLock<HashMap<Address, ChainOperator>>>,
+    health: Arc<RwLock<HashMap<Address, OperatorHealth>>>,
+    auth: Arc<RegistrationAuth>,
+    /// Rate limit of signed registrations, per operator
+    registration_limit: Option<Arc<OperatorRateLimit>>,
+    policy: LivenessPolicy,
 }
 
 impl OperatorState {
-    pub fn new() -> Self {
+    pub fn new(auth: RegistrationAuth, policy: LivenessPolicy) -> Self {
         Self {
             operators: Arc::new(RwLock::new(HashSet::new())),
+            announced: Arc::new(RwLock::new(HashMap::new())),
+            registered: Arc::new(RwLock::new(HashMap::new())),
+            health: Arc::new(RwLock::new(HashMap::new())),
+            auth: Arc::new(auth),
+            registration_limit: None,
+            policy,
         }
     }
 
-    pub fn register_operator(&self, operator: Operator) -> Result<()> {
-        let mut operators = self
-            .operators
+    pub fn with_registration_limit(mut self, limit: Arc<OperatorRateLimit>) -> Self {
+        self.registration_limit = Some(limit);
+        self
+    }
+
+    pub fn policy(&self) -> LivenessPolicy {
+        self.policy
+    }
+
+    pub fn register_operator(&self, operator: Operator) -> Result<bool> {
+        self.announced
             .write()
-            .map_err(|_| eyre::eyre!("Could not lock"))?;
-        if operators.insert(operator.clone()) {
-            info!("Operator registered: {}", serde_json::to_string(&operator)?);
+            .map_err(|_| eyre::eyre!("Could not lock"))?
+            .insert(operator.public_key, operator.url.clone());
+        {
+            let mut health = self
+                .health
+                .write()
+                .map_err(|_| eyre::eyre!("Could not lock"))?;
+            let health = health.entry(operator.public_key).or_default();
+            health.record_heartbeat(Utc::now());
+            if health.status == OperatorStatus::Evicted {
+                health.status = OperatorStatus::Active;
+            }
+        }
+        self.reconcile()?;
+
+        let active = self.is_operator_registered(operator.clone())?;
+        if active {
+            info!("Operator announced: {}", serde_json::to_string(&operator)?);
         } else {
             info!(
-                "Operator already registered: {}",
+                "Operator announced but not registered on chain yet: {}",
                 serde_json::to_string(&operator)?
             );
         }
-        Ok(())
+        Ok(active)
     }
 
     pub fn is_operator_registered(&self, operator: Operator) -> Result<bool> {
@@ -67,16 +253,271 @@ impl OperatorState {
             .map_err(|_| eyre::eyre!("Could not lock"))?
             .contains(&operator))
     }
+
+    /// Addresses announced over HTTP, with the endpoint they announced.
+    pub fn announced(&self) -> Result<HashMap<Address, Url>> {
+        Ok(self
+            .announced
+            .read()
+            .map_err(|_| eyre::eyre!("Could not lock"))?
+            .clone())
+    }
+
+    /// Routed operators that are not quarantined.
+    pub fn available_operators(&self) -> Result<HashSet<Operator>> {
+        let now = Utc::now();
+        let health = self
+            .health
+            .read()
+            .map_err(|_| eyre::eyre!("Could not lock"))?;
+        Ok(self
+            .operators
+            .read()
+            .map_err(|_| eyre::eyre!("Could not lock"))?
+            .iter()
+            .filter(|operator| {
+                health
+                    .get(&operator.public_key)
+                    .map_or(true, |h| h.is_available(now))
+            })
+            .cloned()
+            .collect())
+    }
+
+    /// Registered operators with an endpoint, evicted ones included.
+    pub fn endpoints(&self) -> Result<HashSet<Operator>> {
+        let announced = self
+            .announced
+            .read()
+            .map_err(|_| eyre::eyre!("Could not lock"))?;
+        let registered = self
+            .registered
+            .read()
+            .map_err(|_| eyre::eyre!("Could not lock"))?;
+        Ok(registered
+            .iter()
+            .filter_map(|(address, chain)| {
+                let url = chain.url.as_ref().or_else(|| announced.get(address))?;
+                Some(Operator::new(*address, url.clone()))
+            })
+            .collect())
+    }
+
+    pub fn record_success(
+        &self,
+        public_key: Address,
+        observation: Observation,
+        latency: Duration,
+    ) -> Result<()> {
+        let revived = self
+            .health
+            .write()
+            .map_err(|_| eyre::eyre!("Could not lock"))?
+            .entry(public_key)
+            .or_default()
+            .record_success(observation, latency, Utc::now());
+        if revived {
+            info!("Operator {public_key} answered again after eviction");
+            self.reconcile()?;
+        }
+        Ok(())
+    }
+
+    pub fn record_failure(&self, public_key: Address, observation: Observation) -> Result<()> {
+        let mut health = self
+            .health
+            .write()
+            .map_err(|_| eyre::eyre!("Could not lock"))?;
+        let health = health.entry(public_key).or_default();
+        let was_available = health.is_available(Utc::now());
+        health.record_failure(observation, &self.policy, Utc::now());
+        if let (true, OperatorStatus::Quarantined { until }) = (was_available, health.status) {
+            info!(
+                "Operator {public_key} quarantined until {until} after {} consecutive failures",
+                health.consecutive_failures
+            );
+        }
+        Ok(())
+    }
+
+    /// Evict routed operators without any sign of life for the eviction period.
+    /// Their announcement is dropped, so they need to register again unless
+    /// their endpoint is registered on chain and answers a probe.
+    pub fn evict_inactive(&self) -> Result<()> {
+        let now = Utc::now();
+        let eviction_after = chrono::Duration::from_std(self.policy.eviction_after)?;
+        let routed: Vec<Address> = self
+            .operators
+            .read()
+            .map_err(|_| eyre::eyre!("Could not lock"))?
+            .iter()
+            .map(|operator| operator.public_key)
+            .collect();
+
+        let mut evicted = Vec::new();
+        {
+            let mut health = self
+                .health
+                .write()
+                .map_err(|_| eyre::eyre!("Could not lock"))?;
+            for public_key in routed {
+                let health = health.entry(public_key).or_default();
+                match health.last_activity() {
+                    // Start the clock for operators routed before any observation
+                    None => health.record_heartbeat(now),
+                    Some(last) if now - last > eviction_after => {
+                        health.status = OperatorStatus::Evicted;
+                        evicted.push(public_key);
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+        if evicted.is_empty() {
+            return Ok(());
+        }
+
+        let mut announced = self
+            .announced
+            .write()
+            .map_err(|_| eyre::eyre!("Could not lock"))?;
+        for public_key in evicted {
+            info!(
+                "Operator {public_key} evicted after {:?} without activity",
+                self.policy.eviction_after
+            );
+            announced.remove(&public_key);
+        }
+        drop(announced);
+        self.reconcile()
+    }
+
+    /// Every operator that is announced, registered or has liveness records.
+    pub fn list(&self) -> Result<Vec<OperatorInfo>> {
+        let now = Utc::now();
+        let announced = self
+            .announced
+            .read()
+            .map_err(|_| eyre::eyre!("Could not lock"))?;
+        let registered = self
+            .registered
+            .read()
+            .map_err(|_| eyre::eyre!("Could not lock"))?;
+        let health = self
+            .health
+            .read()
+            .map_err(|_| eyre::eyre!("Could not lock"))?;
+        let operators = self
+            .operators
+            .read()
+            .map_err(|_| eyre::eyre!("Could not lock"))?;
+
+        let addresses: BTreeSet<Address> = announced
+            .keys()
+            .chain(registered.keys())
+            .chain(health.keys())
+            .copied()
+            .collect();
+        Ok(addresses
+            .into_iter()
+            .map(|public_key| {
+                let routed = operators.iter().find(|o| o.public_key == public_key);
+                let health = health.get(&public_key).cloned();
+                OperatorInfo {
+                    public_key,
+                    url: routed.map(|o| o.url.clone()),
+                    announced_url: announced.get(&public_key).cloned(),
+                    chain: registered.get(&public_key).cloned(),
+                    routed: routed.is_some(),
+                    available: routed.is_some()
+                        && health.as_ref().map_or(true, |h| h.is_available(now)),
+                    health,
+                }
+            })
+            .collect())
+    }
+
+    /// Replace the on-chain registrations and recompute the routed operators.
+    /// Announcements of operators that left the registrations are forgotten,
+    /// so a later re-registration needs a fresh announcement or endpoint.
+    pub fn update_registrations(&self, registered: HashMap<Address, ChainOperator>) -> Result<()> {
+        {
+            let mut announced = self
+                .announced
+                .write()
+                .map_err(|_| eyre::eyre!("Could not lock"))?;
+            let mut current = self
+                .registered
+                .write()
+                .map_err(|_| eyre::eyre!("Could not lock"))?;
+            let mut health = self
+                .health
+                .write()
+                .map_err(|_| eyre::eyre!("Could not lock"))?;
+            for address in current.keys().filter(|a| !registered.contains_key(*a)) {
+                info!("Operator {address} unregistered on chain");
+                announced.remove(address);
+                health.remove(address);
+            }
+            *current = registered;
+        }
+        self.reconcile()
+    }
+
+    fn reconcile(&self) -> Result<()> {
+        let endpoints = self.endpoints()?;
+        let active: HashSet<Operator> = {
+            let health = self
+                .health
+                .read()
+                .map_err(|_| eyre::eyre!("Could not lock"))?;
+            endpoints
+                .into_iter()
+                .filter(|operator| {
+                    health
+                        .get(&operator.public_key)
+                        .map_or(true, |h| h.status != OperatorStatus::Evicted)
+                })
+                .collect()
+        };
+
+        let mut operators = self
+            .operators
+            .write()
+            .map_err(|_| eyre::eyre!("Could not lock"))?;
+        for operator in operators.difference(&active) {
+            info!("Operator removed: {}", serde_json::to_string(operator)?);
+        }
+        for operator in active.difference(&operators) {
+            info!("Operator added: {}", serde_json::to_string(operator)?);
+        }
+        *operators = active;
+        Ok(())
+    }
 }
 
 pub async fn register_operator(
     State(operators): State<Arc<OperatorState>>,
-    Json(operator): Json<Operator>,
+    Json(registration): Json<SignedRegistration>,
 ) -> Result<Json<bool>, AppError> {
-    operators.register_operator(operator)?;
-    Ok(Json(true))
+    if let Some(limit) = &operators.registration_limit {
+        let operator = operators
+            .auth
+            .signer(&registration)
+            .map_err(AppError::unauthorized)?;
+        limit.check(&operator)?;
+    }
+    operators
+        .auth
+        .verify(&registration)
+        .map_err(AppError::unauthorized)?;
+    let active = operators.register_operator(registration.operator)?;
+    Ok(Json(active))
 }
 
+// Operators poll this to re-register when dropped. The request is not signed,
+// so it is not taken as a sign of life: liveness comes from signed
+// registrations, probes and tasks only.
 pub async fn is_operator_registered(
     State(operators): State<Arc<OperatorState>>,
     Json(operator): Json<Operator>,
@@ -85,9 +526,228 @@ pub async fn is_operator_registered(
     Ok(Json(registered))
 }
 
-pub fn aggregator_router(operator_state: Arc<OperatorState>) -> Router {
+pub async fn list_operators(
+    State(operators): State<Arc<OperatorState>>,
+) -> Result<Json<Vec<OperatorInfo>>, AppError> {
+    Ok(Json(operators.list()?))
+}
+
+/// Signed registrations, rate limited per operator by `register_operator`.
+pub fn registration_router(operator_state: Arc<OperatorState>) -> Router {
     Router::new()
         .route("/registerOperator", post(register_operator))
+        .with_state(operator_state)
+}
+
+pub fn aggregator_router(operator_state: Arc<OperatorState>) -> Router {
+    Router::new()
         .route("/isOperatorRegistered", post(is_operator_registered))
+        .route("/operators", get(list_operators))
         .with_state(operator_state)
 }
+
+#[cfg(test)]
+mod tests {
+    use alloy::{
+        primitives::address,
+        signers::{local::PrivateKeySigner, SignerSync},
+    };
+
+    use super::*;
+
+    const OPERATOR: Address = address!("1111111111111111111111111111111111111111");
+    const AGGREGATOR: Address = address!("2222222222222222222222222222222222222222");
+
+    fn policy() -> LivenessPolicy {
+        LivenessPolicy {
+            probe_interval: Duration::from_secs(10),
+            probe_timeout: Duration::from_secs(2),
+            max_consecutive_failures: 3,
+            quarantine: Duration::from_secs(60),
+            eviction_after: Duration::from_secs(300),
+        }
+    }
+
+    // A state routing OPERATOR, registered on chain with its endpoint
+    fn routed_state() -> (Arc<OperatorState>, Operator) {
+        let state = Arc::new(OperatorState::new(
+            RegistrationAuth::new(1, AGGREGATOR, 300),
+            policy(),
+        ));
+        let url = Url::parse("http://operator:8080/").unwrap();
+        state
+            .update_registrations(HashMap::from([(
+                OPERATOR,
+                ChainOperator {
+                    url: Some(url.clone()),
+                    ..ChainOperator::default()
+                },
+            )]))
+            .unwrap();
+        (state, Operator::new(OPERATOR, url))
+    }
+
+    #[test]
+    fn quarantined_operators_are_not_given_tasks() {
+        let (state, operator) = routed_state();
+        for _ in 1..policy().max_consecutive_failures {
+            state.record_failure(OPERATOR, Observation::Task).unwrap();
+        }
+        assert!(state.available_operators().unwrap().contains(&operator));
+
+        state.record_failure(OPERATOR, Observation::Task).unwrap();
+        assert!(!state.available_operators().unwrap().contains(&operator));
+        assert!(state.endpoints().unwrap().contains(&operator));
+    }
+
+    async fn poll(state: &Arc<OperatorState>, operator: &Operator) -> bool {
+        match is_operator_registered(State(state.clone()), Json(operator.clone())).await {
+            Ok(Json(registered)) => registered,
+            Err(_) => panic!("isOperatorRegistered failed"),
+        }
+    }
+
+    #[tokio::test]
+    async fn unsigned_polls_are_not_a_sign_of_life() {
+        let (state, operator) = routed_state();
+        assert!(poll(&state, &operator).await);
+        assert!(state
+            .health
+            .read()
+            .unwrap()
+            .get(&OPERATOR)
+            .and_then(OperatorHealth::last_activity)
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn spoofed_polls_do_not_prevent_eviction() {
+        let (state, operator) = routed_state();
+        state
+            .health
+            .write()
+            .unwrap()
+            .entry(OPERATOR)
+            .or_default()
+            .last_seen = Some(Utc::now() - chrono::Duration::minutes(10));
+
+        // Anyone can send the operator's address
+        assert!(poll(&state, &operator).await);
+        state.evict_inactive().unwrap();
+
+        assert!(!state.is_operator_registered(operator.clone()).unwrap());
+        assert_eq!(
+            state.health.read().unwrap()[&OPERATOR].status,
+            OperatorStatus::Evicted
+        );
+        assert!(!poll(&state, &operator).await);
+    }
+
+    fn key(n: u8) -> PrivateKeySigner {
+        format!("{n:064x}").parse().unwrap()
+    }
+
+    fn now() -> u64 {
+        Utc::now().timestamp() as u64
+    }
+
+    fn signed(signer: &PrivateKeySigner, url: &Url, timestamp: u64) -> SignedRegistration {
+        let message = registration_message(&signer.address(), url, 1, &AGGREGATOR, timestamp);
+        let signature = signer.sign_message_sync(message.as_bytes()).unwrap();
+        SignedRegistration {
+            operator: Operator::new(signer.address(), url.clone()),
+            timestamp,
+            signature: Bytes::from(signature.as_bytes().to_vec()),
+        }
+    }
+
+    #[test]
+    fn registration_messages_bind_chain_and_aggregator() {
+        let url = Url::parse("https://operator.example/").unwrap();
+        assert_eq!(
+            registration_message(&OPERATOR, &url, 17000, &AGGREGATOR, 1700000000),
+            format!(
+                "Register operator {OPERATOR} at https://operator.example/ with aggregator \
+                 {AGGREGATOR} on chain 17000 at 1700000000"
+            )
+        );
+    }
+
+    #[test]
+    fn timestamps_must_be_recent_and_not_ahead() {
+        let now = 1_700_000_000;
+        assert!(check_timestamp("Registration", now, now, 300).is_ok());
+        assert!(check_timestamp("Registration", now - 300, now, 300).is_ok());
+        assert!(check_timestamp("Registration", now - 301, now, 300).is_err());
+        assert!(check_timestamp("Registration", now + MAX_CLOCK_SKEW, now, 300).is_ok());
+        assert!(check_timestamp("Registration", now + MAX_CLOCK_SKEW + 1, now, 300).is_err());
+        // Ahead by less than the age window is still refused
+        assert!(check_timestamp("Registration", now + 200, now, 300).is_err());
+    }
+
+    #[test]
+    fn accepts_a_fresh_registration_once() {
+        let auth = RegistrationAuth::new(1, AGGREGATOR, 300);
+        let signer = key(1);
+        let url = Url::parse("https://operator.example/").unwrap();
+        let registration = signed(&signer, &url, now());
+
+        auth.verify(&registration).unwrap();
+        assert!(auth.verify(&registration).is_err());
+        assert!(auth
+            .verify(&signed(&signer, &url, registration.timestamp - 1))
+            .is_err());
+        auth.verify(&signed(&signer, &url, registration.timestamp + 1))
+            .unwrap();
+    }
+
+    #[test]
+    fn future_registrations_do_not_block_later_ones() {
+        let auth = RegistrationAuth::new(1, AGGREGATOR, 300);
+        let signer = key(1);
+        let url = Url::parse("https://operator.example/").unwrap();
+
+        assert!(auth.verify(&signed(&signer, &url, now() + 200)).is_err());
+        auth.verify(&signed(&signer, &url, now())).unwrap();
+    }
+
+    #[test]
+    fn rejects_stale_and_foreign_registrations() {
+        let auth = RegistrationAuth::new(1, AGGREGATOR, 300);
+        let signer = key(1);
+        let url = Url::parse("https://operator.example/").unwrap();
+        assert!(auth.verify(&signed(&signer, &url, now() - 600)).is_err());
+
+        // Signed by another key than the operator it names
+        let mut registration = signed(&key(2), &url, now());
+        registration.operator = Operator::new(signer.address(), url.clone());
+        assert!(auth.verify(&registration).is_err());
+
+        // Signed for another endpoint
+        let mut registration = signed(&signer, &url, now());
+        registration.operator = Operator::new(
+            signer.address(),
+            Url::parse("https://attacker.example/").unwrap(),
+        );
+        assert!(auth.verify(&registration).is_err());
+
+        // Signed for another aggregator
+        let other = RegistrationAuth::new(1, OPERATOR, 300);
+        assert!(other.verify(&signed(&signer, &url, now())).is_err());
+    }
+
+    #[test]
+    fn registrations_are_keyed_by_their_signer() {
+        let auth = RegistrationAuth::new(1, AGGREGATOR, 300);
+        let signer = key(1);
+        let url = Url::parse("https://operator.example/").unwrap();
+        let registration = signed(&signer, &url, now());
+        assert_eq!(auth.signer(&registration).unwrap(), signer.address());
+        // Recovering the signer does not consume the timestamp
+        auth.verify(&registration).unwrap();
+
+        let mut registration = signed(&key(2), &url, now());
+        registration.operator = Operator::new(signer.address(), url.clone());
+        assert!(auth.signer(&registration).is_err());
+    }
+}
@@ -1,19 +1,25 @@
+use alloy::eips::BlockId;
 use alloy::network::{Ethereum, EthereumWallet};
-use alloy::primitives::Address;
+use alloy::primitives::{Address, Bytes, B256};
 use alloy::providers::fillers::{FillProvider, JoinFill, RecommendedFiller, WalletFiller};
-use alloy::providers::{ProviderBuilder, ReqwestProvider};
+use alloy::providers::utils::Eip1559Estimation;
+use alloy::providers::{Provider, ProviderBuilder, ReqwestProvider};
+use alloy::rpc::types::TransactionReceipt;
 use alloy::sol;
+use alloy::sol_types::{Panic, Revert, SolError, SolEventInterface, SolInterface};
 use alloy::transports::http::ReqwestTransport;
-use karak_rs::contracts::Core::CoreInstance;
-use url::Url;
-use SquareNumberDSS::{TaskRequest, TaskResponse};
-use TxnVerifier::{Task,OperatorResponse};
-use tracing::info;
+use karak_rs::contracts::Core::{self, CoreInstance};
+use serde::{Deserialize, Serialize};
+use tracing::{info, instrument, warn};
 
+use crate::dss::{Dss, DssResponse, DssTask};
+use crate::metrics::metrics;
+use crate::multicall::Multicall;
 use crate::Config;
 use crate::TaskError;
 
 sol!(
+    #[derive(Debug)]
     #[sol(rpc)]
     SquareNumberDSS,
     "../abi/SquareNumberDSS.json",
@@ -21,6 +27,7 @@ sol!(
 
 
 sol!(
+    #[derive(Debug)]
     #[sol(rpc)]
     TxnVerifier,
     "../abi/TxnVerifier.json",
@@ -33,102 +40,389 @@ sol!(
     "../abi/Vault.json",
 );
 
-type RecommendedProvider = FillProvider<
+// Underlying assets of vaults and their ETH price feeds
+sol! {
+    #[sol(rpc)]
+    interface Erc20 {
+        function decimals() external view returns (uint8);
+    }
+
+    #[sol(rpc)]
+    interface ChainlinkAggregator {
+        function decimals() external view returns (uint8);
+        function latestRoundData() external view returns (uint80 roundId, int256 answer, uint256 startedAt, uint256 updatedAt, uint80 answeredInRound);
+    }
+}
+
+// Registration activity used to discover operators from chain state
+sol! {
+    #[derive(Debug)]
+    interface KarakCoreEvents {
+        event RegisteredOperatorToDSS(address indexed operator, address indexed dss);
+        event UnregisteredOperatorToDSS(address indexed operator, address indexed dss);
+    }
+
+    #[derive(Debug)]
+    interface EigenAVSDirectory {
+        event OperatorAVSRegistrationStatusUpdated(address indexed operator, address indexed avs, uint8 status);
+    }
+
+    #[derive(Debug)]
+    interface EigenRestaking {
+        struct SignatureWithSaltAndExpiry {
+            bytes signature;
+            bytes32 salt;
+            uint256 expiry;
+        }
+
+        function registerOperator(string rpc, string rpc1, string rpc2, SignatureWithSaltAndExpiry operatorSignature);
+    }
+
+    #[derive(Debug)]
+    #[sol(rpc)]
+    interface SymbioticRestaking {
+        event OperatorRegistered(address indexed operator, string rpc);
+
+        function SYMBIOTIC_NETWORK() external view returns (address);
+        function OPERATOR_NET_OPTIN() external view returns (address);
+    }
+
+    #[sol(rpc)]
+    interface SymbioticOptInService {
+        function isOptedIn(address who, address network) external view returns (bool);
+    }
+}
+
+pub(crate) type RecommendedProvider = FillProvider<
     JoinFill<RecommendedFiller, WalletFiller<EthereumWallet>>,
     ReqwestProvider,
     ReqwestTransport,
     Ethereum,
 >;
 
+/// Outcome of a submitted transaction. Only `tx_hash` is set when the
+/// manager is configured not to wait for confirmations.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TxOutcome {
+    pub tx_hash: B256,
+    pub success: Option<bool>,
+    pub block_number: Option<u64>,
+    pub gas_used: Option<u128>,
+    pub events: Vec<String>,
+    pub revert_reason: Option<String>,
+}
+
+impl TxOutcome {
+    pub fn submitted(tx_hash: B256) -> Self {
+        Self {
+            tx_hash,
+            success: None,
+            block_number: None,
+            gas_used: None,
+            events: Vec::new(),
+            revert_reason: None,
+        }
+    }
+
+    pub fn from_receipt(receipt: &TransactionReceipt) -> Self {
+        let events = receipt
+            .inner
+            .logs()
+            .iter()
+            .map(|log| {
+                if let Ok(event) = TxnVerifier::TxnVerifierEvents::decode_log(&log.inner, true) {
+                    return format!("{:?}", event.data);
+                }
+                match SquareNumberDSS::SquareNumberDSSEvents::decode_log(&log.inner, true) {
+                    Ok(event) => format!("{:?}", event.data),
+                    Err(_) => format!("{} topic0 {:?}", log.address(), log.topic0()),
+                }
+            })
+            .collect();
+
+        Self {
+            tx_hash: receipt.transaction_hash,
+            success: Some(receipt.status()),
+            block_number: receipt.block_number,
+            gas_used: Some(receipt.gas_used),
+            events,
+            revert_reason: None,
+        }
+    }
+}
+
+/// Decode revert data returned by the DSS contracts: their custom errors
+/// first, then `Error(string)` and `Panic(uint256)`.
+pub fn decode_revert(error: &alloy::contract::Error) -> Option<String> {
+    let alloy::contract::Error::TransportError(transport_error) = error else {
+        return None;
+    };
+    let data = transport_error.as_error_resp()?.as_revert_data()?;
+    Some(decode_revert_data(&data))
+}
+
+/// Decode raw revert data, e.g. of a call that failed inside a multicall.
+pub fn decode_revert_data(data: &Bytes) -> String {
+    if let Ok(decoded) = TxnVerifier::TxnVerifierErrors::abi_decode(data, true) {
+        return format!("{:?}", decoded);
+    }
+    if let Ok(decoded) = SquareNumberDSS::SquareNumberDSSErrors::abi_decode(data, true) {
+        return format!("{:?}", decoded);
+    }
+    if let Ok(revert) = Revert::abi_decode(data, true) {
+        return revert.reason;
+    }
+    if let Ok(panic) = Panic::abi_decode(data, true) {
+        return format!("panic code {}", panic.code);
+    }
+    format!("unknown revert {}", data)
+}
+
+pub(crate) fn contract_error(error: alloy::contract::Error) -> TaskError {
+    match decode_revert(&error) {
+        Some(reason) => TaskError::ContractReverted(reason),
+        None => TaskError::ContractError(error.to_string()),
+    }
+}
+
+/// Count a failed contract call by method and map it like `contract_error`.
+pub(crate) fn rpc_error(method: &'static str) -> impl Fn(alloy::contract::Error) -> TaskError {
+    move |error| {
+        metrics().rpc_error(method);
+        contract_error(error)
+    }
+}
+
 pub struct ContractManager {
-    pub dss_instance:
-    TxnVerifier::TxnVerifierInstance<ReqwestTransport, RecommendedProvider>,
+    /// Served DSSs, the TxnVerifier at `TXN_VERIFIER_ADDRESS` first
+    pub deployments: Vec<Dss>,
     pub core_instance: CoreInstance<ReqwestTransport, RecommendedProvider>,
     pub provider: RecommendedProvider,
+    /// Batched view calls pinned to a block
+    pub multicall: Multicall,
+    pub confirmations: u64,
+    /// Account the task responses are sent from
+    pub account: Address,
 }
 
 impl ContractManager {
     pub fn new(config: &Config) -> Result<Self, TaskError> {
-        let rpc_url = config
-            .get_rpc_url()
-            .map_err(|e| TaskError::CustomUrlError(e.to_string()))?;
+        let rpc_url = config.rpc_url.clone();
         let private_key = config.get_private_key()?;
+        let account = private_key.address();
 
         let provider = ProviderBuilder::new()
             .with_recommended_fillers()
             .wallet(EthereumWallet::from(private_key))
             .on_http(rpc_url);
 
-        let txn_verifier_address = config.txn_verifier_address;
-        let dss_instance = TxnVerifier::new(txn_verifier_address, provider.clone());
+        let deployments = config
+            .deployments()
+            .into_iter()
+            .map(|deployment| Dss::new(deployment, provider.clone()))
+            .collect();
 
         let core_address = config.core_address;
         let core_instance = CoreInstance::new(core_address, provider.clone());
+        let multicall = Multicall::new(
+            config.multicall_address,
+            provider.clone(),
+            config.multicall_batch_size,
+        );
 
         Ok(Self {
-            dss_instance,
+            deployments,
             core_instance,
             provider,
+            multicall,
+            confirmations: config.confirmations,
+            account,
         })
     }
 
-    pub async fn fetch_vaults_staked_in_dss(
+    /// Vaults each of `operators` has staked in `dss_address` at `block`, in
+    /// the order of `operators`.
+    #[instrument(skip(self, operators), fields(operators = operators.len()), err)]
+    pub async fn fetch_vaults_staked_in_dss_at(
         &self,
-        operator: Address,
+        operators: &[Address],
         dss_address: Address,
-    ) -> Result<Vec<Address>, TaskError> {
-        let result = self
-            .core_instance
-            .fetchVaultsStakedInDSS(operator, dss_address)
-            .call()
-            .await
-            .map_err(|_| TaskError::ContractCallError)?;
-
-        Ok(result.vaults)
+        block: u64,
+    ) -> Result<Vec<Vec<Address>>, TaskError> {
+        let calls = operators
+            .iter()
+            .map(|operator| {
+                (
+                    *self.core_instance.address(),
+                    Core::fetchVaultsStakedInDSSCall {
+                        operator: *operator,
+                        dss: dss_address,
+                    },
+                )
+            })
+            .collect();
+        Ok(self
+            .multicall
+            .call("fetchVaultsStakedInDSS", calls, block)
+            .await?
+            .into_iter()
+            .map(|result| result.vaults)
+            .collect())
     }
 
-    pub async fn submit_task_response(
+    /// `isOperatorRegistered` of each of `operators` in the TxnVerifier at
+    /// `block`, in their order.
+    #[instrument(skip(self, operators), fields(operators = operators.len()), err)]
+    pub async fn are_operators_registered(
         &self,
-        dss_task_request: Task,
-        task_response: OperatorResponse,
-    ) -> Result<(), TaskError> {
-
-        // info!("submit_task_response  {:?}",dss_task_request);
-        info!("submit_task_responseafter  {:?}",dss_task_request.transaction_hash);
-
+        operators: &[Address],
+        block: u64,
+    ) -> Result<Vec<bool>, TaskError> {
+        let txn_verifier = self.dss(None)?.deployment.address;
+        let calls = operators
+            .iter()
+            .map(|operator| {
+                (
+                    txn_verifier,
+                    TxnVerifier::isOperatorRegisteredCall {
+                        operator: *operator,
+                    },
+                )
+            })
+            .collect();
+        Ok(self
+            .multicall
+            .call("isOperatorRegistered", calls, block)
+            .await?
+            .into_iter()
+            .map(|result| result._0)
+            .collect())
+    }
 
-        let _ = self
-            .dss_instance
-            .submitTaskResponse(dss_task_request.transaction_hash, task_response)
-            .send()
+    /// Whether each of `operators` is opted in to the network of the Symbiotic
+    /// `middleware` at `block`, in their order.
+    #[instrument(skip(self, operators), fields(operators = operators.len()), err)]
+    pub async fn are_operators_opted_in(
+        &self,
+        middleware: Address,
+        operators: &[Address],
+        block: u64,
+    ) -> Result<Vec<bool>, TaskError> {
+        let instance = SymbioticRestaking::new(middleware, self.provider.clone());
+        let network = instance
+            .SYMBIOTIC_NETWORK()
+            .block(BlockId::number(block))
+            .call()
+            .await
+            .map_err(rpc_error("SYMBIOTIC_NETWORK"))?
+            ._0;
+        let opt_in_service = instance
+            .OPERATOR_NET_OPTIN()
+            .block(BlockId::number(block))
+            .call()
             .await
-            .map_err(|_| TaskError::ContractCallError)?;
+            .map_err(rpc_error("OPERATOR_NET_OPTIN"))?
+            ._0;
 
-        Ok(())
+        let calls = operators
+            .iter()
+            .map(|operator| {
+                (
+                    opt_in_service,
+                    SymbioticOptInService::isOptedInCall {
+                        who: *operator,
+                        network,
+                    },
+                )
+            })
+            .collect();
+        Ok(self
+            .multicall
+            .call("isOptedIn", calls, block)
+            .await?
+            .into_iter()
+            .map(|result| result._0)
+            .collect())
     }
-}
 
-pub struct VaultContract {
-    pub vault_instance: VaultAbi::VaultAbiInstance<ReqwestTransport, RecommendedProvider>,
-    pub provider: RecommendedProvider,
-}
+    /// Latest block number, which batched reads are pinned to.
+    pub async fn block_number(&self) -> Result<u64, TaskError> {
+        self.provider.get_block_number().await.map_err(|e| {
+            metrics().rpc_error("eth_blockNumber");
+            TaskError::ContractError(e.to_string())
+        })
+    }
 
-impl VaultContract {
-    pub fn new(
-        rpc_url: Url,
-        private_key: alloy::signers::local::PrivateKeySigner,
-        vault_address: Address,
-    ) -> Result<Self, TaskError> {
-        let provider = ProviderBuilder::new()
-            .with_recommended_fillers()
-            .wallet(EthereumWallet::from(private_key))
-            .on_http(rpc_url);
+    /// Deployment at `address`. Tasks recorded without one predate serving
+    /// several DSSs and belong to the TxnVerifier.
+    pub fn dss(&self, address: Option<Address>) -> Result<&Dss, TaskError> {
+        match address {
+            None => self
+                .deployments
+                .first()
+                .ok_or_else(|| TaskError::ContractError("No DSS is configured".to_string())),
+            Some(address) => self
+                .deployments
+                .iter()
+                .find(|dss| dss.deployment.address == address)
+                .ok_or_else(|| {
+                    TaskError::ContractError(format!("DSS {address} is not configured"))
+                }),
+        }
+    }
 
-        let vault_instance = VaultAbi::new(vault_address, provider.clone());
+    /// Send a task response to `dss` without waiting for it to be mined. A
+    /// `nonce` replaces the transaction sent with it; fees default to the
+    /// current estimate. Returns the transaction hash and the nonce used.
+    #[instrument(skip(self, dss, task, task_response, fees), fields(dss = %dss.deployment), err)]
+    pub async fn send_task_response(
+        &self,
+        dss: &Dss,
+        task_id: B256,
+        task: &DssTask,
+        task_response: &DssResponse,
+        nonce: Option<u64>,
+        fees: Eip1559Estimation,
+    ) -> Result<(B256, u64), TaskError> {
+        let nonce = match nonce {
+            Some(nonce) => nonce,
+            None => self
+                .provider
+                .get_transaction_count(self.account)
+                .block_id(BlockId::pending())
+                .await
+                .map_err(|e| {
+                    metrics().rpc_error("eth_getTransactionCount");
+                    TaskError::ContractError(e.to_string())
+                })?,
+        };
+        info!("Submitting task response for {} with nonce {}", task_id, nonce);
 
-        Ok(Self {
-            vault_instance,
-            provider,
-        })
+        let tx_hash = dss
+            .send_response(task_id, task, task_response, nonce, fees)
+            .await?;
+        Ok((tx_hash, nonce))
+    }
+
+    /// Outcome of a mined task response transaction, with the revert reason
+    /// recovered by replaying the call at its block when it failed.
+    pub async fn task_response_outcome(
+        &self,
+        dss: &Dss,
+        receipt: &TransactionReceipt,
+        task_id: B256,
+        task: &DssTask,
+        task_response: &DssResponse,
+    ) -> TxOutcome {
+        let mut outcome = TxOutcome::from_receipt(receipt);
+        if outcome.success == Some(false) {
+            if let Some(block_number) = receipt.block_number {
+                outcome.revert_reason = dss
+                    .revert_reason(task_id, task, task_response, block_number)
+                    .await;
+            }
+            warn!("Task response transaction {} failed: {:?}", receipt.transaction_hash, outcome.revert_reason);
+        }
+        outcome
     }
 }
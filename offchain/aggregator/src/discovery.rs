@@ -0,0 +1,496 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Arc,
+    time::Duration,
+};
+
+use alloy::{
+    primitives::Address,
+    providers::Provider,
+    rpc::types::Log,
+    sol_types::{SolCall, SolEvent},
+};
+use eyre::Result;
+use serde::Serialize;
+use tokio::{signal, time};
+use tracing::{error, info, warn};
+use url::Url;
+
+use crate::{
+    aggregator::OperatorState,
+    contract::{
+        ContractManager, EigenAVSDirectory, EigenRestaking, KarakCoreEvents, SymbioticRestaking,
+    },
+    metrics::metrics,
+    scanner::LogScanner,
+    Config,
+};
+
+/// `IAVSDirectory.OperatorAVSRegistrationStatus.REGISTERED`
+const EIGEN_STATUS_REGISTERED: u8 = 1;
+
+/// Restaking protocol an operator registration was found in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Restaking {
+    Karak,
+    EigenLayer,
+    Symbiotic,
+}
+
+/// An operator registered on chain, with the endpoint it registered if any.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ChainOperator {
+    pub restaking: BTreeSet<Restaking>,
+    pub url: Option<Url>,
+}
+
+struct Source {
+    restaking: Restaking,
+    scanner: LogScanner,
+}
+
+/// Registrations found on chain, by operator and restaking protocol.
+#[derive(Debug, Default)]
+struct Registrations {
+    operators: HashMap<(Address, Restaking), Option<Url>>,
+    /// Endpoints of the Symbiotic `OperatorRegistered` events, kept while an
+    /// operator is opted out since none is emitted when it opts back in
+    symbiotic_endpoints: HashMap<Address, Option<Url>>,
+}
+
+impl Registrations {
+    fn register(&mut self, operator: Address, restaking: Restaking, url: Option<Url>) {
+        if restaking == Restaking::Symbiotic {
+            self.symbiotic_endpoints.insert(operator, url.clone());
+        }
+        self.operators.insert((operator, restaking), url);
+    }
+
+    fn unregister(&mut self, operator: Address, restaking: Restaking) {
+        self.operators.remove(&(operator, restaking));
+    }
+
+    fn contains(&self, operator: &Address) -> bool {
+        self.operators
+            .keys()
+            .any(|(address, _)| address == operator)
+    }
+
+    /// Operators registered through `restaking`.
+    fn of(&self, restaking: Restaking) -> BTreeSet<Address> {
+        self.operators
+            .keys()
+            .filter(|(_, r)| *r == restaking)
+            .map(|(address, _)| *address)
+            .collect()
+    }
+
+    // Apply the result of a contract view of `restaking` at one block:
+    // operators it reports registered are added, with the endpoint of their
+    // registration event if any, and the others removed
+    fn reconcile(
+        &mut self,
+        restaking: Restaking,
+        checked: impl IntoIterator<Item = (Address, bool)>,
+    ) {
+        for (operator, registered) in checked {
+            if !registered {
+                self.unregister(operator, restaking);
+                continue;
+            }
+            let url = match restaking {
+                Restaking::Symbiotic => self.symbiotic_endpoints.get(&operator).cloned().flatten(),
+                _ => None,
+            };
+            self.operators.entry((operator, restaking)).or_insert(url);
+        }
+    }
+
+    fn chain_operators(&self) -> HashMap<Address, ChainOperator> {
+        let mut registered: HashMap<Address, ChainOperator> = HashMap::new();
+        for ((address, restaking), url) in &self.operators {
+            let operator = registered.entry(*address).or_default();
+            operator.restaking.insert(*restaking);
+            if operator.url.is_none() {
+                operator.url = url.clone();
+            }
+        }
+        registered
+    }
+}
+
+/// Builds the operator set from chain state and hands it to `OperatorState`:
+///
+/// - Karak: `RegisteredOperatorToDSS`/`UnregisteredOperatorToDSS` of the core for
+///   this DSS, cross-checked with `isOperatorRegistered`, which also covers
+///   announced operators that registered before the scanned range.
+/// - EigenLayer: `OperatorAVSRegistrationStatusUpdated` of the AVS directory; the
+///   endpoint is the `rpc` argument of the middleware `registerOperator` call.
+/// - Symbiotic: `OperatorRegistered(operator, rpc)` of the middleware. The
+///   middleware has no deregistration, so every known operator is re-checked
+///   to be opted in to its network.
+pub struct OperatorDiscovery {
+    contract_manager: ContractManager,
+    operator_state: Arc<OperatorState>,
+    dss_address: Address,
+    eigen_avs_address: Option<Address>,
+    symbiotic_middleware_address: Option<Address>,
+    sources: Vec<Source>,
+    next_block: u64,
+    registrations: Registrations,
+    interval: Duration,
+}
+
+impl OperatorDiscovery {
+    pub fn new(operator_state: Arc<OperatorState>, config: &Config) -> Result<Self> {
+        let contract_manager = ContractManager::new(config)?;
+        let dss_address = config.txn_verifier_address;
+
+        let mut sources = vec![Source {
+            restaking: Restaking::Karak,
+            scanner: LogScanner::new(config.core_address)
+                .with_topic0(KarakCoreEvents::RegisteredOperatorToDSS::SIGNATURE_HASH)
+                .with_topic0(KarakCoreEvents::UnregisteredOperatorToDSS::SIGNATURE_HASH),
+        }];
+
+        let eigen_avs_address = match (config.eigen_avs_directory_address, config.eigen_avs_address)
+        {
+            (Some(avs_directory), Some(avs)) => {
+                sources.push(Source {
+                    restaking: Restaking::EigenLayer,
+                    scanner: LogScanner::new(avs_directory).with_topic0(
+                        EigenAVSDirectory::OperatorAVSRegistrationStatusUpdated::SIGNATURE_HASH,
+                    ),
+                });
+                Some(avs)
+            }
+            (None, None) => None,
+            _ => eyre::bail!(
+                "EIGEN_AVS_DIRECTORY_ADDRESS and EIGEN_AVS_ADDRESS must be set together"
+            ),
+        };
+
+        if let Some(middleware) = config.symbiotic_middleware_address {
+            sources.push(Source {
+                restaking: Restaking::Symbiotic,
+                scanner: LogScanner::new(middleware)
+                    .with_topic0(SymbioticRestaking::OperatorRegistered::SIGNATURE_HASH),
+            });
+        }
+
+        Ok(Self {
+            contract_manager,
+            operator_state,
+            dss_address,
+            eigen_avs_address,
+            symbiotic_middleware_address: config.symbiotic_middleware_address,
+            sources,
+            next_block: config.discovery_start_block.unwrap_or(0),
+            registrations: Registrations::default(),
+            interval: Duration::from_millis(config.heartbeat),
+        })
+    }
+
+    pub async fn start(mut self) {
+        info!("Discovering operators from block {}", self.next_block);
+
+        tokio::spawn(async move {
+            let mut interval = time::interval(self.interval);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {
+                        if let Err(e) = self.poll().await {
+                            error!("Failed to discover operators: {e}");
+                        }
+                    }
+                    _ = signal::ctrl_c() => {
+                        info!("Received shutdown signal. Stopping operator discovery...");
+                        break;
+                    }
+                }
+            }
+        });
+    }
+
+    async fn poll(&mut self) -> Result<()> {
+        let latest_block = self
+            .contract_manager
+            .provider
+            .get_block_number()
+            .await
+            .inspect_err(|_| metrics().rpc_error("eth_blockNumber"))?;
+
+        if self.next_block <= latest_block {
+            let mut next_block = latest_block + 1;
+            let mut found = Vec::new();
+            for source in &self.sources {
+                let outcome = source
+                    .scanner
+                    .scan(
+                        &self.contract_manager.provider,
+                        self.next_block,
+                        latest_block,
+                        |_| {},
+                    )
+                    .await;
+                if let Some(e) = &outcome.interrupted {
+                    warn!("{:?} registration scan interrupted: {e}", source.restaking);
+                    next_block = next_block.min(outcome.next_block);
+                }
+                found.push((source.restaking, outcome.logs));
+            }
+
+            // Replaying a range after an interruption is harmless: each source's
+            // logs are applied in order, so the last event of an operator wins
+            for (restaking, logs) in found {
+                for log in logs {
+                    metrics()
+                        .events_ingested
+                        .with_label_values(&[registration_event(restaking, &log)])
+                        .inc();
+                    self.apply(restaking, &log).await?;
+                }
+            }
+            self.next_block = next_block;
+        }
+
+        self.check_karak_registrations(latest_block).await;
+        self.check_symbiotic_registrations(latest_block).await;
+
+        self.operator_state
+            .update_registrations(self.registrations.chain_operators())
+    }
+
+    async fn apply(&mut self, restaking: Restaking, log: &Log) -> Result<()> {
+        match restaking {
+            Restaking::Karak => match log.topic0() {
+                Some(&KarakCoreEvents::RegisteredOperatorToDSS::SIGNATURE_HASH) => {
+                    let KarakCoreEvents::RegisteredOperatorToDSS { operator, dss } =
+                        log.log_decode()?.inner.data;
+                    if dss == self.dss_address {
+                        self.registrations.register(operator, restaking, None);
+                    }
+                }
+                Some(&KarakCoreEvents::UnregisteredOperatorToDSS::SIGNATURE_HASH) => {
+                    let KarakCoreEvents::UnregisteredOperatorToDSS { operator, dss } =
+                        log.log_decode()?.inner.data;
+                    if dss == self.dss_address {
+                        self.registrations.unregister(operator, restaking);
+                    }
+                }
+                _ => {}
+            },
+            Restaking::EigenLayer => {
+                let EigenAVSDirectory::OperatorAVSRegistrationStatusUpdated {
+                    operator,
+                    avs,
+                    status,
+                } = log.log_decode()?.inner.data;
+                if Some(avs) != self.eigen_avs_address {
+                    return Ok(());
+                }
+                if status == EIGEN_STATUS_REGISTERED {
+                    let url = self.eigen_registered_rpc(log).await;
+                    self.registrations.register(operator, restaking, url);
+                } else {
+                    self.registrations.unregister(operator, restaking);
+                }
+            }
+            Restaking::Symbiotic => {
+                let SymbioticRestaking::OperatorRegistered { operator, rpc } =
+                    log.log_decode()?.inner.data;
+                self.registrations
+                    .register(operator, restaking, parse_endpoint(operator, &rpc));
+            }
+        }
+        Ok(())
+    }
+
+    // The AVS directory event carries no endpoint, so decode it from the
+    // middleware call of the registering transaction. Registrations sent
+    // through a multisig or another contract have none.
+    async fn eigen_registered_rpc(&self, log: &Log) -> Option<Url> {
+        let tx_hash = log.transaction_hash?;
+        let tx = match self
+            .contract_manager
+            .provider
+            .get_transaction_by_hash(tx_hash)
+            .await
+        {
+            Ok(tx) => tx?,
+            Err(e) => {
+                metrics().rpc_error("eth_getTransactionByHash");
+                warn!("Failed to fetch EigenLayer registration {tx_hash}: {e}");
+                return None;
+            }
+        };
+        let call = EigenRestaking::registerOperatorCall::abi_decode(&tx.input, true).ok()?;
+        parse_endpoint(tx.from, &call.rpc)
+    }
+
+    // Re-check every known Karak operator and every announced operator not yet
+    // known on chain with `isOperatorRegistered`, batched at `block`. RPC
+    // failures keep the current state.
+    async fn check_karak_registrations(&mut self, block: u64) {
+        let announced = match self.operator_state.announced() {
+            Ok(announced) => announced,
+            Err(e) => {
+                error!("Failed to read announced operators: {e}");
+                return;
+            }
+        };
+
+        let mut candidates = self.registrations.of(Restaking::Karak);
+        candidates.extend(
+            announced
+                .keys()
+                .filter(|address| !self.registrations.contains(address)),
+        );
+        let candidates: Vec<Address> = candidates.into_iter().collect();
+        if candidates.is_empty() {
+            return;
+        }
+
+        let registered = match self
+            .contract_manager
+            .are_operators_registered(&candidates, block)
+            .await
+        {
+            Ok(registered) => registered,
+            Err(e) => {
+                warn!(
+                    "Failed to check registration of {} operators: {e}",
+                    candidates.len()
+                );
+                return;
+            }
+        };
+        self.registrations
+            .reconcile(Restaking::Karak, candidates.into_iter().zip(registered));
+    }
+
+    // Re-check every operator that registered with the Symbiotic middleware,
+    // including those that opted out earlier, for being opted in to its
+    // network at `block`. RPC failures keep the current state.
+    async fn check_symbiotic_registrations(&mut self, block: u64) {
+        let Some(middleware) = self.symbiotic_middleware_address else {
+            return;
+        };
+        let candidates: Vec<Address> = self
+            .registrations
+            .symbiotic_endpoints
+            .keys()
+            .copied()
+            .collect();
+        if candidates.is_empty() {
+            return;
+        }
+
+        let opted_in = match self
+            .contract_manager
+            .are_operators_opted_in(middleware, &candidates, block)
+            .await
+        {
+            Ok(opted_in) => opted_in,
+            Err(e) => {
+                warn!(
+                    "Failed to check opt-in of {} Symbiotic operators: {e}",
+                    candidates.len()
+                );
+                return;
+            }
+        };
+        self.registrations
+            .reconcile(Restaking::Symbiotic, candidates.into_iter().zip(opted_in));
+    }
+}
+
+fn registration_event(restaking: Restaking, log: &Log) -> &'static str {
+    match (restaking, log.topic0()) {
+        (Restaking::Karak, Some(&KarakCoreEvents::UnregisteredOperatorToDSS::SIGNATURE_HASH)) => {
+            "UnregisteredOperatorToDSS"
+        }
+        (Restaking::Karak, _) => "RegisteredOperatorToDSS",
+        (Restaking::EigenLayer, _) => "OperatorAVSRegistrationStatusUpdated",
+        (Restaking::Symbiotic, _) => "OperatorRegistered",
+    }
+}
+
+fn parse_endpoint(operator: Address, rpc: &str) -> Option<Url> {
+    match Url::parse(rpc) {
+        Ok(url) => Some(url),
+        Err(e) => {
+            warn!("Operator {operator} registered an invalid endpoint {rpc:?}: {e}");
+            None
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::address;
+
+    use super::*;
+
+    const FIRST: Address = address!("1111111111111111111111111111111111111111");
+    const SECOND: Address = address!("2222222222222222222222222222222222222222");
+
+    fn url(port: u16) -> Option<Url> {
+        Some(Url::parse(&format!("https://operator:{port}")).unwrap())
+    }
+
+    #[test]
+    fn karak_operators_follow_events_and_checks() {
+        let mut registrations = Registrations::default();
+        registrations.register(FIRST, Restaking::Karak, None);
+        assert_eq!(registrations.of(Restaking::Karak), BTreeSet::from([FIRST]));
+
+        // Registered before the scanned range, found by the check
+        registrations.reconcile(Restaking::Karak, [(FIRST, true), (SECOND, true)]);
+        assert_eq!(
+            registrations.of(Restaking::Karak),
+            BTreeSet::from([FIRST, SECOND])
+        );
+
+        registrations.unregister(FIRST, Restaking::Karak);
+        registrations.reconcile(Restaking::Karak, [(SECOND, false)]);
+        assert!(registrations.of(Restaking::Karak).is_empty());
+        assert!(registrations.chain_operators().is_empty());
+    }
+
+    #[test]
+    fn symbiotic_operators_are_dropped_while_opted_out() {
+        let mut registrations = Registrations::default();
+        registrations.register(FIRST, Restaking::Symbiotic, url(8080));
+        registrations.register(SECOND, Restaking::Symbiotic, url(8081));
+
+        registrations.reconcile(Restaking::Symbiotic, [(FIRST, true), (SECOND, false)]);
+        let operators = registrations.chain_operators();
+        assert_eq!(operators[&FIRST].url, url(8080));
+        assert!(!operators.contains_key(&SECOND));
+
+        // Opting back in emits no event; the endpoint of the registration is kept
+        registrations.reconcile(Restaking::Symbiotic, [(SECOND, true)]);
+        assert_eq!(registrations.chain_operators()[&SECOND].url, url(8081));
+    }
+
+    #[test]
+    fn operators_keep_their_other_registrations() {
+        let mut registrations = Registrations::default();
+        registrations.register(FIRST, Restaking::Karak, None);
+        registrations.register(FIRST, Restaking::Symbiotic, url(8080));
+
+        let operator = &registrations.chain_operators()[&FIRST];
+        assert_eq!(
+            operator.restaking,
+            BTreeSet::from([Restaking::Karak, Restaking::Symbiotic])
+        );
+        assert_eq!(operator.url, url(8080));
+
+        registrations.reconcile(Restaking::Symbiotic, [(FIRST, false)]);
+        let operator = &registrations.chain_operators()[&FIRST];
+        assert_eq!(operator.restaking, BTreeSet::from([Restaking::Karak]));
+        assert_eq!(operator.url, None);
+        assert!(registrations.contains(&FIRST));
+    }
+}
@@ -0,0 +1,523 @@
+use std::{fmt, str::FromStr};
+
+use alloy::{
+    eips::BlockId,
+    primitives::{keccak256, Address, B256, U256},
+    providers::utils::Eip1559Estimation,
+    rpc::types::Log,
+    sol_types::{SolEvent, SolValue},
+    transports::http::ReqwestTransport,
+};
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    contract::{decode_revert, rpc_error, RecommendedProvider, SquareNumberDSS, TxnVerifier},
+    history,
+    scanner::LogScanner,
+    task::{OperatorResponse, Task},
+    TaskError,
+};
+
+/// Kind of DSS contract, which decides the task event, the operator route
+/// answering it and how the answer is submitted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DssKind {
+    /// Tasks from `TxnVerificationResult`, answered by `POST /operator/verify`
+    TxnVerifier,
+    /// Tasks from `TaskRequestGenerated`, answered by `POST /operator/square`
+    SquareNumber,
+}
+
+impl DssKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::TxnVerifier => "txnVerifier",
+            Self::SquareNumber => "squareNumber",
+        }
+    }
+
+    /// Name of the event carrying tasks, also used as a metric label.
+    pub fn event(&self) -> &'static str {
+        match self {
+            Self::TxnVerifier => "TxnVerificationResult",
+            Self::SquareNumber => "TaskRequestGenerated",
+        }
+    }
+
+    fn topic0(&self) -> B256 {
+        match self {
+            Self::TxnVerifier => TxnVerifier::TxnVerificationResult::SIGNATURE_HASH,
+            Self::SquareNumber => SquareNumberDSS::TaskRequestGenerated::SIGNATURE_HASH,
+        }
+    }
+
+    /// Path of the operator route answering tasks of this kind.
+    pub fn operator_path(&self) -> &'static str {
+        match self {
+            Self::TxnVerifier => "operator/verify",
+            Self::SquareNumber => "operator/square",
+        }
+    }
+}
+
+impl FromStr for DssKind {
+    type Err = eyre::Report;
+
+    fn from_str(kind: &str) -> Result<Self> {
+        match kind {
+            "txnVerifier" => Ok(Self::TxnVerifier),
+            "squareNumber" => Ok(Self::SquareNumber),
+            _ => eyre::bail!("Unknown DSS kind {kind}, expected txnVerifier or squareNumber"),
+        }
+    }
+}
+
+impl fmt::Display for DssKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A DSS contract served by the aggregator, written `kind:address` in
+/// `DSS_DEPLOYMENTS`, e.g. `squareNumber:0x9A9f…`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct DssDeployment {
+    pub kind: DssKind,
+    pub address: Address,
+}
+
+impl FromStr for DssDeployment {
+    type Err = eyre::Report;
+
+    fn from_str(deployment: &str) -> Result<Self> {
+        let (kind, address) = deployment
+            .trim()
+            .split_once(':')
+            .ok_or_else(|| eyre::eyre!("Expected kind:address, got {deployment}"))?;
+        Ok(Self {
+            kind: kind.parse()?,
+            address: address
+                .parse()
+                .map_err(|e| eyre::eyre!("Invalid DSS address {address}: {e}"))?,
+        })
+    }
+}
+
+impl TryFrom<String> for DssDeployment {
+    type Error = String;
+
+    fn try_from(deployment: String) -> Result<Self, String> {
+        deployment.parse().map_err(|e: eyre::Report| e.to_string())
+    }
+}
+
+impl From<DssDeployment> for String {
+    fn from(deployment: DssDeployment) -> Self {
+        deployment.to_string()
+    }
+}
+
+impl fmt::Display for DssDeployment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.kind, self.address)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SquareTask {
+    pub value: U256,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SquareResponse {
+    pub response: U256,
+}
+
+/// Task of any DSS kind. Untagged, so operators receive the bare task and
+/// records written before several DSSs were served still read back.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DssTask {
+    TxnVerifier(Task),
+    SquareNumber(SquareTask),
+}
+
+impl DssTask {
+    pub fn kind(&self) -> DssKind {
+        match self {
+            Self::TxnVerifier(_) => DssKind::TxnVerifier,
+            Self::SquareNumber(_) => DssKind::SquareNumber,
+        }
+    }
+
+    /// Id of the task on chain: the verified transaction hash, or the hash of
+    /// the ABI encoded request of a square task.
+    pub fn id(&self) -> Result<B256> {
+        match self {
+            Self::TxnVerifier(task) => history::normalize_hash(&task.transaction_hash),
+            Self::SquareNumber(task) => Ok(keccak256(square_request(task).abi_encode())),
+        }
+    }
+
+    /// Parses an operator's answer to this task.
+    pub fn parse_response(&self, body: &str) -> serde_json::Result<DssResponse> {
+        match self {
+            Self::TxnVerifier(_) => serde_json::from_str(body).map(DssResponse::TxnVerifier),
+            Self::SquareNumber(_) => serde_json::from_str(body).map(DssResponse::SquareNumber),
+        }
+    }
+}
+
+impl fmt::Display for DssTask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TxnVerifier(task) => f.write_str(&task.transaction_hash),
+            Self::SquareNumber(task) => write!(f, "square of {}", task.value),
+        }
+    }
+}
+
+/// Key of a task in the history, the outbox and the in-flight set: its id
+/// scoped by the DSS, since deployments of one kind derive the same id from
+/// the same input.
+pub fn task_key(dss: Address, task_id: B256) -> B256 {
+    keccak256((dss, task_id).abi_encode())
+}
+
+/// Answer to a [`DssTask`], compared between operators to reach the quorum.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DssResponse {
+    TxnVerifier(OperatorResponse),
+    SquareNumber(SquareResponse),
+}
+
+fn square_request(task: &SquareTask) -> SquareNumberDSS::TaskRequest {
+    SquareNumberDSS::TaskRequest { value: task.value }
+}
+
+fn txn_task(task: &Task) -> TxnVerifier::Task {
+    TxnVerifier::Task {
+        transaction_hash: task.transaction_hash.clone(),
+        block_number: task.block_number.clone(),
+    }
+}
+
+fn txn_response(response: &OperatorResponse) -> TxnVerifier::OperatorResponse {
+    TxnVerifier::OperatorResponse {
+        is_included: response.is_included,
+        proposer_index: response.proposer_index.unwrap_or(0),
+        block_number: response.block_number.clone(),
+    }
+}
+
+enum DssContract {
+    TxnVerifier(TxnVerifier::TxnVerifierInstance<ReqwestTransport, RecommendedProvider>),
+    SquareNumber(SquareNumberDSS::SquareNumberDSSInstance<ReqwestTransport, RecommendedProvider>),
+}
+
+/// One served deployment: decodes its task events, reads the responses it
+/// stored and submits new ones.
+pub struct Dss {
+    pub deployment: DssDeployment,
+    contract: DssContract,
+}
+
+impl Dss {
+    pub fn new(deployment: DssDeployment, provider: RecommendedProvider) -> Self {
+        let contract = match deployment.kind {
+            DssKind::TxnVerifier => {
+                DssContract::TxnVerifier(TxnVerifier::new(deployment.address, provider))
+            }
+            DssKind::SquareNumber => {
+                DssContract::SquareNumber(SquareNumberDSS::new(deployment.address, provider))
+            }
+        };
+        Self {
+            deployment,
+            contract,
+        }
+    }
+
+    /// Scanner of the task events of this deployment.
+    pub fn scanner(&self) -> LogScanner {
+        LogScanner::new(self.deployment.address).with_topic0(self.deployment.kind.topic0())
+    }
+
+    /// Task carried by a task event, `None` for other logs.
+    pub fn decode_task(&self, log: &Log) -> Result<Option<DssTask>> {
+        if log.topic0() != Some(&self.deployment.kind.topic0()) {
+            return Ok(None);
+        }
+        let task = match self.deployment.kind {
+            DssKind::TxnVerifier => {
+                let TxnVerifier::TxnVerificationResult {
+                    txnHash,
+                    blockNumber,
+                } = log.log_decode()?.inner.data;
+                DssTask::TxnVerifier(Task {
+                    transaction_hash: txnHash.to_string(),
+                    block_number: blockNumber.to_string(),
+                    pubkey: None,
+                })
+            }
+            DssKind::SquareNumber => {
+                let SquareNumberDSS::TaskRequestGenerated { taskRequest, .. } =
+                    log.log_decode()?.inner.data;
+                DssTask::SquareNumber(SquareTask {
+                    value: taskRequest.value,
+                })
+            }
+        };
+        Ok(Some(task))
+    }
+
+    fn mismatch(&self, task: &DssTask) -> TaskError {
+        TaskError::ContractError(format!(
+            "{} task {task} does not belong to DSS {}",
+            task.kind(),
+            self.deployment
+        ))
+    }
+
+    /// Response the DSS already stored for a task. The TxnVerifier keeps it
+    /// under the transaction hash once `taskCompleted` is set, or under the
+    /// task itself.
+    pub async fn stored_response(&self, task: &DssTask) -> Result<Option<DssResponse>, TaskError> {
+        match (&self.contract, task) {
+            (DssContract::TxnVerifier(instance), DssTask::TxnVerifier(txn)) => {
+                let transaction_hash = history::normalize_hash(&txn.transaction_hash)
+                    .map_err(|e| TaskError::InvalidTransactionHash(e.to_string()))?;
+                let completed = instance
+                    .taskCompleted(transaction_hash)
+                    .call()
+                    .await
+                    .map_err(rpc_error("taskCompleted"))?
+                    ._0;
+                let response = if completed {
+                    instance
+                        .getTaskResponse(transaction_hash)
+                        .call()
+                        .await
+                        .map_err(rpc_error("getTaskResponse"))?
+                        ._0
+                } else {
+                    let response = instance
+                        .getTaskResponseVerifiy(txn_task(txn))
+                        .call()
+                        .await
+                        .map_err(rpc_error("getTaskResponseVerifiy"))?
+                        ._0;
+                    // A missing response reads as the zero value
+                    if response.block_number.is_empty() {
+                        return Ok(None);
+                    }
+                    response
+                };
+                Ok(Some(DssResponse::TxnVerifier(OperatorResponse {
+                    is_included: response.is_included,
+                    proposer_index: Some(response.proposer_index),
+                    block_number: response.block_number,
+                })))
+            }
+            (DssContract::SquareNumber(instance), DssTask::SquareNumber(square)) => {
+                let completed = instance
+                    .isTaskCompleted(square_request(square))
+                    .call()
+                    .await
+                    .map_err(rpc_error("isTaskCompleted"))?
+                    ._0;
+                if !completed {
+                    return Ok(None);
+                }
+                let response = instance
+                    .getTaskResponse(square_request(square))
+                    .call()
+                    .await
+                    .map_err(rpc_error("getTaskResponse"))?
+                    ._0;
+                Ok(Some(DssResponse::SquareNumber(SquareResponse {
+                    response: response.response,
+                })))
+            }
+            _ => Err(self.mismatch(task)),
+        }
+    }
+
+    /// Send a task response with the given nonce and fees, returning the
+    /// transaction hash.
+    pub async fn send_response(
+        &self,
+        task_id: B256,
+        task: &DssTask,
+        response: &DssResponse,
+        nonce: u64,
+        fees: Eip1559Estimation,
+    ) -> Result<B256, TaskError> {
+        let pending_tx = match (&self.contract, task, response) {
+            (
+                DssContract::TxnVerifier(instance),
+                DssTask::TxnVerifier(_),
+                DssResponse::TxnVerifier(response),
+            ) => {
+                instance
+                    .submitTaskResponse(task_id, txn_response(response))
+                    .nonce(nonce)
+                    .max_fee_per_gas(fees.max_fee_per_gas)
+                    .max_priority_fee_per_gas(fees.max_priority_fee_per_gas)
+                    .send()
+                    .await
+            }
+            (
+                DssContract::SquareNumber(instance),
+                DssTask::SquareNumber(square),
+                DssResponse::SquareNumber(response),
+            ) => {
+                instance
+                    .submitTaskResponse(
+                        square_request(square),
+                        SquareNumberDSS::TaskResponse {
+                            response: response.response,
+                        },
+                    )
+                    .nonce(nonce)
+                    .max_fee_per_gas(fees.max_fee_per_gas)
+                    .max_priority_fee_per_gas(fees.max_priority_fee_per_gas)
+                    .send()
+                    .await
+            }
+            _ => return Err(self.mismatch(task)),
+        }
+        .map_err(rpc_error("submitTaskResponse"))?;
+        Ok(*pending_tx.tx_hash())
+    }
+
+    /// Why a mined task response reverted, found by replaying the call at its
+    /// block.
+    pub async fn revert_reason(
+        &self,
+        task_id: B256,
+        task: &DssTask,
+        response: &DssResponse,
+        block_number: u64,
+    ) -> Option<String> {
+        let replayed = match (&self.contract, task, response) {
+            (
+                DssContract::TxnVerifier(instance),
+                DssTask::TxnVerifier(_),
+                DssResponse::TxnVerifier(response),
+            ) => instance
+                .submitTaskResponse(task_id, txn_response(response))
+                .block(BlockId::number(block_number))
+                .call()
+                .await
+                .map(|_| ()),
+            (
+                DssContract::SquareNumber(instance),
+                DssTask::SquareNumber(square),
+                DssResponse::SquareNumber(response),
+            ) => instance
+                .submitTaskResponse(
+                    square_request(square),
+                    SquareNumberDSS::TaskResponse {
+                        response: response.response,
+                    },
+                )
+                .block(BlockId::number(block_number))
+                .call()
+                .await
+                .map(|_| ()),
+            _ => return Some(self.mismatch(task).to_string()),
+        };
+        replayed
+            .err()
+            .map(|e| decode_revert(&e).unwrap_or_else(|| e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::address;
+
+    use super::*;
+
+    #[test]
+    fn parses_deployments() {
+        let deployment: DssDeployment = " squareNumber:0x9A9f2CCfdE556A7E9Ff0848998Aa4a0CFD8863AE "
+            .parse()
+            .unwrap();
+        assert_eq!(
+            deployment,
+            DssDeployment {
+                kind: DssKind::SquareNumber,
+                address: address!("9A9f2CCfdE556A7E9Ff0848998Aa4a0CFD8863AE"),
+            }
+        );
+        assert_eq!(
+            deployment.to_string().parse::<DssDeployment>().unwrap(),
+            deployment
+        );
+        assert_eq!(
+            "txnVerifier:0x9A9f2CCfdE556A7E9Ff0848998Aa4a0CFD8863AE"
+                .parse::<DssDeployment>()
+                .unwrap()
+                .kind,
+            DssKind::TxnVerifier
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_deployments() {
+        for deployment in [
+            "0x9A9f2CCfdE556A7E9Ff0848998Aa4a0CFD8863AE",
+            "cubeNumber:0x9A9f2CCfdE556A7E9Ff0848998Aa4a0CFD8863AE",
+            "SquareNumber:0x9A9f2CCfdE556A7E9Ff0848998Aa4a0CFD8863AE",
+            "squareNumber:0x1234",
+            "squareNumber:",
+        ] {
+            assert!(deployment.parse::<DssDeployment>().is_err(), "{deployment}");
+        }
+    }
+
+    #[test]
+    fn deserializes_deployments_from_settings() {
+        let deployments: Vec<DssDeployment> =
+            serde_json::from_str(r#"["squareNumber:0x9A9f2CCfdE556A7E9Ff0848998Aa4a0CFD8863AE"]"#)
+                .unwrap();
+        assert_eq!(deployments[0].kind, DssKind::SquareNumber);
+        assert!(serde_json::from_str::<Vec<DssDeployment>>(r#"["squareNumber"]"#).is_err());
+    }
+
+    #[test]
+    fn task_keys_are_scoped_by_dss() {
+        let first = address!("9A9f2CCfdE556A7E9Ff0848998Aa4a0CFD8863AE");
+        let second = address!("1111111111111111111111111111111111111111");
+        let square = DssTask::SquareNumber(SquareTask {
+            value: U256::from(7),
+        });
+        let task_id = square.id().unwrap();
+        assert_eq!(
+            square.id().unwrap(),
+            DssTask::SquareNumber(SquareTask {
+                value: U256::from(7)
+            })
+            .id()
+            .unwrap()
+        );
+        assert_eq!(task_key(first, task_id), task_key(first, task_id));
+        assert_ne!(task_key(first, task_id), task_key(second, task_id));
+
+        let txn = DssTask::TxnVerifier(Task {
+            transaction_hash: format!("{:#x}", B256::repeat_byte(0xab)),
+            block_number: "100".to_string(),
+            pubkey: None,
+        });
+        let txn_id = txn.id().unwrap();
+        assert_eq!(txn_id, B256::repeat_byte(0xab));
+        assert_ne!(task_key(first, txn_id), task_key(second, txn_id));
+        assert_ne!(task_key(first, txn_id), txn_id);
+    }
+}
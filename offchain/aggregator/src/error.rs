@@ -5,19 +5,54 @@ use axum::{
 };
 use serde_json::json;
 
-pub struct AppError(eyre::Report);
+pub struct AppError {
+    status: StatusCode,
+    error: eyre::Report,
+}
+
+impl AppError {
+    pub fn bad_request(error: eyre::Report) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            error,
+        }
+    }
+
+    pub fn unauthorized(error: eyre::Report) -> Self {
+        Self {
+            status: StatusCode::UNAUTHORIZED,
+            error,
+        }
+    }
+
+    pub fn too_many_requests(error: eyre::Report) -> Self {
+        Self {
+            status: StatusCode::TOO_MANY_REQUESTS,
+            error,
+        }
+    }
+
+    pub fn conflict(error: eyre::Report) -> Self {
+        Self {
+            status: StatusCode::CONFLICT,
+            error,
+        }
+    }
+}
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        tracing::error!(error = %self.0);
-        let status = StatusCode::INTERNAL_SERVER_ERROR;
-        (status, Json(json!({ "error": self.0.to_string() }))).into_response()
+        tracing::error!(error = %self.error);
+        (self.status, Json(json!({ "error": self.error.to_string() }))).into_response()
     }
 }
 
 impl From<eyre::Report> for AppError {
     fn from(error: eyre::Report) -> Self {
-        Self(error)
+        Self {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            error,
+        }
     }
 }
 
@@ -1,32 +1,222 @@
+use std::{future::Future, sync::Arc, time::Duration};
+
+use alloy::{
+    primitives::{Address, U256},
+    providers::{Provider, ProviderBuilder, ReqwestProvider},
+    rpc::types::SyncStatus,
+};
 use axum::{
+    extract::State,
     http::StatusCode,
     response::{IntoResponse, Response},
     Json,
 };
+use eyre::Result;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::{aggregator::OperatorState, metrics::metrics, Config};
+
+/// Time each readiness check may take before it fails.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum Status {
     Ok,
     Warn,
     Fail,
 }
 
+/// Outcome of one dependency check.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Check {
+    pub name: String,
+    pub status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl Check {
+    fn new(name: &str, status: Status, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            detail: Some(detail.into()),
+        }
+    }
+
+    fn ok(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            status: Status::Ok,
+            detail: None,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct HealthCheck {
     status: Status,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    checks: Vec<Check>,
+}
+
+impl HealthCheck {
+    /// Status of the worst check.
+    pub fn from_checks(checks: Vec<Check>) -> Self {
+        let status = checks
+            .iter()
+            .map(|check| check.status)
+            .max()
+            .unwrap_or(Status::Ok);
+        Self { status, checks }
+    }
 }
 
 impl IntoResponse for HealthCheck {
     fn into_response(self) -> Response {
         let code = match self.status {
             Status::Ok | Status::Warn => StatusCode::OK,
-            Status::Fail => StatusCode::INTERNAL_SERVER_ERROR,
+            Status::Fail => StatusCode::SERVICE_UNAVAILABLE,
         };
         (code, Json(self)).into_response()
     }
 }
 
+/// Liveness: the process is up and serving requests.
 pub async fn health_check() -> HealthCheck {
-    HealthCheck { status: Status::Ok }
+    HealthCheck {
+        status: Status::Ok,
+        checks: Vec::new(),
+    }
+}
+
+/// Dependencies the aggregator needs to process tasks.
+pub struct Readiness {
+    provider: ReqwestProvider,
+    account: Address,
+    operator_state: Arc<OperatorState>,
+    quorum: usize,
+    max_lag: u64,
+    /// Served DSSs, as labelled in the event cursor metrics
+    deployments: Vec<String>,
+}
+
+impl Readiness {
+    pub fn new(config: &Config, operator_state: Arc<OperatorState>) -> Result<Self> {
+        Ok(Self {
+            provider: ProviderBuilder::new().on_http(config.rpc_url.clone()),
+            account: config.get_private_key()?.address(),
+            operator_state,
+            quorum: config.task_quorum.max(1),
+            max_lag: config.health_max_lag_blocks,
+            deployments: config
+                .deployments()
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+        })
+    }
+
+    async fn check_rpc(&self) -> Check {
+        let name = "execution_rpc";
+        match self.provider.syncing().await {
+            Ok(SyncStatus::None) => Check::ok(name),
+            Ok(SyncStatus::Info(info)) => Check::new(
+                name,
+                Status::Warn,
+                format!(
+                    "Syncing, at block {} of {}",
+                    info.current_block, info.highest_block
+                ),
+            ),
+            Err(e) => Check::new(name, Status::Fail, e.to_string()),
+        }
+    }
+
+    // The key is loaded at startup, so this checks it can pay for submissions
+    async fn check_signer(&self) -> Check {
+        let name = "signer";
+        match self.provider.get_balance(self.account).await {
+            Ok(balance) if balance == U256::ZERO => Check::new(
+                name,
+                Status::Warn,
+                format!("{} has no funds to submit responses", self.account),
+            ),
+            Ok(_) => Check::ok(name),
+            Err(e) => Check::new(name, Status::Fail, e.to_string()),
+        }
+    }
+
+    fn check_event_cursor(&self) -> Check {
+        let name = "event_cursor";
+        let mut behind = Vec::new();
+        for dss in &self.deployments {
+            let processed = metrics()
+                .last_processed_block
+                .with_label_values(&[dss])
+                .get();
+            let lag = metrics().chain_head_lag.with_label_values(&[dss]).get().max(0) as u64;
+            if processed == 0 {
+                behind.push(format!("{dss}: no blocks scanned yet"));
+            } else if lag > self.max_lag {
+                behind.push(format!(
+                    "{dss}: {lag} blocks behind the chain head at block {processed}"
+                ));
+            }
+        }
+        if behind.is_empty() {
+            Check::ok(name)
+        } else {
+            Check::new(name, Status::Warn, behind.join("; "))
+        }
+    }
+
+    fn check_operators(&self) -> Check {
+        let name = "operators";
+        match self.operator_state.available_operators() {
+            Ok(operators) if operators.is_empty() => {
+                Check::new(name, Status::Fail, "No live operators")
+            }
+            Ok(operators) if operators.len() < self.quorum => Check::new(
+                name,
+                Status::Warn,
+                format!(
+                    "{} live operators, fewer than the quorum of {}",
+                    operators.len(),
+                    self.quorum
+                ),
+            ),
+            Ok(operators) => Check::new(
+                name,
+                Status::Ok,
+                format!("{} live operators", operators.len()),
+            ),
+            Err(e) => Check::new(name, Status::Fail, e.to_string()),
+        }
+    }
+}
+
+async fn within_timeout(name: &str, check: impl Future<Output = Check>) -> Check {
+    match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+        Ok(check) => check,
+        Err(_) => Check::new(
+            name,
+            Status::Fail,
+            format!("No answer within {CHECK_TIMEOUT:?}"),
+        ),
+    }
+}
+
+/// Readiness: whether the aggregator can receive and answer tasks.
+pub async fn readiness_check(State(readiness): State<Arc<Readiness>>) -> HealthCheck {
+    let (rpc, signer) = tokio::join!(
+        within_timeout("execution_rpc", readiness.check_rpc()),
+        within_timeout("signer", readiness.check_signer()),
+    );
+    HealthCheck::from_checks(vec![
+        rpc,
+        signer,
+        readiness.check_event_cursor(),
+        readiness.check_operators(),
+    ])
 }
@@ -0,0 +1,358 @@
+use std::{str::FromStr, sync::Arc};
+
+use alloy::{
+    primitives::{keccak256, Address, B256},
+    rpc::types::Log,
+    sol_types::SolValue,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{
+    contract::TxOutcome,
+    dss::{DssResponse, DssTask},
+    error::AppError,
+};
+
+/// Largest number of records returned by one `GET /aggregator/tasks`.
+pub const MAX_PAGE_SIZE: usize = 500;
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Where a task came from.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum TaskSource {
+    #[serde(rename_all = "camelCase")]
+    Event {
+        contract: Address,
+        event: String,
+        block_number: Option<u64>,
+        transaction_hash: Option<B256>,
+        log_index: Option<u64>,
+    },
+    /// `POST /aggregator/tasks`, with the API key name or signer it was
+    /// authenticated with
+    Api { client: String },
+}
+
+impl TaskSource {
+    pub fn from_log(log: &Log, event: &str) -> Self {
+        Self::Event {
+            contract: log.address(),
+            event: event.to_string(),
+            block_number: log.block_number,
+            transaction_hash: log.transaction_hash,
+            log_index: log.log_index,
+        }
+    }
+
+    /// Block of the source event.
+    pub fn block_number(&self) -> Option<u64> {
+        match self {
+            Self::Event { block_number, .. } => *block_number,
+            Self::Api { .. } => None,
+        }
+    }
+}
+
+/// Answer of one operator to a task, or why there was none.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperatorResult {
+    pub operator: Address,
+    pub url: Url,
+    pub latency_ms: u64,
+    pub response: Option<DssResponse>,
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TaskStatus {
+    /// Received, not yet answered
+    Pending,
+    /// No operator was available to answer
+    NoOperators,
+    /// Every operator failed to answer
+    OperatorsFailed,
+    /// Answered by an operator, not submitted on chain
+    Answered,
+    /// Response waiting in the outbox to be sent
+    Queued,
+    /// Response sent, not yet confirmed
+    Submitted,
+    /// Response mined successfully
+    Confirmed,
+    /// Response could not be sent or reverted
+    SubmissionFailed,
+    /// The DSS already held a response, recorded as the result; nothing was sent
+    AlreadySubmitted,
+}
+
+impl TaskStatus {
+    /// Name of the status in the API, also used as a metric label.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::NoOperators => "noOperators",
+            Self::OperatorsFailed => "operatorsFailed",
+            Self::Answered => "answered",
+            Self::Queued => "queued",
+            Self::Submitted => "submitted",
+            Self::Confirmed => "confirmed",
+            Self::SubmissionFailed => "submissionFailed",
+            Self::AlreadySubmitted => "alreadySubmitted",
+        }
+    }
+}
+
+/// Everything the aggregator did for one task.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskRecord {
+    /// Key of the record, see [`crate::dss::task_key`]; zero on records written
+    /// before keys were scoped by DSS, which are stored under the task id
+    #[serde(default)]
+    pub key: B256,
+    pub task: DssTask,
+    /// DSS the task came from; unset on records of the TxnVerifier written
+    /// before several DSSs were served
+    #[serde(default)]
+    pub dss: Option<Address>,
+    pub source: TaskSource,
+    pub status: TaskStatus,
+    pub received_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub responses: Vec<OperatorResult>,
+    /// Response submitted to the DSS
+    pub result: Option<DssResponse>,
+    pub submission: Option<TxOutcome>,
+    pub error: Option<String>,
+}
+
+impl TaskRecord {
+    pub fn new(key: B256, task: DssTask, dss: Address, source: TaskSource) -> Self {
+        let now = Utc::now();
+        Self {
+            key,
+            task,
+            dss: Some(dss),
+            source,
+            status: TaskStatus::Pending,
+            received_at: now,
+            updated_at: now,
+            responses: Vec::new(),
+            result: None,
+            submission: None,
+            error: None,
+        }
+    }
+
+    pub fn set_status(&mut self, status: TaskStatus) {
+        self.status = status;
+        self.updated_at = Utc::now();
+    }
+
+    /// Record of the same task submitted over HTTP, kept apart from the
+    /// record of its on-chain event and of earlier submissions.
+    pub fn api(key: B256, task: DssTask, dss: Address, client: String) -> Self {
+        let mut record = Self::new(key, task, dss, TaskSource::Api { client });
+        let received_at = record.received_at.timestamp_nanos_opt().unwrap_or_default();
+        record.key = keccak256((key, received_at as u64).abi_encode());
+        record
+    }
+
+    /// Key the record is stored under.
+    pub fn storage_key(&self) -> Result<B256> {
+        if self.key.is_zero() {
+            self.task.id()
+        } else {
+            Ok(self.key)
+        }
+    }
+}
+
+/// Filters of `GET /aggregator/tasks`. Records are returned newest first.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskFilter {
+    pub status: Option<TaskStatus>,
+    /// Only tasks this operator answered or failed
+    pub operator: Option<Address>,
+    /// Range of the block the source event was emitted in
+    pub from_block: Option<u64>,
+    pub to_block: Option<u64>,
+    /// Range of the time the task was received
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+impl TaskFilter {
+    fn matches(&self, record: &TaskRecord) -> bool {
+        let block = record.source.block_number();
+        self.status.map_or(true, |status| record.status == status)
+            && self.operator.map_or(true, |operator| {
+                record.responses.iter().any(|r| r.operator == operator)
+            })
+            && self
+                .from_block
+                .map_or(true, |from| block.map_or(false, |b| b >= from))
+            && self
+                .to_block
+                .map_or(true, |to| block.map_or(false, |b| b <= to))
+            && self.since.map_or(true, |since| record.received_at >= since)
+            && self.until.map_or(true, |until| record.received_at <= until)
+    }
+}
+
+/// Task records in an embedded database, keyed by [`crate::dss::task_key`].
+/// A task seen again replaces the earlier record.
+#[derive(Clone, Debug)]
+pub struct TaskHistory {
+    tasks: sled::Tree,
+}
+
+impl TaskHistory {
+    pub fn new(db: &sled::Db) -> Result<Self> {
+        let tasks = db.open_tree("tasks")?;
+        Ok(Self { tasks })
+    }
+
+    pub fn put(&self, record: &TaskRecord) -> Result<()> {
+        let key = record.storage_key()?;
+        self.tasks
+            .insert(key.as_slice(), serde_json::to_vec(record)?)?;
+        self.tasks.flush()?;
+        Ok(())
+    }
+
+    pub fn get(&self, key: &B256) -> Result<Option<TaskRecord>> {
+        match self.tasks.get(key.as_slice())? {
+            Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn list(&self, filter: &TaskFilter) -> Result<Vec<TaskRecord>> {
+        let mut records = Vec::new();
+        for entry in self.tasks.iter() {
+            let (_, value) = entry?;
+            let record: TaskRecord = serde_json::from_slice(&value)?;
+            if filter.matches(&record) {
+                records.push(record);
+            }
+        }
+        records.sort_by(|a, b| b.received_at.cmp(&a.received_at));
+
+        let limit = filter.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+        Ok(records
+            .into_iter()
+            .skip(filter.offset.unwrap_or(0))
+            .take(limit)
+            .collect())
+    }
+}
+
+pub fn normalize_hash(transaction_hash: &str) -> Result<B256> {
+    B256::from_str(transaction_hash)
+        .map_err(|e| eyre::eyre!("Invalid transaction hash {transaction_hash}: {e}"))
+}
+
+pub async fn list_tasks(
+    State(history): State<Arc<TaskHistory>>,
+    Query(filter): Query<TaskFilter>,
+) -> Result<Json<Vec<TaskRecord>>, AppError> {
+    Ok(Json(history.list(&filter)?))
+}
+
+pub async fn get_task(
+    State(history): State<Arc<TaskHistory>>,
+    Path(key): Path<String>,
+) -> Result<Response, AppError> {
+    let key = normalize_hash(&key).map_err(AppError::bad_request)?;
+    Ok(match history.get(&key)? {
+        Some(record) => Json(record).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::{address, U256};
+
+    use super::*;
+    use crate::dss::{task_key, SquareTask};
+
+    fn history() -> TaskHistory {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        TaskHistory::new(&db).unwrap()
+    }
+
+    #[test]
+    fn records_the_same_task_of_two_deployments_apart() {
+        let first = address!("1111111111111111111111111111111111111111");
+        let second = address!("2222222222222222222222222222222222222222");
+        let task = DssTask::SquareNumber(SquareTask {
+            value: U256::from(7),
+        });
+        let task_id = task.id().unwrap();
+        let source = TaskSource::Api {
+            client: "test".to_string(),
+        };
+        let history = history();
+
+        let mut answered = TaskRecord::new(
+            task_key(first, task_id),
+            task.clone(),
+            first,
+            source.clone(),
+        );
+        answered.set_status(TaskStatus::Answered);
+        history.put(&answered).unwrap();
+        history
+            .put(&TaskRecord::new(
+                task_key(second, task_id),
+                task,
+                second,
+                source,
+            ))
+            .unwrap();
+
+        let first_record = history.get(&task_key(first, task_id)).unwrap().unwrap();
+        assert_eq!(first_record.dss, Some(first));
+        assert_eq!(first_record.status, TaskStatus::Answered);
+        let second_record = history.get(&task_key(second, task_id)).unwrap().unwrap();
+        assert_eq!(second_record.dss, Some(second));
+        assert_eq!(second_record.status, TaskStatus::Pending);
+        assert_eq!(history.list(&TaskFilter::default()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn records_without_a_key_stay_under_their_task_id() {
+        let task = DssTask::SquareNumber(SquareTask {
+            value: U256::from(3),
+        });
+        let mut record = TaskRecord::new(
+            B256::ZERO,
+            task.clone(),
+            Address::ZERO,
+            TaskSource::Api {
+                client: "test".to_string(),
+            },
+        );
+        record.dss = None;
+        let history = history();
+        history.put(&record).unwrap();
+        assert!(history.get(&task.id().unwrap()).unwrap().is_some());
+    }
+}
@@ -1,30 +1,316 @@
-use aggregator::aggregator_router;
-use alloy::{primitives::Address, signers::local::PrivateKeySigner};
+use aggregator::{aggregator_router, registration_router, RegistrationAuth};
+use alloy::{
+    primitives::Address,
+    providers::{Provider, ProviderBuilder},
+    signers::local::PrivateKeySigner,
+};
 use axum::{routing::get, Router};
 use eyre::Result;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::{fs, net::IpAddr, str::FromStr, sync::Arc};
-use task::BlockNumberData;
+use task::{BlockNumberData, TaskAuth};
 use thiserror::Error;
 use url::Url;
 
 pub mod aggregator;
 pub mod contract;
+pub mod discovery;
+pub mod dss;
 pub mod error;
 pub mod health;
+pub mod history;
+pub mod limits;
+pub mod liveness;
+pub mod metrics;
+pub mod multicall;
+pub mod outbox;
+pub mod scanner;
+pub mod settings;
+pub mod stake;
 pub mod task;
+pub mod telemetry;
+pub mod tls;
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct Config {
     pub host: IpAddr,
     pub port: u16,
-    pub rpc_url: String,
+    #[serde(serialize_with = "settings::redact_url")]
+    pub rpc_url: Url,
+    #[serde(serialize_with = "settings::redact")]
     pub private_key: String,
-    pub square_number_dss_address: Address,
+    /// TxnVerifier DSS tasks are read from and answered on
     pub txn_verifier_address:Address,
+    /// Further DSSs served next to the TxnVerifier, as `kind:address`
+    #[serde(default)]
+    pub dss_deployments: Vec<dss::DssDeployment>,
     pub core_address: Address,
     pub block_number_store: String,
     pub heartbeat: u64,
+    #[serde(default = "default_confirmations")]
+    pub confirmations: u64,
+    /// First block scanned for operator registrations (defaults to genesis)
+    pub discovery_start_block: Option<u64>,
+    pub eigen_avs_directory_address: Option<Address>,
+    /// AVS the EigenLayer middleware registers operators to (its restaking helper)
+    pub eigen_avs_address: Option<Address>,
+    pub symbiotic_middleware_address: Option<Address>,
+    /// Fixed ETH prices of vault assets, as `asset:price`
+    #[serde(default)]
+    pub asset_prices: Vec<stake::AssetPrice>,
+    /// Chainlink feeds quoting vault assets in ETH, as `asset:feed`, for
+    /// assets without a fixed price
+    #[serde(default)]
+    pub asset_price_feeds: Vec<stake::AssetPriceFeed>,
+    /// Age above which a feed answer is rejected, in seconds
+    #[serde(default = "default_asset_price_max_age_secs")]
+    pub asset_price_max_age_secs: u64,
+    /// ETH value an operator needs staked in a DSS to be sent its tasks; tasks
+    /// go to every available operator when unset
+    pub min_operator_stake: Option<stake::EthAmount>,
+    /// Multicall3 contract stake and registration reads are batched through
+    #[serde(default = "default_multicall_address")]
+    pub multicall_address: Address,
+    /// Calls sent in one multicall request
+    #[serde(default = "default_multicall_batch_size")]
+    pub multicall_batch_size: usize,
+    /// Largest clock difference accepted for signed operator registrations and
+    /// task submissions, in seconds
+    #[serde(default = "default_registration_max_age")]
+    pub registration_max_age: u64,
+    /// Timeout of an operator health probe, in milliseconds
+    #[serde(default = "default_operator_probe_timeout")]
+    pub operator_probe_timeout: u64,
+    #[serde(default = "default_operator_max_failures")]
+    pub operator_max_failures: u32,
+    #[serde(default = "default_operator_quarantine_secs")]
+    pub operator_quarantine_secs: u64,
+    #[serde(default = "default_operator_eviction_secs")]
+    pub operator_eviction_secs: u64,
+    /// Timeout of one task request to an operator, in milliseconds
+    #[serde(default = "default_operator_request_timeout")]
+    pub operator_request_timeout: u64,
+    /// Timeout of connecting to an operator, in milliseconds
+    #[serde(default = "default_operator_connect_timeout")]
+    pub operator_connect_timeout: u64,
+    /// Time a task waits for operator responses in total, in milliseconds
+    #[serde(default = "default_task_deadline")]
+    pub task_deadline: u64,
+    /// Number of identical operator responses that settle a task
+    #[serde(default = "default_task_quorum")]
+    pub task_quorum: usize,
+    /// Times a failed event task is processed again on later ticks
+    #[serde(default = "default_task_retries")]
+    pub task_retries: u32,
+    /// Idle connections kept open per operator
+    #[serde(default = "default_operator_pool_max_idle")]
+    pub operator_pool_max_idle: usize,
+    #[serde(default = "default_operator_pool_idle_secs")]
+    pub operator_pool_idle_secs: u64,
+    #[serde(default = "default_operator_keepalive_secs")]
+    pub operator_keepalive_secs: u64,
+    /// OTLP gRPC endpoint spans are exported to, e.g. `http://localhost:4317`
+    pub otlp_endpoint: Option<String>,
+    #[serde(default = "default_otel_service_name")]
+    pub otel_service_name: String,
+    /// `text` or `json`
+    #[serde(default)]
+    pub log_format: telemetry::LogFormat,
+    /// Event cursor lag, in blocks, above which readiness warns
+    #[serde(default = "default_health_max_lag_blocks")]
+    pub health_max_lag_blocks: u64,
+    /// Directory of the embedded task history database
+    #[serde(default = "default_task_history_path")]
+    pub task_history_path: String,
+    /// API keys accepted by `POST /aggregator/tasks`
+    #[serde(default, serialize_with = "settings::redact_all")]
+    pub task_api_keys: Vec<String>,
+    /// Accounts whose signed submissions `POST /aggregator/tasks` accepts
+    #[serde(default)]
+    pub task_submitters: Vec<Address>,
+    /// API keys accepted by the `/aggregator/outbox` admin routes
+    #[serde(default, serialize_with = "settings::redact_all")]
+    pub admin_api_keys: Vec<String>,
+    /// Interval between outbox passes, in milliseconds
+    #[serde(default = "default_outbox_poll_interval")]
+    pub outbox_poll_interval: u64,
+    #[serde(default = "default_outbox_max_attempts")]
+    pub outbox_max_attempts: u32,
+    /// Delay before the first retry of a submission, doubled on each attempt,
+    /// in milliseconds
+    #[serde(default = "default_outbox_backoff")]
+    pub outbox_backoff: u64,
+    #[serde(default = "default_outbox_max_backoff_secs")]
+    pub outbox_max_backoff_secs: u64,
+    /// Time a sent submission may stay unmined before it is replaced
+    #[serde(default = "default_outbox_resend_after_secs")]
+    pub outbox_resend_after_secs: u64,
+    /// Fee increase of a replacement transaction, in percent
+    #[serde(default = "default_outbox_fee_bump_percent")]
+    pub outbox_fee_bump_percent: u64,
+    /// Window of the rate limits, in milliseconds
+    #[serde(default = "default_common_rate_limit_window_ms")]
+    pub common_rate_limit_window_ms: u64,
+    /// Requests a client IP may make to any route per window
+    #[serde(default = "default_common_rate_limit_max_requests")]
+    pub common_rate_limit_max_requests: u32,
+    /// Requests a client IP may make to the operator registration routes per
+    /// window (defaults to the common limit)
+    pub registration_rate_limit_max_requests: Option<u32>,
+    /// Requests an API key, or a client IP without one, may make to the task
+    /// routes per window (defaults to the common limit)
+    pub task_rate_limit_max_requests: Option<u32>,
+    /// Origins allowed to call the API from a browser, `*` for any; no CORS
+    /// headers are sent when empty
+    #[serde(default)]
+    pub cors_origin: Vec<String>,
+    /// Largest accepted request body, in bytes
+    #[serde(default = "default_max_body_bytes")]
+    pub max_body_bytes: usize,
+    /// Time a request may take before it is answered with 408, in milliseconds
+    #[serde(default = "default_request_timeout_ms")]
+    pub request_timeout_ms: u64,
+    /// Certificate chain and key the HTTP server is served with over TLS;
+    /// plain HTTP when unset
+    pub tls_cert_path: Option<String>,
+    pub tls_key_path: Option<String>,
+    /// Client certificate and key presented to operators that require mutual
+    /// TLS
+    pub tls_client_cert_path: Option<String>,
+    pub tls_client_key_path: Option<String>,
+    /// CA bundle operator certificates are verified against, on top of the
+    /// system roots
+    pub tls_operator_ca_path: Option<String>,
+    /// Interval between checks for rotated certificates, in seconds
+    #[serde(default = "default_tls_reload_interval_secs")]
+    pub tls_reload_interval_secs: u64,
+    /// Deprecated settings that were set, with their replacement
+    #[serde(skip)]
+    pub deprecated_settings: Vec<(&'static str, &'static str)>,
+}
+
+fn default_confirmations() -> u64 {
+    1
+}
+
+fn default_asset_price_max_age_secs() -> u64 {
+    86400
+}
+
+fn default_multicall_address() -> Address {
+    multicall::MULTICALL3_ADDRESS
+}
+
+fn default_multicall_batch_size() -> usize {
+    500
+}
+
+fn default_registration_max_age() -> u64 {
+    300
+}
+
+fn default_operator_probe_timeout() -> u64 {
+    2000
+}
+
+fn default_operator_max_failures() -> u32 {
+    3
+}
+
+fn default_operator_quarantine_secs() -> u64 {
+    60
+}
+
+fn default_operator_eviction_secs() -> u64 {
+    300
+}
+
+fn default_operator_request_timeout() -> u64 {
+    5000
+}
+
+fn default_operator_connect_timeout() -> u64 {
+    2000
+}
+
+fn default_task_deadline() -> u64 {
+    10000
+}
+
+fn default_task_quorum() -> usize {
+    1
+}
+
+fn default_task_retries() -> u32 {
+    5
+}
+
+fn default_operator_pool_max_idle() -> usize {
+    4
+}
+
+fn default_operator_pool_idle_secs() -> u64 {
+    90
+}
+
+fn default_operator_keepalive_secs() -> u64 {
+    60
+}
+
+fn default_otel_service_name() -> String {
+    "aggregator".to_string()
+}
+
+fn default_health_max_lag_blocks() -> u64 {
+    50
+}
+
+fn default_task_history_path() -> String {
+    "task-history".to_string()
+}
+
+fn default_outbox_poll_interval() -> u64 {
+    5000
+}
+
+fn default_outbox_max_attempts() -> u32 {
+    10
+}
+
+fn default_outbox_backoff() -> u64 {
+    2000
+}
+
+fn default_outbox_max_backoff_secs() -> u64 {
+    300
+}
+
+fn default_outbox_resend_after_secs() -> u64 {
+    120
+}
+
+fn default_outbox_fee_bump_percent() -> u64 {
+    20
+}
+
+fn default_common_rate_limit_window_ms() -> u64 {
+    1000
+}
+
+fn default_common_rate_limit_max_requests() -> u32 {
+    100
+}
+
+fn default_max_body_bytes() -> usize {
+    64 * 1024
+}
+
+fn default_request_timeout_ms() -> u64 {
+    10000
+}
+
+fn default_tls_reload_interval_secs() -> u64 {
+    30
 }
 
 #[derive(Debug, Deserialize)]
@@ -36,8 +322,17 @@ pub struct ContractAddresses {
 
 #[derive(Debug, Error)]
 pub enum TaskError {
-    #[error("Contract call error")]
-    ContractCallError,
+    #[error("Contract error: {0}")]
+    ContractError(String),
+
+    #[error("Contract reverted: {0}")]
+    ContractReverted(String),
+
+    #[error("Transaction {} failed: {}", .0.tx_hash, .0.revert_reason.as_deref().unwrap_or("unknown reason"))]
+    TransactionFailed(contract::TxOutcome),
+
+    #[error("Invalid transaction hash: {0}")]
+    InvalidTransactionHash(String),
 
     #[error("Operator not found")]
     OperatorNotFound,
@@ -57,6 +352,9 @@ pub enum TaskError {
     #[error("Custom URL error: {0}")]
     CustomUrlError(String),
 
+    #[error("No ETH price: {0}")]
+    PriceUnavailable(String),
+
     #[error("NoOperatorAvailable")]
     NoOperatorAvailable,
 
@@ -65,18 +363,24 @@ pub enum TaskError {
 }
 
 impl Config {
-    pub fn load_block_number(&self) -> Result<u64, TaskError> {
+    /// The TxnVerifier at `txn_verifier_address` followed by `dss_deployments`.
+    pub fn deployments(&self) -> Vec<dss::DssDeployment> {
+        let mut deployments = vec![dss::DssDeployment {
+            kind: dss::DssKind::TxnVerifier,
+            address: self.txn_verifier_address,
+        }];
+        deployments.extend(&self.dss_deployments);
+        deployments
+    }
+
+    pub fn load_block_numbers(&self) -> Result<BlockNumberData, TaskError> {
         let file_content = fs::read_to_string(&self.block_number_store)
             .map_err(|e| TaskError::LoadContractJsonError(e.to_string()))?;
 
         let block_number_data: BlockNumberData = serde_json::from_str(&file_content)
             .map_err(|e| TaskError::LoadContractJsonError(e.to_string()))?;
 
-        Ok(block_number_data.block_number)
-    }
-
-    pub fn get_rpc_url(&self) -> Result<Url> {
-        Ok(Url::parse(&self.rpc_url)?)
+        Ok(block_number_data)
     }
 
     pub fn get_private_key(&self) -> Result<PrivateKeySigner, TaskError> {
@@ -84,10 +388,57 @@ impl Config {
             .map_err(|e| TaskError::CustomUrlError(e.to_string()))?;
         Ok(private_key)
     }
+
+    // Signed requests are bound to the chain of `rpc_url` and the aggregator
+    // account, which is the DSS `aggregator`
+    async fn signing_domain(&self) -> Result<(u64, Address)> {
+        let provider = ProviderBuilder::new().on_http(self.rpc_url.clone());
+        let chain_id = provider.get_chain_id().await?;
+        let aggregator = self.get_private_key()?.address();
+        Ok((chain_id, aggregator))
+    }
+
+    pub async fn registration_auth(&self) -> Result<RegistrationAuth> {
+        let (chain_id, aggregator) = self.signing_domain().await?;
+        Ok(RegistrationAuth::new(
+            chain_id,
+            aggregator,
+            self.registration_max_age,
+        ))
+    }
+
+    pub async fn task_auth(&self) -> Result<TaskAuth> {
+        let (chain_id, aggregator) = self.signing_domain().await?;
+        Ok(TaskAuth::new(
+            self.task_api_keys.clone(),
+            self.task_submitters.iter().copied().collect(),
+            chain_id,
+            aggregator,
+            self.registration_max_age,
+        ))
+    }
 }
 
-pub fn routes(operator_state: Arc<aggregator::OperatorState>) -> Router {
+pub fn routes(
+    operator_state: Arc<aggregator::OperatorState>,
+    task_api: task::TaskApi,
+    outbox_api: outbox::OutboxApi,
+    readiness: Arc<health::Readiness>,
+    limits: &limits::HttpLimits,
+) -> Router {
     Router::new()
         .route("/health", get(health::health_check))
-        .nest("/aggregator", aggregator_router(operator_state))
+        .route("/health/live", get(health::health_check))
+        .route(
+            "/health/ready",
+            get(health::readiness_check).with_state(readiness),
+        )
+        .route("/metrics", get(metrics::metrics_handler))
+        .nest(
+            "/aggregator",
+            registration_router(operator_state.clone())
+                .merge(aggregator_router(operator_state).layer(limits.registration_layer()))
+                .merge(task::task_router(task_api).layer(limits.task_layer()))
+                .merge(outbox::outbox_router(outbox_api)),
+        )
 }
@@ -0,0 +1,244 @@
+use std::{num::NonZeroU32, sync::Arc, time::Duration};
+
+use alloy::primitives::Address;
+use axum::{
+    extract::DefaultBodyLimit,
+    http::{header, HeaderName, HeaderValue, Method, Request},
+    Router,
+};
+use eyre::{eyre, Result};
+use governor::{middleware::NoOpMiddleware, DefaultKeyedRateLimiter, Quota, RateLimiter};
+use tower_governor::{
+    governor::{GovernorConfig, GovernorConfigBuilder},
+    key_extractor::{KeyExtractor, PeerIpKeyExtractor},
+    GovernorError, GovernorLayer,
+};
+use tower_http::{
+    cors::{AllowOrigin, Any, CorsLayer},
+    timeout::TimeoutLayer,
+};
+
+use crate::{error::AppError, task::api_key_index, Config};
+
+type Governor<K> = Arc<GovernorConfig<K, NoOpMiddleware>>;
+
+/// Rate limits, CORS policy, body size limit and request timeout of the HTTP
+/// routes, built from the configuration
+pub struct HttpLimits {
+    common: Governor<PeerIpKeyExtractor>,
+    registration: Governor<PeerIpKeyExtractor>,
+    operators: Arc<OperatorRateLimit>,
+    tasks: Governor<ClientKeyExtractor>,
+    cors: Option<CorsLayer>,
+    max_body_bytes: usize,
+    request_timeout: Duration,
+}
+
+impl HttpLimits {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let window = config.common_rate_limit_window_ms;
+        let common = config.common_rate_limit_max_requests;
+        let task_clients = ClientKeyExtractor {
+            api_keys: Arc::new(config.task_api_keys.clone()),
+        };
+        let registration = config
+            .registration_rate_limit_max_requests
+            .unwrap_or(common);
+        Ok(Self {
+            common: governor(PeerIpKeyExtractor, window, common)?,
+            registration: governor(PeerIpKeyExtractor, window, registration)?,
+            operators: Arc::new(OperatorRateLimit::new(window, registration)?),
+            tasks: governor(
+                task_clients,
+                window,
+                config.task_rate_limit_max_requests.unwrap_or(common),
+            )?,
+            cors: cors_layer(&config.cors_origin)?,
+            max_body_bytes: config.max_body_bytes,
+            request_timeout: Duration::from_millis(config.request_timeout_ms),
+        })
+    }
+
+    /// Rate limit of the unsigned operator routes, per client IP
+    pub fn registration_layer(&self) -> GovernorLayer<PeerIpKeyExtractor, NoOpMiddleware> {
+        GovernorLayer {
+            config: self.registration.clone(),
+        }
+    }
+
+    /// Rate limit of signed registrations, per operator
+    pub fn operator_limit(&self) -> Arc<OperatorRateLimit> {
+        self.operators.clone()
+    }
+
+    /// Rate limit of the task routes, per API key or client IP
+    pub fn task_layer(&self) -> GovernorLayer<ClientKeyExtractor, NoOpMiddleware> {
+        GovernorLayer {
+            config: self.tasks.clone(),
+        }
+    }
+
+    /// Applies the limits shared by every route
+    pub fn apply(&self, router: Router) -> Router {
+        let router = router
+            .layer(DefaultBodyLimit::max(self.max_body_bytes))
+            .layer(TimeoutLayer::new(self.request_timeout))
+            .layer(GovernorLayer {
+                config: self.common.clone(),
+            });
+        match &self.cors {
+            Some(cors) => router.layer(cors.clone()),
+            None => router,
+        }
+    }
+}
+
+/// Allows `max_requests` per `window_ms` with the whole allowance available as
+/// a burst
+fn governor<K: KeyExtractor>(key: K, window_ms: u64, max_requests: u32) -> Result<Governor<K>> {
+    let period = (window_ms / u64::from(max_requests.max(1))).max(1);
+    GovernorConfigBuilder::default()
+        .key_extractor(key)
+        .per_millisecond(period)
+        .burst_size(max_requests)
+        .finish()
+        .map(Arc::new)
+        .ok_or_else(|| eyre!("Rate limit of {max_requests} requests per {window_ms}ms is invalid"))
+}
+
+/// Limits signed registrations per operator address recovered from the
+/// signature, which the body carries out of reach of a `KeyExtractor`, so
+/// operators behind one IP keep separate allowances. Each route is still
+/// limited per IP by the common limit.
+pub struct OperatorRateLimit {
+    limiter: DefaultKeyedRateLimiter<Address>,
+}
+
+impl OperatorRateLimit {
+    /// Allows `max_requests` per `window_ms` per operator, like `governor`
+    pub fn new(window_ms: u64, max_requests: u32) -> Result<Self> {
+        let invalid =
+            || eyre!("Rate limit of {max_requests} requests per {window_ms}ms is invalid");
+        let period = (window_ms / u64::from(max_requests.max(1))).max(1);
+        let quota = Quota::with_period(Duration::from_millis(period))
+            .ok_or_else(invalid)?
+            .allow_burst(NonZeroU32::new(max_requests).ok_or_else(invalid)?);
+        Ok(Self {
+            limiter: RateLimiter::keyed(quota),
+        })
+    }
+
+    pub fn check(&self, operator: &Address) -> Result<(), AppError> {
+        self.limiter.check_key(operator).map_err(|_| {
+            AppError::too_many_requests(eyre!("Too many registrations for {operator}"))
+        })
+    }
+}
+
+/// No layer when no origin is configured so browsers keep the same-origin
+/// policy; `*` allows any origin.
+fn cors_layer(origins: &[String]) -> Result<Option<CorsLayer>> {
+    if origins.is_empty() {
+        return Ok(None);
+    }
+    let allow_origin = if origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::from(Any)
+    } else {
+        let origins = origins
+            .iter()
+            .map(|origin| {
+                HeaderValue::from_str(origin).map_err(|_| eyre!("Invalid CORS origin {origin}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        AllowOrigin::list(origins)
+    };
+    Ok(Some(
+        CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_methods([Method::GET, Method::POST, Method::DELETE])
+            .allow_headers([
+                header::CONTENT_TYPE,
+                HeaderName::from_static("x-api-key"),
+                HeaderName::from_static("traceparent"),
+            ]),
+    ))
+}
+
+/// Keys requests by their API key when it is a configured one, so clients
+/// sharing an address keep separate allowances, and by peer IP otherwise.
+/// Unknown keys fall back to the IP so made-up keys do not open new buckets.
+#[derive(Clone)]
+pub struct ClientKeyExtractor {
+    api_keys: Arc<Vec<String>>,
+}
+
+impl KeyExtractor for ClientKeyExtractor {
+    type Key = String;
+
+    fn extract<T>(&self, req: &Request<T>) -> Result<Self::Key, GovernorError> {
+        if let Some(i) = req
+            .headers()
+            .get("x-api-key")
+            .and_then(|key| api_key_index(&self.api_keys, key.as_bytes()))
+        {
+            return Ok(format!("api key #{}", i + 1));
+        }
+        PeerIpKeyExtractor.extract(req).map(|ip| ip.to_string())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use alloy::primitives::address;
+    use axum::extract::ConnectInfo;
+
+    use super::*;
+
+    fn request(api_key: Option<&str>) -> Request<()> {
+        let mut builder = Request::builder();
+        if let Some(api_key) = api_key {
+            builder = builder.header("x-api-key", api_key);
+        }
+        let mut request = builder.body(()).unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo("10.0.0.1:4000".parse::<SocketAddr>().unwrap()));
+        request
+    }
+
+    #[test]
+    fn keys_task_clients_by_api_key_or_ip() {
+        let extractor = ClientKeyExtractor {
+            api_keys: Arc::new(vec!["first".to_string(), "second".to_string()]),
+        };
+        assert_eq!(
+            extractor.extract(&request(Some("second"))).unwrap(),
+            "api key #2"
+        );
+        assert_eq!(
+            extractor.extract(&request(Some("made-up"))).unwrap(),
+            "10.0.0.1"
+        );
+        assert_eq!(extractor.extract(&request(None)).unwrap(), "10.0.0.1");
+    }
+
+    #[test]
+    fn limits_registrations_per_operator() {
+        let first = address!("1111111111111111111111111111111111111111");
+        let second = address!("2222222222222222222222222222222222222222");
+        let limit = OperatorRateLimit::new(60_000, 2).unwrap();
+
+        assert!(limit.check(&first).is_ok());
+        assert!(limit.check(&first).is_ok());
+        assert!(limit.check(&first).is_err());
+        // Another operator, e.g. behind the same IP, has its own allowance
+        assert!(limit.check(&second).is_ok());
+    }
+
+    #[test]
+    fn refuses_empty_rate_limits() {
+        assert!(OperatorRateLimit::new(1000, 0).is_err());
+        assert!(governor(PeerIpKeyExtractor, 1000, 0).is_err());
+    }
+}
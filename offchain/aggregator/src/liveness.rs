@@ -0,0 +1,373 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use eyre::Result;
+use serde::Serialize;
+use tokio::{signal, task::JoinSet, time};
+use tracing::{error, info};
+
+use crate::{
+    aggregator::OperatorState,
+    tls::{ClientTls, OperatorClient},
+    Config,
+};
+
+/// When operators are probed, quarantined and evicted.
+#[derive(Clone, Copy, Debug)]
+pub struct LivenessPolicy {
+    pub probe_interval: Duration,
+    pub probe_timeout: Duration,
+    /// Consecutive failed probes or tasks after which an operator is quarantined
+    pub max_consecutive_failures: u32,
+    pub quarantine: Duration,
+    /// Time without a signed registration or successful probe or task after
+    /// which an operator is evicted
+    pub eviction_after: Duration,
+}
+
+impl LivenessPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            probe_interval: Duration::from_millis(config.heartbeat),
+            probe_timeout: Duration::from_millis(config.operator_probe_timeout),
+            max_consecutive_failures: config.operator_max_failures,
+            quarantine: Duration::from_secs(config.operator_quarantine_secs),
+            eviction_after: Duration::from_secs(config.operator_eviction_secs),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "state")]
+pub enum OperatorStatus {
+    /// Receives tasks
+    Active,
+    /// Skipped by task fan-outs until `until`, but still probed
+    Quarantined { until: DateTime<Utc> },
+    /// Dropped from the routed operators until it registers again or, for an
+    /// endpoint registered on chain, answers a probe
+    Evicted,
+}
+
+/// Liveness record of one operator.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperatorHealth {
+    pub status: OperatorStatus,
+    pub last_seen: Option<DateTime<Utc>>,
+    pub last_probe: Option<DateTime<Utc>>,
+    pub last_probe_ok: Option<bool>,
+    pub last_success: Option<DateTime<Utc>>,
+    pub consecutive_failures: u32,
+    pub tasks_succeeded: u64,
+    pub tasks_failed: u64,
+    pub average_latency_ms: Option<u64>,
+    #[serde(skip)]
+    latency_samples: u64,
+    #[serde(skip)]
+    latency_total_ms: u64,
+}
+
+impl Default for OperatorHealth {
+    fn default() -> Self {
+        Self {
+            status: OperatorStatus::Active,
+            last_seen: None,
+            last_probe: None,
+            last_probe_ok: None,
+            last_success: None,
+            consecutive_failures: 0,
+            tasks_succeeded: 0,
+            tasks_failed: 0,
+            average_latency_ms: None,
+            latency_samples: 0,
+            latency_total_ms: 0,
+        }
+    }
+}
+
+/// What an observation of an operator came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Observation {
+    Probe,
+    Task,
+}
+
+impl OperatorHealth {
+    /// Whether task fan-outs should include the operator at `now`.
+    pub fn is_available(&self, now: DateTime<Utc>) -> bool {
+        match self.status {
+            OperatorStatus::Active => true,
+            OperatorStatus::Quarantined { until } => until <= now,
+            OperatorStatus::Evicted => false,
+        }
+    }
+
+    /// Latest sign of life: a signed registration, or a successful probe or
+    /// task.
+    pub fn last_activity(&self) -> Option<DateTime<Utc>> {
+        self.last_seen.max(self.last_success)
+    }
+
+    pub fn record_heartbeat(&mut self, now: DateTime<Utc>) {
+        self.last_seen = Some(now);
+    }
+
+    /// Record a successful probe or task. Returns whether this brought an
+    /// evicted operator back.
+    pub fn record_success(
+        &mut self,
+        observation: Observation,
+        latency: Duration,
+        now: DateTime<Utc>,
+    ) -> bool {
+        self.latency_samples += 1;
+        self.latency_total_ms += latency.as_millis() as u64;
+        self.average_latency_ms = Some(self.latency_total_ms / self.latency_samples);
+        match observation {
+            Observation::Probe => {
+                self.last_probe = Some(now);
+                self.last_probe_ok = Some(true);
+            }
+            Observation::Task => self.tasks_succeeded += 1,
+        }
+        self.last_success = Some(now);
+        self.consecutive_failures = 0;
+
+        match self.status {
+            OperatorStatus::Quarantined { until } if until <= now => {
+                self.status = OperatorStatus::Active;
+                false
+            }
+            OperatorStatus::Evicted if observation == Observation::Probe => {
+                self.status = OperatorStatus::Active;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn record_failure(
+        &mut self,
+        observation: Observation,
+        policy: &LivenessPolicy,
+        now: DateTime<Utc>,
+    ) {
+        match observation {
+            Observation::Probe => {
+                self.last_probe = Some(now);
+                self.last_probe_ok = Some(false);
+            }
+            Observation::Task => self.tasks_failed += 1,
+        }
+        self.consecutive_failures += 1;
+
+        if self.consecutive_failures >= policy.max_consecutive_failures
+            && self.status != OperatorStatus::Evicted
+            && self.is_available(now)
+        {
+            let quarantine = chrono::Duration::from_std(policy.quarantine).unwrap_or_default();
+            self.status = OperatorStatus::Quarantined {
+                until: now + quarantine,
+            };
+        }
+    }
+}
+
+/// Probes the `/health` endpoint of every routed operator and applies the
+/// quarantine and eviction policy.
+pub struct LivenessService {
+    operator_state: Arc<OperatorState>,
+    policy: LivenessPolicy,
+    client: OperatorClient,
+}
+
+impl LivenessService {
+    pub fn new(operator_state: Arc<OperatorState>, tls: Arc<ClientTls>) -> Result<Self> {
+        let policy = operator_state.policy();
+        let client = OperatorClient::new(tls, move |builder| builder.timeout(policy.probe_timeout))?;
+        Ok(Self {
+            operator_state,
+            policy,
+            client,
+        })
+    }
+
+    pub async fn start(self) {
+        info!("Probing operators every {:?}", self.policy.probe_interval);
+
+        tokio::spawn(async move {
+            let mut interval = time::interval(self.policy.probe_interval);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {
+                        if let Err(e) = self.probe_all().await {
+                            error!("Failed to probe operators: {e}");
+                        }
+                    }
+                    _ = signal::ctrl_c() => {
+                        info!("Received shutdown signal. Stopping operator probes...");
+                        break;
+                    }
+                }
+            }
+        });
+    }
+
+    // Operators are probed concurrently, so a pass takes at most one probe
+    // timeout however many operators are down
+    async fn probe_all(&self) -> Result<()> {
+        let client = self.client.get();
+        let mut probes = JoinSet::new();
+        for operator in self.operator_state.endpoints()? {
+            let client = client.clone();
+            probes.spawn(async move {
+                let started = time::Instant::now();
+                let healthy = match client
+                    .get(format!("{}health", operator.url()))
+                    .send()
+                    .await
+                {
+                    Ok(response) => response.status().is_success(),
+                    Err(_) => false,
+                };
+                (operator, healthy, started.elapsed())
+            });
+        }
+
+        while let Some(joined) = probes.join_next().await {
+            let (operator, healthy, latency) = match joined {
+                Ok(joined) => joined,
+                Err(e) => {
+                    error!("Operator probe failed to complete: {e}");
+                    continue;
+                }
+            };
+            if healthy {
+                self.operator_state.record_success(
+                    *operator.public_key(),
+                    Observation::Probe,
+                    latency,
+                )?;
+            } else {
+                info!("Operator {} failed its health probe", operator.public_key());
+                self.operator_state
+                    .record_failure(*operator.public_key(), Observation::Probe)?;
+            }
+        }
+        self.operator_state.evict_inactive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> LivenessPolicy {
+        LivenessPolicy {
+            probe_interval: Duration::from_secs(10),
+            probe_timeout: Duration::from_secs(2),
+            max_consecutive_failures: 3,
+            quarantine: Duration::from_secs(60),
+            eviction_after: Duration::from_secs(300),
+        }
+    }
+
+    #[test]
+    fn quarantines_after_consecutive_failures() {
+        let now = Utc::now();
+        let mut health = OperatorHealth::default();
+        health.record_failure(Observation::Probe, &policy(), now);
+        health.record_failure(Observation::Task, &policy(), now);
+        assert_eq!(health.status, OperatorStatus::Active);
+        assert!(health.is_available(now));
+
+        health.record_failure(Observation::Probe, &policy(), now);
+        let until = now + chrono::Duration::seconds(60);
+        assert_eq!(health.status, OperatorStatus::Quarantined { until });
+        assert!(!health.is_available(now));
+        assert!(health.is_available(until));
+        assert_eq!(health.consecutive_failures, 3);
+        assert_eq!(health.tasks_failed, 1);
+        assert_eq!(health.last_probe_ok, Some(false));
+    }
+
+    #[test]
+    fn failures_during_quarantine_do_not_extend_it() {
+        let now = Utc::now();
+        let mut health = OperatorHealth::default();
+        for _ in 0..3 {
+            health.record_failure(Observation::Probe, &policy(), now);
+        }
+        let quarantined = health.status;
+        health.record_failure(
+            Observation::Probe,
+            &policy(),
+            now + chrono::Duration::seconds(30),
+        );
+        assert_eq!(health.status, quarantined);
+    }
+
+    #[test]
+    fn success_ends_an_expired_quarantine() {
+        let now = Utc::now();
+        let mut health = OperatorHealth::default();
+        for _ in 0..3 {
+            health.record_failure(Observation::Task, &policy(), now);
+        }
+
+        // Still quarantined before `until`
+        assert!(!health.record_success(Observation::Task, Duration::from_millis(10), now));
+        assert!(matches!(health.status, OperatorStatus::Quarantined { .. }));
+
+        let later = now + chrono::Duration::seconds(61);
+        assert!(!health.record_success(Observation::Probe, Duration::from_millis(30), later));
+        assert_eq!(health.status, OperatorStatus::Active);
+        assert_eq!(health.consecutive_failures, 0);
+        assert_eq!(health.last_activity(), Some(later));
+        assert_eq!(health.average_latency_ms, Some(20));
+    }
+
+    #[test]
+    fn only_probes_revive_evicted_operators() {
+        let now = Utc::now();
+        let mut health = OperatorHealth {
+            status: OperatorStatus::Evicted,
+            ..OperatorHealth::default()
+        };
+        for _ in 0..3 {
+            health.record_failure(Observation::Probe, &policy(), now);
+        }
+        assert_eq!(health.status, OperatorStatus::Evicted);
+        assert!(!health.is_available(now));
+
+        assert!(!health.record_success(Observation::Task, Duration::ZERO, now));
+        assert_eq!(health.status, OperatorStatus::Evicted);
+        assert!(health.record_success(Observation::Probe, Duration::ZERO, now));
+        assert_eq!(health.status, OperatorStatus::Active);
+    }
+
+    #[test]
+    fn last_activity_is_the_latest_registration_or_success() {
+        let now = Utc::now();
+        let mut health = OperatorHealth::default();
+        assert_eq!(health.last_activity(), None);
+        health.record_heartbeat(now);
+        assert_eq!(health.last_activity(), Some(now));
+        health.record_failure(
+            Observation::Probe,
+            &policy(),
+            now + chrono::Duration::seconds(5),
+        );
+        assert_eq!(health.last_activity(), Some(now));
+        health.record_success(
+            Observation::Task,
+            Duration::ZERO,
+            now + chrono::Duration::seconds(9),
+        );
+        assert_eq!(
+            health.last_activity(),
+            Some(now + chrono::Duration::seconds(9))
+        );
+    }
+}
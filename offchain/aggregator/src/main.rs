@@ -1,14 +1,24 @@
-use std::{fs, net::SocketAddr, sync::Arc};
+use std::{fs, net::SocketAddr, sync::Arc, time::Duration};
 
+use clap::Parser;
 use dotenvy::dotenv;
-use square_number_dss_aggregator::{aggregator::OperatorState, task::TaskService};
+use square_number_dss_aggregator::{
+    aggregator::OperatorState,
+    discovery::OperatorDiscovery,
+    health::Readiness,
+    history::TaskHistory,
+    limits::HttpLimits,
+    liveness::{LivenessPolicy, LivenessService},
+    outbox::{Outbox, OutboxApi, OutboxSubmitter},
+    settings::Cli,
+    task::{TaskApi, TaskService},
+    telemetry,
+    tls::{ClientTls, ServerTls},
+};
 use tokio::net::TcpListener;
 use tokio::signal;
-use tower::ServiceBuilder;
-use tower_governor::{governor::GovernorConfig, GovernorLayer};
 use tower_http::trace::{self, TraceLayer};
 use tracing::{warn, Level};
-use tracing_subscriber::EnvFilter;
 
 #[tokio::main]
 async fn main() -> eyre::Result<()> {
@@ -17,42 +27,106 @@ async fn main() -> eyre::Result<()> {
     } else {
         warn!("No .env file not found.");
     }
-    let config = envy::from_env::<square_number_dss_aggregator::Config>()?;
-    tracing_subscriber::fmt()
-        .with_target(false)
-        .with_env_filter(EnvFilter::from_default_env())
-        .compact()
-        .init();
+    let cli = Cli::parse();
+    let config = cli.load()?;
+    if cli.print_config {
+        print!("{}", config.redacted()?);
+        return Ok(());
+    }
+    telemetry::init(&config)?;
+    config.warn_deprecated();
+
+    let limits = HttpLimits::from_config(&config)?;
+    let operator_state = Arc::new(
+        OperatorState::new(
+            config.registration_auth().await?,
+            LivenessPolicy::from_config(&config),
+        )
+        .with_registration_limit(limits.operator_limit()),
+    );
+    let db = sled::open(&config.task_history_path)?;
+    let task_history = Arc::new(TaskHistory::new(&db)?);
+    let outbox = Arc::new(Outbox::new(&db)?);
+    let task_auth = Arc::new(config.task_auth().await?);
+
+    let readiness = Arc::new(Readiness::new(&config, operator_state.clone())?);
+
+    let address = SocketAddr::new(config.host, config.port);
+    let server_tls = ServerTls::from_config(&config)?.map(Arc::new);
+    let client_tls = Arc::new(ClientTls::from_config(&config)?);
+    client_tls.clone().start().await;
 
-    let governor_config = Arc::new(GovernorConfig::default());
-    let operator_state = Arc::new(OperatorState::new());
-    let aggregator_app = square_number_dss_aggregator::routes(operator_state.clone());
-    let app = aggregator_app
+    let discovery = OperatorDiscovery::new(operator_state.clone(), &config)?;
+    discovery.start().await;
+    let liveness = LivenessService::new(operator_state.clone(), client_tls.clone())?;
+    liveness.start().await;
+    let submitter = OutboxSubmitter::new(outbox.clone(), task_history.clone(), &config)?;
+    submitter.start().await;
+    let outbox_api = OutboxApi {
+        outbox: outbox.clone(),
+        history: task_history.clone(),
+        admin_api_keys: Arc::new(config.admin_api_keys.clone()),
+    };
+
+    // let task_service = Arc::new(TaskService::new(operator_state, config)?);
+    let verifer_service = Arc::new(TaskService::new(
+        operator_state.clone(),
+        task_history.clone(),
+        outbox,
+        client_tls,
+        config,
+    )?);
+    // tokio::spawn(async move { task_service.start().await });
+    tokio::spawn(verifer_service.clone().start());
+
+    let task_api = TaskApi {
+        service: verifer_service,
+        history: task_history,
+        auth: task_auth,
+    };
+    let aggregator_app = square_number_dss_aggregator::routes(
+        operator_state,
+        task_api,
+        outbox_api,
+        readiness,
+        &limits,
+    );
+    let app = limits
+        .apply(aggregator_app)
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(trace::DefaultMakeSpan::new().level(Level::INFO))
                 .on_request(trace::DefaultOnRequest::new().level(Level::INFO))
                 .on_response(trace::DefaultOnResponse::new().level(Level::INFO))
                 .on_failure(trace::DefaultOnFailure::new().level(Level::ERROR)),
-        )
-        .layer(ServiceBuilder::new().layer(GovernorLayer {
-            config: governor_config.clone(),
-        }));
-
-    let listener = TcpListener::bind((config.host, config.port)).await?;
+        );
 
-    // let task_service = Arc::new(TaskService::new(operator_state, config)?);
-    let verifer_service= Arc::new(TaskService::new(operator_state, config)?);
-    // tokio::spawn(async move { task_service.start().await });
-    tokio::spawn(async move { verifer_service.start().await });
-
-    axum::serve(
-        listener,
-        app.into_make_service_with_connect_info::<SocketAddr>(),
-    )
-    .with_graceful_shutdown(shutdown_signal())
-    .await?;
+    match server_tls {
+        Some(tls) => {
+            tls.clone().start().await;
+            let handle = axum_server::Handle::new();
+            let shutdown = handle.clone();
+            tokio::spawn(async move {
+                shutdown_signal().await;
+                shutdown.graceful_shutdown(Some(Duration::from_secs(10)));
+            });
+            axum_server::bind_rustls(address, tls.rustls_config())
+                .handle(handle)
+                .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                .await?;
+        }
+        None => {
+            let listener = TcpListener::bind(address).await?;
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(shutdown_signal())
+            .await?;
+        }
+    }
 
+    telemetry::shutdown();
     Ok(())
 }
 
@@ -0,0 +1,150 @@
+use std::sync::OnceLock;
+
+use axum::{
+    http::header,
+    response::{IntoResponse, Response},
+};
+use prometheus::{
+    Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+
+use crate::error::AppError;
+
+/// Prometheus metrics of the aggregator, served on `GET /metrics`.
+pub struct Metrics {
+    registry: Registry,
+    /// Contract events ingested, by event
+    pub events_ingested: IntCounterVec,
+    /// Tasks settled, by status
+    pub tasks_processed: IntCounterVec,
+    /// Tasks not processed, by reason
+    pub tasks_skipped: IntCounterVec,
+    /// Operator task requests, by operator and outcome
+    pub operator_requests: IntCounterVec,
+    pub operator_latency: HistogramVec,
+    /// Fan-outs by whether they reached quorum
+    pub quorum_outcomes: IntCounterVec,
+    /// Outbox submissions, by outcome
+    pub submissions: IntCounterVec,
+    pub submission_gas_used: Histogram,
+    /// Time from queueing a response to its confirmation
+    pub submission_latency: Histogram,
+    /// Failed RPC calls, by method
+    pub rpc_errors: IntCounterVec,
+    /// Last block scanned for task events, by DSS
+    pub last_processed_block: IntGaugeVec,
+    /// Blocks between the chain head and the last processed block
+    pub chain_head_lag: IntGaugeVec,
+}
+
+impl Metrics {
+    fn new() -> prometheus::Result<Self> {
+        let registry = Registry::new_custom(Some("aggregator".to_string()), None)?;
+
+        let events_ingested = IntCounterVec::new(
+            Opts::new("events_ingested_total", "Contract events ingested"),
+            &["event"],
+        )?;
+        let tasks_processed = IntCounterVec::new(
+            Opts::new("tasks_processed_total", "Tasks settled, by status"),
+            &["status"],
+        )?;
+        let tasks_skipped = IntCounterVec::new(
+            Opts::new("tasks_skipped_total", "Tasks not processed, by reason"),
+            &["reason"],
+        )?;
+        let operator_requests = IntCounterVec::new(
+            Opts::new("operator_requests_total", "Task requests sent to operators"),
+            &["operator", "outcome"],
+        )?;
+        let operator_latency = HistogramVec::new(
+            HistogramOpts::new(
+                "operator_response_seconds",
+                "Latency of operator task responses",
+            ),
+            &["operator"],
+        )?;
+        let quorum_outcomes = IntCounterVec::new(
+            Opts::new("quorum_outcomes_total", "Task fan-outs, by quorum outcome"),
+            &["outcome"],
+        )?;
+        let submissions = IntCounterVec::new(
+            Opts::new("submissions_total", "Task response submissions, by outcome"),
+            &["outcome"],
+        )?;
+        let submission_gas_used = Histogram::with_opts(
+            HistogramOpts::new("submission_gas_used", "Gas used by task responses")
+                .buckets(prometheus::exponential_buckets(25_000.0, 2.0, 8)?),
+        )?;
+        let submission_latency = Histogram::with_opts(
+            HistogramOpts::new(
+                "submission_seconds",
+                "Time from queueing a task response to its confirmation",
+            )
+            .buckets(prometheus::exponential_buckets(1.0, 2.0, 12)?),
+        )?;
+        let rpc_errors = IntCounterVec::new(
+            Opts::new("rpc_errors_total", "Failed RPC calls, by method"),
+            &["method"],
+        )?;
+        let last_processed_block = IntGaugeVec::new(
+            Opts::new("last_processed_block", "Last block scanned for task events"),
+            &["dss"],
+        )?;
+        let chain_head_lag = IntGaugeVec::new(
+            Opts::new(
+                "chain_head_lag_blocks",
+                "Blocks between the chain head and the last processed block",
+            ),
+            &["dss"],
+        )?;
+
+        registry.register(Box::new(events_ingested.clone()))?;
+        registry.register(Box::new(tasks_processed.clone()))?;
+        registry.register(Box::new(tasks_skipped.clone()))?;
+        registry.register(Box::new(operator_requests.clone()))?;
+        registry.register(Box::new(operator_latency.clone()))?;
+        registry.register(Box::new(quorum_outcomes.clone()))?;
+        registry.register(Box::new(submissions.clone()))?;
+        registry.register(Box::new(submission_gas_used.clone()))?;
+        registry.register(Box::new(submission_latency.clone()))?;
+        registry.register(Box::new(rpc_errors.clone()))?;
+        registry.register(Box::new(last_processed_block.clone()))?;
+        registry.register(Box::new(chain_head_lag.clone()))?;
+
+        Ok(Self {
+            registry,
+            events_ingested,
+            tasks_processed,
+            tasks_skipped,
+            operator_requests,
+            operator_latency,
+            quorum_outcomes,
+            submissions,
+            submission_gas_used,
+            submission_latency,
+            rpc_errors,
+            last_processed_block,
+            chain_head_lag,
+        })
+    }
+
+    pub fn rpc_error(&self, method: &str) {
+        self.rpc_errors.with_label_values(&[method]).inc();
+    }
+
+    pub fn render(&self) -> prometheus::Result<String> {
+        TextEncoder::new().encode_to_string(&self.registry.gather())
+    }
+}
+
+/// Metrics of this process.
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(|| Metrics::new().expect("metrics are registered once"))
+}
+
+pub async fn metrics_handler() -> Result<Response, AppError> {
+    let body = metrics().render().map_err(|e| eyre::eyre!(e))?;
+    Ok(([(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)], body).into_response())
+}
@@ -0,0 +1,173 @@
+use std::sync::Arc;
+
+use alloy::{
+    eips::BlockId,
+    network::TransactionBuilder,
+    primitives::{address, Address},
+    providers::Provider,
+    rpc::types::TransactionRequest,
+    sol,
+    sol_types::SolCall,
+    transports::http::ReqwestTransport,
+};
+use tokio::sync::OnceCell;
+use tracing::{instrument, warn};
+
+use crate::{
+    contract::{decode_revert_data, rpc_error, RecommendedProvider},
+    metrics::metrics,
+    TaskError,
+};
+
+sol! {
+    #[sol(rpc)]
+    interface Multicall3 {
+        struct Call3 {
+            address target;
+            bool allowFailure;
+            bytes callData;
+        }
+
+        struct CallResult {
+            bool success;
+            bytes returnData;
+        }
+
+        function aggregate3(Call3[] calldata calls) external payable returns (CallResult[] memory returnData);
+    }
+}
+
+/// Address Multicall3 is deployed at on mainnet, Sepolia and most other chains.
+pub const MULTICALL3_ADDRESS: Address = address!("cA11bde05977b3631167028862bE2a173976CA11");
+
+/// Batches view calls through Multicall3 so that many reads cost one RPC
+/// request and all see the state of the same block. Chains without Multicall3,
+/// e.g. a fresh local anvil, are read with one `eth_call` per call instead.
+#[derive(Clone)]
+pub struct Multicall {
+    instance: Multicall3::Multicall3Instance<ReqwestTransport, RecommendedProvider>,
+    batch_size: usize,
+    /// Whether Multicall3 has code, checked on the first call
+    deployed: Arc<OnceCell<bool>>,
+}
+
+impl Multicall {
+    pub fn new(address: Address, provider: RecommendedProvider, batch_size: usize) -> Self {
+        Self {
+            instance: Multicall3::new(address, provider),
+            batch_size: batch_size.max(1),
+            deployed: Arc::new(OnceCell::new()),
+        }
+    }
+
+    /// Makes `method` on every target at `block` and returns the results in
+    /// the order of `calls`. Calls are sent `batch_size` at a time; one that
+    /// reverts fails the whole batch with its revert reason.
+    #[instrument(skip(self, calls), err)]
+    pub async fn call<C: SolCall>(
+        &self,
+        method: &'static str,
+        calls: Vec<(Address, C)>,
+        block: u64,
+    ) -> Result<Vec<C::Return>, TaskError> {
+        if !self.is_deployed().await? {
+            return self.call_each(method, calls, block).await;
+        }
+
+        let mut returns = Vec::with_capacity(calls.len());
+        for chunk in calls.chunks(self.batch_size) {
+            let batch = chunk
+                .iter()
+                .map(|(target, call)| Multicall3::Call3 {
+                    target: *target,
+                    allowFailure: true,
+                    callData: call.abi_encode().into(),
+                })
+                .collect();
+            let results = self
+                .instance
+                .aggregate3(batch)
+                .block(BlockId::number(block))
+                .call()
+                .await
+                .map_err(rpc_error("aggregate3"))?
+                .returnData;
+
+            for ((target, _), result) in chunk.iter().zip(results) {
+                if !result.success {
+                    metrics().rpc_error(method);
+                    return Err(TaskError::ContractReverted(format!(
+                        "{method} on {target}: {}",
+                        decode_revert_data(&result.returnData)
+                    )));
+                }
+                let decoded = C::abi_decode_returns(&result.returnData, true).map_err(|e| {
+                    metrics().rpc_error(method);
+                    TaskError::ContractError(format!("Invalid {method} result of {target}: {e}"))
+                })?;
+                returns.push(decoded);
+            }
+        }
+        Ok(returns)
+    }
+
+    async fn is_deployed(&self) -> Result<bool, TaskError> {
+        let address = *self.instance.address();
+        let deployed = self
+            .deployed
+            .get_or_try_init(|| async {
+                let code = self
+                    .instance
+                    .provider()
+                    .get_code_at(address)
+                    .await
+                    .map_err(|e| {
+                        metrics().rpc_error("eth_getCode");
+                        TaskError::ContractError(e.to_string())
+                    })?;
+                if code.is_empty() {
+                    warn!("No Multicall3 at {address}, reading with one eth_call per call");
+                }
+                Ok::<_, TaskError>(!code.is_empty())
+            })
+            .await?;
+        Ok(*deployed)
+    }
+
+    // Same results as `call`, without Multicall3
+    async fn call_each<C: SolCall>(
+        &self,
+        method: &'static str,
+        calls: Vec<(Address, C)>,
+        block: u64,
+    ) -> Result<Vec<C::Return>, TaskError> {
+        let mut returns = Vec::with_capacity(calls.len());
+        for (target, call) in calls {
+            let request = TransactionRequest::default()
+                .with_to(target)
+                .with_input(call.abi_encode());
+            let data = self
+                .instance
+                .provider()
+                .call(&request)
+                .block(BlockId::number(block))
+                .await
+                .map_err(|e| {
+                    metrics().rpc_error(method);
+                    match e.as_error_resp().and_then(|resp| resp.as_revert_data()) {
+                        Some(data) => TaskError::ContractReverted(format!(
+                            "{method} on {target}: {}",
+                            decode_revert_data(&data)
+                        )),
+                        None => TaskError::ContractError(format!("{method} on {target}: {e}")),
+                    }
+                })?;
+            let decoded = C::abi_decode_returns(&data, true).map_err(|e| {
+                metrics().rpc_error(method);
+                TaskError::ContractError(format!("Invalid {method} result of {target}: {e}"))
+            })?;
+            returns.push(decoded);
+        }
+        Ok(returns)
+    }
+}
@@ -0,0 +1,807 @@
+use std::{sync::Arc, time::Duration};
+
+use alloy::{
+    primitives::{Address, B256},
+    providers::{utils::Eip1559Estimation, Provider},
+};
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use tokio::{signal, sync::Notify, time};
+use tracing::{error, info, instrument, warn};
+
+use crate::{
+    contract::{ContractManager, TxOutcome},
+    dss::{DssResponse, DssTask},
+    error::AppError,
+    history::{self, TaskHistory, TaskRecord, TaskStatus},
+    metrics::metrics,
+    task::api_key_index,
+    Config,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SubmissionState {
+    /// Waiting to be sent at `next_attempt_at`
+    Pending,
+    /// Sent, waiting for the transaction to be confirmed
+    Sent,
+    /// Out of attempts; retry or drop it through the admin API
+    Failed,
+}
+
+/// A task response to submit to the DSS. Confirmed submissions leave the
+/// outbox; their transaction is kept in the task history.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Submission {
+    /// Key of the task, see [`crate::dss::task_key`]; zero on submissions
+    /// queued before keys were scoped by DSS, which are stored under the task id
+    #[serde(default)]
+    pub key: B256,
+    /// Key of the task record the submission updates, when it is not `key`:
+    /// the record of a task submitted over HTTP
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub record: Option<B256>,
+    pub task_id: B256,
+    /// DSS the response is submitted to; the TxnVerifier when unset
+    #[serde(default)]
+    pub dss: Option<Address>,
+    pub task: DssTask,
+    pub response: DssResponse,
+    pub state: SubmissionState,
+    pub attempts: u32,
+    pub next_attempt_at: DateTime<Utc>,
+    /// Latest transaction sent, with the nonce and fees a replacement bumps
+    pub tx_hash: Option<B256>,
+    pub nonce: Option<u64>,
+    pub max_fee_per_gas: Option<u128>,
+    pub max_priority_fee_per_gas: Option<u128>,
+    pub sent_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Submission {
+    fn new(
+        key: B256,
+        record: B256,
+        task_id: B256,
+        dss: Address,
+        task: DssTask,
+        response: DssResponse,
+    ) -> Self {
+        let now = Utc::now();
+        Self {
+            key,
+            record: (record != key).then_some(record),
+            task_id,
+            dss: Some(dss),
+            task,
+            response,
+            state: SubmissionState::Pending,
+            attempts: 0,
+            next_attempt_at: now,
+            tx_hash: None,
+            nonce: None,
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            sent_at: None,
+            last_error: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Key the submission is stored under.
+    pub fn storage_key(&self) -> B256 {
+        if self.key.is_zero() {
+            self.task_id
+        } else {
+            self.key
+        }
+    }
+
+    /// Key of the task record the submission updates.
+    pub fn record_key(&self) -> B256 {
+        self.record.unwrap_or_else(|| self.storage_key())
+    }
+
+    // Fees of the next attempt. A replacement has to outbid the transaction it
+    // replaces.
+    fn fees(&self, policy: &RetryPolicy, estimate: Eip1559Estimation) -> Eip1559Estimation {
+        match (self.nonce, self.max_fee_per_gas, self.max_priority_fee_per_gas) {
+            (Some(_), Some(max_fee), Some(priority_fee)) => Eip1559Estimation {
+                max_fee_per_gas: estimate.max_fee_per_gas.max(policy.bump(max_fee)),
+                max_priority_fee_per_gas: estimate
+                    .max_priority_fee_per_gas
+                    .max(policy.bump(priority_fee)),
+            },
+            _ => estimate,
+        }
+    }
+
+    fn sent(&mut self, tx_hash: B256, nonce: u64, fees: Eip1559Estimation, now: DateTime<Utc>) {
+        self.state = SubmissionState::Sent;
+        self.tx_hash = Some(tx_hash);
+        self.nonce = Some(nonce);
+        self.max_fee_per_gas = Some(fees.max_fee_per_gas);
+        self.max_priority_fee_per_gas = Some(fees.max_priority_fee_per_gas);
+        self.sent_at = Some(now);
+        self.last_error = None;
+        self.updated_at = now;
+    }
+
+    // Send the transaction again with the same nonce once it has stayed
+    // unmined for `resend_after`. Returns whether it is replaced.
+    fn replace_if_unmined(&mut self, policy: &RetryPolicy, now: DateTime<Utc>) -> Result<bool> {
+        let sent_at = self.sent_at.unwrap_or(self.updated_at);
+        if sent_at + chrono::Duration::from_std(policy.resend_after)? > now {
+            return Ok(false);
+        }
+        self.state = SubmissionState::Pending;
+        self.next_attempt_at = now;
+        self.updated_at = now;
+        Ok(true)
+    }
+
+    // Schedule the next attempt after a failure, or mark the submission failed
+    // once it is out of attempts. Returns whether it failed.
+    fn retry_later(
+        &mut self,
+        policy: &RetryPolicy,
+        error: String,
+        now: DateTime<Utc>,
+    ) -> Result<bool> {
+        let failed = self.attempts >= policy.max_attempts;
+        if failed {
+            self.state = SubmissionState::Failed;
+        } else {
+            self.state = SubmissionState::Pending;
+            let delay = policy.delay(self.attempts.max(1));
+            self.next_attempt_at = now + chrono::Duration::from_std(delay)?;
+        }
+        self.last_error = Some(error);
+        self.updated_at = now;
+        Ok(failed)
+    }
+}
+
+/// Task responses waiting to be submitted, in the task history database so
+/// they survive restarts.
+#[derive(Debug)]
+pub struct Outbox {
+    submissions: sled::Tree,
+    queued: Notify,
+}
+
+impl Outbox {
+    pub fn new(db: &sled::Db) -> Result<Self> {
+        let submissions = db.open_tree("outbox")?;
+        Ok(Self {
+            submissions,
+            queued: Notify::new(),
+        })
+    }
+
+    /// Queue a response and wake the submitter. Returns `false` when the task
+    /// already has a submission.
+    pub fn enqueue(
+        &self,
+        key: B256,
+        record: B256,
+        task_id: B256,
+        dss: Address,
+        task: DssTask,
+        response: DssResponse,
+    ) -> Result<bool> {
+        if self.get(&key)?.is_some() {
+            return Ok(false);
+        }
+        self.put(&Submission::new(key, record, task_id, dss, task, response))?;
+        self.queued.notify_one();
+        Ok(true)
+    }
+
+    pub fn contains(&self, key: &B256) -> Result<bool> {
+        Ok(self.submissions.contains_key(key.as_slice())?)
+    }
+
+    pub fn get(&self, key: &B256) -> Result<Option<Submission>> {
+        match self.submissions.get(key.as_slice())? {
+            Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn put(&self, submission: &Submission) -> Result<()> {
+        self.submissions.insert(
+            submission.storage_key().as_slice(),
+            serde_json::to_vec(submission)?,
+        )?;
+        self.submissions.flush()?;
+        Ok(())
+    }
+
+    pub fn remove(&self, key: &B256) -> Result<Option<Submission>> {
+        let removed = self.submissions.remove(key.as_slice())?;
+        self.submissions.flush()?;
+        match removed {
+            Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Submissions, oldest first.
+    pub fn list(&self) -> Result<Vec<Submission>> {
+        let mut submissions = Vec::new();
+        for entry in self.submissions.iter() {
+            let (_, value) = entry?;
+            submissions.push(serde_json::from_slice::<Submission>(&value)?);
+        }
+        submissions.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+        Ok(submissions)
+    }
+}
+
+/// How submissions are retried and replaced.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub poll_interval: Duration,
+    pub max_attempts: u32,
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+    /// Time a sent transaction may stay unmined before it is replaced
+    pub resend_after: Duration,
+    /// Fee increase of a replacement, in percent
+    pub fee_bump_percent: u128,
+}
+
+impl RetryPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            poll_interval: Duration::from_millis(config.outbox_poll_interval),
+            max_attempts: config.outbox_max_attempts,
+            backoff: Duration::from_millis(config.outbox_backoff),
+            max_backoff: Duration::from_secs(config.outbox_max_backoff_secs),
+            resend_after: Duration::from_secs(config.outbox_resend_after_secs),
+            fee_bump_percent: config.outbox_fee_bump_percent.into(),
+        }
+    }
+
+    fn delay(&self, attempts: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+            .min(self.max_backoff)
+    }
+
+    fn bump(&self, fee: u128) -> u128 {
+        fee.saturating_mul(100 + self.fee_bump_percent) / 100
+    }
+}
+
+/// Sends the outbox to the DSS: retries failed sends with backoff, replaces
+/// transactions left unmined with higher fees, and records confirmed ones in
+/// the task history.
+pub struct OutboxSubmitter {
+    contract_manager: ContractManager,
+    outbox: Arc<Outbox>,
+    history: Arc<TaskHistory>,
+    policy: RetryPolicy,
+}
+
+impl OutboxSubmitter {
+    pub fn new(outbox: Arc<Outbox>, history: Arc<TaskHistory>, config: &Config) -> Result<Self> {
+        Ok(Self {
+            contract_manager: ContractManager::new(config)?,
+            outbox,
+            history,
+            policy: RetryPolicy::from_config(config),
+        })
+    }
+
+    pub async fn start(self) {
+        info!("Submitting task responses from the outbox");
+
+        tokio::spawn(async move {
+            let mut interval = time::interval(self.policy.poll_interval);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = self.outbox.queued.notified() => {}
+                    _ = signal::ctrl_c() => {
+                        info!("Received shutdown signal. Stopping the outbox submitter...");
+                        break;
+                    }
+                }
+                if let Err(e) = self.submit_due().await {
+                    error!("Failed to process the outbox: {e}");
+                }
+            }
+        });
+    }
+
+    async fn submit_due(&self) -> Result<()> {
+        let now = Utc::now();
+        for submission in self.outbox.list()? {
+            let task_id = submission.task_id;
+            let result = match submission.state {
+                SubmissionState::Pending if submission.next_attempt_at <= now => {
+                    self.send(submission).await
+                }
+                SubmissionState::Sent => self.check(submission).await,
+                _ => Ok(()),
+            };
+            // One failing submission does not hold up the others
+            if let Err(e) = result {
+                error!("Failed to process the submission of task {task_id}: {e}");
+            }
+        }
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(task = %submission.task_id, attempt = submission.attempts + 1))]
+    async fn send(&self, mut submission: Submission) -> Result<()> {
+        let dss = match self.contract_manager.dss(submission.dss) {
+            Ok(dss) => dss,
+            Err(e) => return self.retry_later(submission, e.to_string()),
+        };
+        // An earlier attempt may have landed after all
+        match dss.stored_response(&submission.task).await {
+            Ok(Some(_)) => {
+                info!(
+                    "Task {} already has a response on chain",
+                    submission.task_id
+                );
+                return self.complete(submission, None);
+            }
+            Ok(None) => {}
+            Err(e) => return self.retry_later(submission, e.to_string()),
+        }
+
+        let estimate = match self
+            .contract_manager
+            .provider
+            .estimate_eip1559_fees(None)
+            .await
+        {
+            Ok(estimate) => estimate,
+            Err(e) => {
+                metrics().rpc_error("eth_feeHistory");
+                return self.retry_later(submission, e.to_string());
+            }
+        };
+        let fees = submission.fees(&self.policy, estimate);
+
+        submission.attempts += 1;
+        match self
+            .contract_manager
+            .send_task_response(
+                dss,
+                submission.task_id,
+                &submission.task,
+                &submission.response,
+                submission.nonce,
+                fees,
+            )
+            .await
+        {
+            Ok((tx_hash, nonce)) => {
+                info!(
+                    "Sent task response for {} in {tx_hash} (attempt {})",
+                    submission.task_id, submission.attempts
+                );
+                metrics().submissions.with_label_values(&["sent"]).inc();
+                submission.sent(tx_hash, nonce, fees, Utc::now());
+                self.outbox.put(&submission)?;
+                self.update_history(&submission, |record| {
+                    record.submission = Some(TxOutcome::submitted(tx_hash));
+                    record.error = None;
+                    record.set_status(TaskStatus::Submitted);
+                });
+                Ok(())
+            }
+            Err(e) => {
+                metrics()
+                    .submissions
+                    .with_label_values(&["send_failed"])
+                    .inc();
+                let error = e.to_string();
+                // The nonce was taken by another transaction, so the next
+                // attempt is a new transaction rather than a replacement
+                if error.contains("nonce too low") {
+                    submission.nonce = None;
+                }
+                self.retry_later(submission, error)
+            }
+        }
+    }
+
+    #[instrument(skip_all, fields(task = %submission.task_id, tx = ?submission.tx_hash))]
+    async fn check(&self, mut submission: Submission) -> Result<()> {
+        let Some(tx_hash) = submission.tx_hash else {
+            submission.state = SubmissionState::Pending;
+            return self.outbox.put(&submission);
+        };
+        let provider = &self.contract_manager.provider;
+
+        let Some(receipt) = provider
+            .get_transaction_receipt(tx_hash)
+            .await
+            .inspect_err(|_| metrics().rpc_error("eth_getTransactionReceipt"))?
+        else {
+            if submission.replace_if_unmined(&self.policy, Utc::now())? {
+                warn!(
+                    "Task response {tx_hash} for {} is not mined after {:?}, replacing it",
+                    submission.task_id, self.policy.resend_after
+                );
+                metrics().submissions.with_label_values(&["replaced"]).inc();
+                self.outbox.put(&submission)?;
+            }
+            return Ok(());
+        };
+
+        let Some(block_number) = receipt.block_number else {
+            return Ok(());
+        };
+        let latest_block = provider
+            .get_block_number()
+            .await
+            .inspect_err(|_| metrics().rpc_error("eth_blockNumber"))?;
+        if latest_block + 1 < block_number + self.contract_manager.confirmations.max(1) {
+            return Ok(());
+        }
+
+        let dss = match self.contract_manager.dss(submission.dss) {
+            Ok(dss) => dss,
+            Err(e) => return self.retry_later(submission, e.to_string()),
+        };
+        let outcome = self
+            .contract_manager
+            .task_response_outcome(
+                dss,
+                &receipt,
+                submission.task_id,
+                &submission.task,
+                &submission.response,
+            )
+            .await;
+        if outcome.success == Some(false) {
+            let reason = outcome
+                .revert_reason
+                .clone()
+                .unwrap_or_else(|| "unknown reason".to_string());
+            metrics().submissions.with_label_values(&["reverted"]).inc();
+            // The reverted transaction used up its nonce
+            submission.nonce = None;
+            self.update_history(&submission, |record| {
+                record.submission = Some(outcome)
+            });
+            return self.retry_later(
+                submission,
+                format!("Transaction {tx_hash} reverted: {reason}"),
+            );
+        }
+
+        info!(
+            "Task response for {} confirmed in {tx_hash}",
+            submission.task_id
+        );
+        self.complete(submission, Some(outcome))
+    }
+
+    fn complete(&self, submission: Submission, outcome: Option<TxOutcome>) -> Result<()> {
+        self.outbox.remove(&submission.storage_key())?;
+        match &outcome {
+            Some(outcome) => {
+                metrics()
+                    .submissions
+                    .with_label_values(&["confirmed"])
+                    .inc();
+                if let Some(gas_used) = outcome.gas_used {
+                    metrics().submission_gas_used.observe(gas_used as f64);
+                }
+                let latency = Utc::now() - submission.created_at;
+                metrics()
+                    .submission_latency
+                    .observe(latency.num_milliseconds() as f64 / 1000.0);
+            }
+            None => metrics()
+                .submissions
+                .with_label_values(&["already_submitted"])
+                .inc(),
+        }
+        self.update_history(&submission, |record| {
+            record.error = None;
+            match outcome {
+                Some(outcome) => {
+                    record.submission = Some(outcome);
+                    record.set_status(TaskStatus::Confirmed);
+                }
+                None => record.set_status(TaskStatus::AlreadySubmitted),
+            }
+        });
+        Ok(())
+    }
+
+    fn retry_later(&self, mut submission: Submission, error: String) -> Result<()> {
+        if submission.retry_later(&self.policy, error.clone(), Utc::now())? {
+            error!(
+                "Giving up on the task response for {} after {} attempts: {error}",
+                submission.task_id, submission.attempts
+            );
+            metrics().submissions.with_label_values(&["failed"]).inc();
+            self.update_history(&submission, |record| {
+                record.error = Some(error);
+                record.set_status(TaskStatus::SubmissionFailed);
+            });
+        } else {
+            warn!(
+                "Failed to submit the task response for {}, retrying at {}: {error}",
+                submission.task_id, submission.next_attempt_at
+            );
+        }
+        self.outbox.put(&submission)
+    }
+
+    fn update_history(&self, submission: &Submission, update: impl FnOnce(&mut TaskRecord)) {
+        let task_id = submission.task_id;
+        let key = submission.record_key();
+        let updated = self.history.get(&key).and_then(|record| match record {
+            Some(mut record) => {
+                update(&mut record);
+                self.history.put(&record)
+            }
+            None => Ok(()),
+        });
+        if let Err(e) = updated {
+            error!("Failed to record the submission of task {task_id}: {e}");
+        }
+    }
+}
+
+/// State of the outbox admin routes.
+#[derive(Clone)]
+pub struct OutboxApi {
+    pub outbox: Arc<Outbox>,
+    pub history: Arc<TaskHistory>,
+    pub admin_api_keys: Arc<Vec<String>>,
+}
+
+impl OutboxApi {
+    fn authorize(&self, headers: &HeaderMap) -> Result<(), AppError> {
+        let key = headers
+            .get("x-api-key")
+            .ok_or_else(|| AppError::unauthorized(eyre::eyre!("Missing x-api-key header")))?;
+        match api_key_index(&self.admin_api_keys, key.as_bytes()) {
+            Some(_) => Ok(()),
+            None => Err(AppError::unauthorized(eyre::eyre!("Unknown API key"))),
+        }
+    }
+}
+
+pub async fn list_submissions(
+    State(api): State<OutboxApi>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<Submission>>, AppError> {
+    api.authorize(&headers)?;
+    Ok(Json(api.outbox.list()?))
+}
+
+// Send the submission again now, with a fresh attempt budget
+pub async fn retry_submission(
+    State(api): State<OutboxApi>,
+    headers: HeaderMap,
+    Path(key): Path<String>,
+) -> Result<Response, AppError> {
+    api.authorize(&headers)?;
+    let key = history::normalize_hash(&key).map_err(AppError::bad_request)?;
+    let Some(mut submission) = api.outbox.get(&key)? else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+
+    let now = Utc::now();
+    submission.state = SubmissionState::Pending;
+    submission.attempts = 0;
+    submission.next_attempt_at = now;
+    submission.updated_at = now;
+    api.outbox.put(&submission)?;
+    api.outbox.queued.notify_one();
+    info!("Submission of task {} retried", submission.task_id);
+    Ok(Json(submission).into_response())
+}
+
+pub async fn drop_submission(
+    State(api): State<OutboxApi>,
+    headers: HeaderMap,
+    Path(key): Path<String>,
+) -> Result<Response, AppError> {
+    api.authorize(&headers)?;
+    let key = history::normalize_hash(&key).map_err(AppError::bad_request)?;
+    let Some(submission) = api.outbox.remove(&key)? else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+
+    if let Some(mut record) = api.history.get(&submission.record_key())? {
+        record.error = Some("Dropped from the outbox".to_string());
+        record.set_status(TaskStatus::SubmissionFailed);
+        api.history.put(&record)?;
+    }
+    warn!("Submission of task {} dropped", submission.task_id);
+    Ok(Json(submission).into_response())
+}
+
+pub fn outbox_router(api: OutboxApi) -> Router {
+    Router::new()
+        .route("/outbox", get(list_submissions))
+        .route("/outbox/:key", delete(drop_submission))
+        .route("/outbox/:key/retry", post(retry_submission))
+        .with_state(api)
+}
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::{address, U256};
+
+    use super::*;
+    use crate::dss::{task_key, SquareResponse, SquareTask};
+
+    const DSS: Address = address!("2222222222222222222222222222222222222222");
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            poll_interval: Duration::from_secs(5),
+            max_attempts: 3,
+            backoff: Duration::from_secs(2),
+            max_backoff: Duration::from_secs(10),
+            resend_after: Duration::from_secs(60),
+            fee_bump_percent: 10,
+        }
+    }
+
+    fn submission() -> Submission {
+        let task = DssTask::SquareNumber(SquareTask {
+            value: U256::from(3),
+        });
+        let task_id = task.id().unwrap();
+        let key = task_key(DSS, task_id);
+        let response = DssResponse::SquareNumber(SquareResponse {
+            response: U256::from(9),
+        });
+        Submission::new(key, key, task_id, DSS, task, response)
+    }
+
+    fn estimate(max_fee_per_gas: u128, max_priority_fee_per_gas: u128) -> Eip1559Estimation {
+        Eip1559Estimation {
+            max_fee_per_gas,
+            max_priority_fee_per_gas,
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let policy = policy();
+        assert_eq!(policy.delay(0), Duration::from_secs(2));
+        assert_eq!(policy.delay(1), Duration::from_secs(2));
+        assert_eq!(policy.delay(2), Duration::from_secs(4));
+        assert_eq!(policy.delay(3), Duration::from_secs(8));
+        assert_eq!(policy.delay(4), Duration::from_secs(10));
+        assert_eq!(policy.delay(u32::MAX), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn replacements_outbid_the_replaced_fees() {
+        let policy = policy();
+        assert_eq!(policy.bump(100), 110);
+
+        let mut submission = submission();
+        let fees = submission.fees(&policy, estimate(100, 10));
+        assert_eq!(
+            (fees.max_fee_per_gas, fees.max_priority_fee_per_gas),
+            (100, 10)
+        );
+
+        submission.sent(B256::repeat_byte(1), 7, fees, Utc::now());
+        let fees = submission.fees(&policy, estimate(100, 10));
+        assert_eq!(
+            (fees.max_fee_per_gas, fees.max_priority_fee_per_gas),
+            (110, 11)
+        );
+        // A higher estimate wins over the bump
+        let fees = submission.fees(&policy, estimate(200, 5));
+        assert_eq!(
+            (fees.max_fee_per_gas, fees.max_priority_fee_per_gas),
+            (200, 11)
+        );
+    }
+
+    #[test]
+    fn unmined_transactions_are_replaced_after_resend_after() {
+        let policy = policy();
+        let mut submission = submission();
+        let sent_at = Utc::now();
+        submission.attempts = 1;
+        submission.sent(B256::repeat_byte(1), 7, estimate(100, 10), sent_at);
+        assert_eq!(submission.state, SubmissionState::Sent);
+
+        let before = sent_at + chrono::Duration::seconds(59);
+        assert!(!submission.replace_if_unmined(&policy, before).unwrap());
+        assert_eq!(submission.state, SubmissionState::Sent);
+
+        let after = sent_at + chrono::Duration::seconds(60);
+        assert!(submission.replace_if_unmined(&policy, after).unwrap());
+        assert_eq!(submission.state, SubmissionState::Pending);
+        assert_eq!(submission.next_attempt_at, after);
+        // The replacement keeps the nonce
+        assert_eq!(submission.nonce, Some(7));
+    }
+
+    #[test]
+    fn failures_back_off_until_out_of_attempts() {
+        let policy = policy();
+        let mut submission = submission();
+        let now = Utc::now();
+
+        submission.attempts = 2;
+        assert!(!submission
+            .retry_later(&policy, "reverted".to_string(), now)
+            .unwrap());
+        assert_eq!(submission.state, SubmissionState::Pending);
+        assert_eq!(
+            submission.next_attempt_at,
+            now + chrono::Duration::seconds(4)
+        );
+        assert_eq!(submission.last_error.as_deref(), Some("reverted"));
+
+        submission.attempts = 3;
+        assert!(submission
+            .retry_later(&policy, "reverted".to_string(), now)
+            .unwrap());
+        assert_eq!(submission.state, SubmissionState::Failed);
+    }
+
+    #[test]
+    fn confirmed_submissions_leave_the_outbox() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let outbox = Outbox::new(&db).unwrap();
+        let pending = submission();
+        let key = pending.key;
+        assert!(outbox
+            .enqueue(
+                key,
+                key,
+                pending.task_id,
+                DSS,
+                pending.task.clone(),
+                pending.response.clone()
+            )
+            .unwrap());
+        assert!(!outbox
+            .enqueue(
+                key,
+                key,
+                pending.task_id,
+                DSS,
+                pending.task.clone(),
+                pending.response.clone()
+            )
+            .unwrap());
+
+        let mut sent = outbox.get(&key).unwrap().unwrap();
+        assert_eq!(sent.state, SubmissionState::Pending);
+        sent.sent(B256::repeat_byte(1), 7, estimate(100, 10), Utc::now());
+        outbox.put(&sent).unwrap();
+        assert_eq!(outbox.list().unwrap()[0].state, SubmissionState::Sent);
+
+        assert!(outbox.remove(&key).unwrap().is_some());
+        assert!(!outbox.contains(&key).unwrap());
+    }
+}
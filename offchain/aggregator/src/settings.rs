@@ -0,0 +1,452 @@
+use std::{fmt, path::PathBuf, str::FromStr};
+
+use alloy::{
+    primitives::{Address, U256},
+    signers::local::PrivateKeySigner,
+};
+use clap::Parser;
+use config::{Environment, File, FileFormat};
+use serde::{Serialize, Serializer};
+use thiserror::Error;
+use tracing::warn;
+use url::Url;
+
+use crate::Config;
+
+/// Settings holding lists, given comma separated in the environment and in
+/// `--set`.
+const LIST_KEYS: [&str; 7] = [
+    "dss_deployments",
+    "asset_prices",
+    "asset_price_feeds",
+    "task_api_keys",
+    "task_submitters",
+    "admin_api_keys",
+    "cors_origin",
+];
+
+/// Settings still accepted but no longer read, with the replacement.
+const DEPRECATED_KEYS: [(&str, &str); 1] = [("square_number_dss_address", "dss_deployments")];
+
+/// Command line of the aggregator. Settings are read from the config file,
+/// then the environment, then `--set`, each overriding the previous one.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// TOML file of settings keyed by their snake_case name
+    #[arg(long, env = "CONFIG_FILE")]
+    pub config: Option<PathBuf>,
+    /// Overrides a setting, e.g. `--set port=3001`; may be repeated
+    #[arg(long = "set", value_name = "KEY=VALUE", value_parser = parse_setting)]
+    pub settings: Vec<(String, String)>,
+    /// Prints the resolved settings with secrets redacted, then exits
+    #[arg(long)]
+    pub print_config: bool,
+}
+
+fn parse_setting(setting: &str) -> Result<(String, String), String> {
+    let (key, value) = setting
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got `{setting}`"))?;
+    Ok((
+        key.trim().to_lowercase().replace('-', "_"),
+        value.to_string(),
+    ))
+}
+
+#[derive(Debug, Error)]
+pub enum SettingsError {
+    #[error("Invalid configuration: {0}")]
+    Source(#[from] config::ConfigError),
+
+    #[error("Invalid configuration:{}", .0.iter().map(|e| format!("\n  {e}")).collect::<String>())]
+    Invalid(Vec<InvalidSetting>),
+}
+
+#[derive(Debug)]
+pub struct InvalidSetting {
+    pub key: &'static str,
+    pub reason: String,
+}
+
+impl fmt::Display for InvalidSetting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` ({}) {}",
+            self.key,
+            self.key.to_uppercase(),
+            self.reason
+        )
+    }
+}
+
+impl Cli {
+    /// Merges the config file, the environment and `--set`, then validates
+    /// the result.
+    pub fn load(&self) -> Result<Config, SettingsError> {
+        let mut builder = config::Config::builder();
+        if let Some(path) = &self.config {
+            builder = builder.add_source(File::from(path.as_path()).format(FileFormat::Toml));
+        }
+        let mut environment = Environment::default()
+            .ignore_empty(true)
+            .try_parsing(true)
+            .list_separator(",");
+        for key in LIST_KEYS {
+            environment = environment.with_list_parse_key(key);
+        }
+        builder = builder.add_source(environment);
+        for (key, value) in &self.settings {
+            builder = if LIST_KEYS.contains(&key.as_str()) {
+                builder.set_override(key.as_str(), value.split(',').collect::<Vec<_>>())?
+            } else {
+                builder.set_override(key.as_str(), value.as_str())?
+            };
+        }
+
+        let settings = builder.build()?;
+        let deprecated_settings = DEPRECATED_KEYS
+            .into_iter()
+            .filter(|(key, _)| settings.get_string(key).is_ok())
+            .collect();
+        let mut config: Config = settings.try_deserialize()?;
+        config.deprecated_settings = deprecated_settings;
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+impl Config {
+    /// Checks addresses, URLs, files and intervals up front so that a bad
+    /// value stops startup with the offending key.
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        let mut invalid = Vec::new();
+        let mut check = |valid: bool, key: &'static str, reason: &str| {
+            if !valid {
+                invalid.push(InvalidSetting {
+                    key,
+                    reason: reason.to_string(),
+                });
+            }
+        };
+
+        check(
+            matches!(self.rpc_url.scheme(), "http" | "https"),
+            "rpc_url",
+            "must be an http or https URL",
+        );
+        check(
+            PrivateKeySigner::from_str(&self.private_key).is_ok(),
+            "private_key",
+            "must be a hex encoded secp256k1 private key",
+        );
+
+        let addresses = [
+            ("txn_verifier_address", Some(self.txn_verifier_address)),
+            ("core_address", Some(self.core_address)),
+            (
+                "eigen_avs_directory_address",
+                self.eigen_avs_directory_address,
+            ),
+            ("eigen_avs_address", self.eigen_avs_address),
+            ("multicall_address", Some(self.multicall_address)),
+            (
+                "symbiotic_middleware_address",
+                self.symbiotic_middleware_address,
+            ),
+        ];
+        for (key, address) in addresses {
+            check(
+                address != Some(Address::ZERO),
+                key,
+                "must not be the zero address",
+            );
+        }
+        check(
+            self.dss_deployments.iter().all(|d| d.address != Address::ZERO),
+            "dss_deployments",
+            "must not contain the zero address",
+        );
+        check(
+            self.dss_deployments.iter().enumerate().all(|(i, d)| {
+                d.address != self.txn_verifier_address
+                    && self.dss_deployments[..i].iter().all(|e| e.address != d.address)
+            }),
+            "dss_deployments",
+            "must list each DSS once, apart from TXN_VERIFIER_ADDRESS",
+        );
+        check(
+            self.asset_prices.iter().all(|p| p.price > U256::ZERO),
+            "asset_prices",
+            "must be greater than zero",
+        );
+        check(
+            self.asset_price_feeds
+                .iter()
+                .all(|f| f.asset != Address::ZERO && f.feed != Address::ZERO),
+            "asset_price_feeds",
+            "must not contain the zero address",
+        );
+        check(
+            self.task_submitters.iter().all(|a| *a != Address::ZERO),
+            "task_submitters",
+            "must not contain the zero address",
+        );
+
+        let positive = [
+            ("heartbeat", self.heartbeat),
+            ("registration_max_age", self.registration_max_age),
+            ("operator_probe_timeout", self.operator_probe_timeout),
+            ("operator_max_failures", self.operator_max_failures.into()),
+            ("operator_quarantine_secs", self.operator_quarantine_secs),
+            ("operator_eviction_secs", self.operator_eviction_secs),
+            ("operator_request_timeout", self.operator_request_timeout),
+            ("operator_connect_timeout", self.operator_connect_timeout),
+            ("task_deadline", self.task_deadline),
+            ("task_quorum", self.task_quorum as u64),
+            ("asset_price_max_age_secs", self.asset_price_max_age_secs),
+            ("multicall_batch_size", self.multicall_batch_size as u64),
+            ("outbox_poll_interval", self.outbox_poll_interval),
+            ("outbox_max_attempts", self.outbox_max_attempts.into()),
+            ("outbox_backoff", self.outbox_backoff),
+            ("outbox_max_backoff_secs", self.outbox_max_backoff_secs),
+            ("outbox_resend_after_secs", self.outbox_resend_after_secs),
+            (
+                "common_rate_limit_window_ms",
+                self.common_rate_limit_window_ms,
+            ),
+            (
+                "common_rate_limit_max_requests",
+                self.common_rate_limit_max_requests.into(),
+            ),
+            (
+                "registration_rate_limit_max_requests",
+                self.registration_rate_limit_max_requests
+                    .map_or(1, u64::from),
+            ),
+            (
+                "task_rate_limit_max_requests",
+                self.task_rate_limit_max_requests.map_or(1, u64::from),
+            ),
+            ("max_body_bytes", self.max_body_bytes as u64),
+            ("request_timeout_ms", self.request_timeout_ms),
+            ("tls_reload_interval_secs", self.tls_reload_interval_secs),
+        ];
+        for (key, value) in positive {
+            check(value > 0, key, "must be greater than zero");
+        }
+        check(
+            self.task_deadline >= self.operator_request_timeout,
+            "task_deadline",
+            "must not be shorter than OPERATOR_REQUEST_TIMEOUT",
+        );
+
+        if let Some(endpoint) = self.otlp_endpoint.as_deref().filter(|e| !e.is_empty()) {
+            check(
+                Url::parse(endpoint).is_ok(),
+                "otlp_endpoint",
+                "must be a URL",
+            );
+        }
+        check(
+            self.cors_origin
+                .iter()
+                .all(|origin| origin == "*" || Url::parse(origin).is_ok()),
+            "cors_origin",
+            "must be `*` or a list of origins such as `https://example.com`",
+        );
+
+        let files = [
+            ("tls_cert_path", &self.tls_cert_path),
+            ("tls_key_path", &self.tls_key_path),
+            ("tls_client_cert_path", &self.tls_client_cert_path),
+            ("tls_client_key_path", &self.tls_client_key_path),
+            ("tls_operator_ca_path", &self.tls_operator_ca_path),
+        ];
+        for (key, path) in files {
+            if let Some(path) = path {
+                check(
+                    std::path::Path::new(path).is_file(),
+                    key,
+                    "must be an existing file",
+                );
+            }
+        }
+
+        if invalid.is_empty() {
+            Ok(())
+        } else {
+            Err(SettingsError::Invalid(invalid))
+        }
+    }
+
+    /// Logs the deprecated settings found by [`Cli::load`], once logging is set
+    /// up.
+    pub fn warn_deprecated(&self) {
+        for (key, replacement) in &self.deprecated_settings {
+            warn!(
+                "`{key}` ({}) is deprecated and ignored, use `{replacement}` ({})",
+                key.to_uppercase(),
+                replacement.to_uppercase()
+            );
+        }
+    }
+
+    /// The settings as TOML with secrets redacted, for `--print-config`.
+    pub fn redacted(&self) -> Result<String, toml::ser::Error> {
+        toml::to_string(self)
+    }
+}
+
+pub(crate) fn redact<T, S: Serializer>(_: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str("<redacted>")
+}
+
+// RPC providers put API keys in the path or query of their URLs, so only the
+// scheme, host and port are kept
+pub(crate) fn redact_url<S: Serializer>(url: &Url, serializer: S) -> Result<S::Ok, S::Error> {
+    let origin = url.origin().ascii_serialization();
+    let hidden = url.path() != "/"
+        || url.query().is_some()
+        || url.fragment().is_some()
+        || !url.username().is_empty()
+        || url.password().is_some();
+    if hidden {
+        serializer.serialize_str(&format!("{origin}/<redacted>"))
+    } else {
+        serializer.serialize_str(&origin)
+    }
+}
+
+pub(crate) fn redact_all<S: Serializer>(
+    values: &[String],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    vec!["<redacted>"; values.len()].serialize(serializer)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRIVATE_KEY: &str = "0x0000000000000000000000000000000000000000000000000000000000000001";
+
+    fn toml() -> String {
+        format!(
+            r#"
+host = "127.0.0.1"
+port = 3000
+rpc_url = "https://rpc.example.com/v2/secret-api-key?token=secret-token"
+private_key = "{PRIVATE_KEY}"
+txn_verifier_address = "0x1111111111111111111111111111111111111111"
+core_address = "0x2222222222222222222222222222222222222222"
+block_number_store = "block_number.json"
+heartbeat = 1000
+task_quorum = 2
+"#
+        )
+    }
+
+    fn set(settings: &[(&str, &str)]) -> Vec<(String, String)> {
+        settings
+            .iter()
+            .map(|(key, value)| parse_setting(&format!("{key}={value}")).unwrap())
+            .collect()
+    }
+
+    fn invalid_keys(error: SettingsError) -> Vec<&'static str> {
+        match error {
+            SettingsError::Invalid(invalid) => invalid.into_iter().map(|i| i.key).collect(),
+            SettingsError::Source(e) => panic!("unexpected error {e}"),
+        }
+    }
+
+    #[test]
+    fn environment_overrides_the_file_and_set_overrides_both() {
+        let path = std::env::temp_dir().join(format!("aggregator-{}.toml", std::process::id()));
+        std::fs::write(&path, toml()).unwrap();
+        // The only test reading the environment, so it can set variables
+        std::env::set_var("HEARTBEAT", "2000");
+        std::env::set_var("PORT", "3001");
+
+        let cli = Cli {
+            config: Some(path.clone()),
+            settings: set(&[("host", "127.0.0.1"), ("PORT", "3002")]),
+            print_config: false,
+        };
+        let config = cli.load();
+        std::env::remove_var("HEARTBEAT");
+        std::env::remove_var("PORT");
+        std::fs::remove_file(path).unwrap();
+
+        let config = config.unwrap();
+        assert_eq!(config.task_quorum, 2);
+        assert_eq!(config.heartbeat, 2000);
+        assert_eq!(config.port, 3002);
+    }
+
+    #[test]
+    fn parses_set_arguments() {
+        assert_eq!(
+            parse_setting("Task-Quorum= 2").unwrap(),
+            ("task_quorum".to_string(), " 2".to_string())
+        );
+        assert!(parse_setting("task_quorum").is_err());
+    }
+
+    #[test]
+    fn reports_every_invalid_setting() {
+        let mut config: Config = toml::from_str(&toml()).unwrap();
+        config.validate().unwrap();
+
+        config.rpc_url = Url::parse("ws://rpc.example.com").unwrap();
+        config.private_key = "not a key".to_string();
+        config.core_address = Address::ZERO;
+        config.task_quorum = 0;
+        config.operator_request_timeout = config.task_deadline + 1;
+        config.tls_cert_path = Some("/does/not/exist.pem".to_string());
+        assert_eq!(
+            invalid_keys(config.validate().unwrap_err()),
+            [
+                "rpc_url",
+                "private_key",
+                "core_address",
+                "task_quorum",
+                "task_deadline",
+                "tls_cert_path"
+            ]
+        );
+    }
+
+    #[test]
+    fn redacts_secrets() {
+        let mut config: Config = toml::from_str(&toml()).unwrap();
+        config.task_api_keys = vec!["task-key".to_string()];
+        config.admin_api_keys = vec!["admin-key".to_string()];
+
+        let printed = config.redacted().unwrap();
+        for secret in [
+            PRIVATE_KEY,
+            "secret-api-key",
+            "secret-token",
+            "task-key",
+            "admin-key",
+        ] {
+            assert!(!printed.contains(secret), "{secret} is printed");
+        }
+        assert!(printed.contains(r#"rpc_url = "https://rpc.example.com/<redacted>""#));
+        assert!(printed.contains(r#"private_key = "<redacted>""#));
+        assert!(printed.contains(r#"task_api_keys = ["<redacted>"]"#));
+        assert!(printed.contains("task_quorum = 2"));
+    }
+
+    #[test]
+    fn keeps_rpc_urls_without_a_path() {
+        let mut config: Config = toml::from_str(&toml()).unwrap();
+        config.rpc_url = Url::parse("http://localhost:8545").unwrap();
+        assert!(config
+            .redacted()
+            .unwrap()
+            .contains(r#"rpc_url = "http://localhost:8545""#));
+    }
+}
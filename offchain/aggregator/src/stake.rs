@@ -0,0 +1,547 @@
+use std::{collections::HashMap, fmt, str::FromStr, sync::Mutex, time::Duration};
+
+use alloy::{
+    primitives::{
+        utils::{format_ether, parse_ether},
+        Address, I256, U256,
+    },
+};
+use chrono::Utc;
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use tracing::{info, instrument};
+
+use crate::{
+    contract::{ChainlinkAggregator, Erc20, VaultAbi},
+    multicall::Multicall,
+    Config, TaskError,
+};
+
+/// Fixed ETH price of one whole unit of an asset, written `asset:price` in
+/// `ASSET_PRICES`, e.g. `0xC02a…:1` for WETH.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct AssetPrice {
+    pub asset: Address,
+    /// Price in wei
+    pub price: U256,
+}
+
+impl FromStr for AssetPrice {
+    type Err = eyre::Report;
+
+    fn from_str(entry: &str) -> Result<Self> {
+        let (asset, price) = entry
+            .trim()
+            .split_once(':')
+            .ok_or_else(|| eyre::eyre!("Expected asset:price, got {entry}"))?;
+        Ok(Self {
+            asset: asset
+                .parse()
+                .map_err(|e| eyre::eyre!("Invalid asset address {asset}: {e}"))?,
+            price: parse_ether(price).map_err(|e| eyre::eyre!("Invalid ETH price {price}: {e}"))?,
+        })
+    }
+}
+
+impl TryFrom<String> for AssetPrice {
+    type Error = String;
+
+    fn try_from(entry: String) -> Result<Self, String> {
+        entry.parse().map_err(|e: eyre::Report| e.to_string())
+    }
+}
+
+impl From<AssetPrice> for String {
+    fn from(entry: AssetPrice) -> Self {
+        entry.to_string()
+    }
+}
+
+impl fmt::Display for AssetPrice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.asset, format_ether(self.price))
+    }
+}
+
+/// An amount of ETH, written in whole ETH, e.g. `32` or `0.5`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct EthAmount(pub U256);
+
+impl FromStr for EthAmount {
+    type Err = eyre::Report;
+
+    fn from_str(amount: &str) -> Result<Self> {
+        parse_ether(amount.trim())
+            .map(Self)
+            .map_err(|e| eyre::eyre!("Invalid ETH amount {amount}: {e}"))
+    }
+}
+
+impl TryFrom<String> for EthAmount {
+    type Error = String;
+
+    fn try_from(amount: String) -> Result<Self, String> {
+        amount.parse().map_err(|e: eyre::Report| e.to_string())
+    }
+}
+
+impl From<EthAmount> for String {
+    fn from(amount: EthAmount) -> Self {
+        amount.to_string()
+    }
+}
+
+impl fmt::Display for EthAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", format_ether(self.0))
+    }
+}
+
+/// Chainlink feed quoting an asset in ETH, written `asset:feed` in
+/// `ASSET_PRICE_FEEDS`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct AssetPriceFeed {
+    pub asset: Address,
+    pub feed: Address,
+}
+
+impl FromStr for AssetPriceFeed {
+    type Err = eyre::Report;
+
+    fn from_str(entry: &str) -> Result<Self> {
+        let (asset, feed) = entry
+            .trim()
+            .split_once(':')
+            .ok_or_else(|| eyre::eyre!("Expected asset:feed, got {entry}"))?;
+        Ok(Self {
+            asset: asset
+                .parse()
+                .map_err(|e| eyre::eyre!("Invalid asset address {asset}: {e}"))?,
+            feed: feed
+                .parse()
+                .map_err(|e| eyre::eyre!("Invalid feed address {feed}: {e}"))?,
+        })
+    }
+}
+
+impl TryFrom<String> for AssetPriceFeed {
+    type Error = String;
+
+    fn try_from(entry: String) -> Result<Self, String> {
+        entry.parse().map_err(|e: eyre::Report| e.to_string())
+    }
+}
+
+impl From<AssetPriceFeed> for String {
+    fn from(entry: AssetPriceFeed) -> Self {
+        entry.to_string()
+    }
+}
+
+impl fmt::Display for AssetPriceFeed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.asset, self.feed)
+    }
+}
+
+/// Where asset prices come from: a fixed price when the asset has one,
+/// otherwise its Chainlink feed.
+#[derive(Debug)]
+pub struct PriceSource {
+    fixed: HashMap<Address, U256>,
+    feeds: HashMap<Address, Address>,
+    max_age: Duration,
+}
+
+impl PriceSource {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            fixed: config
+                .asset_prices
+                .iter()
+                .map(|entry| (entry.asset, entry.price))
+                .collect(),
+            feeds: config
+                .asset_price_feeds
+                .iter()
+                .map(|entry| (entry.asset, entry.feed))
+                .collect(),
+            max_age: Duration::from_secs(config.asset_price_max_age_secs),
+        }
+    }
+
+    /// Fixed prices of `assets`, and the feeds of those without one.
+    fn fixed_prices(
+        &self,
+        assets: &[Address],
+    ) -> Result<(HashMap<Address, U256>, Vec<(Address, Address)>), TaskError> {
+        let mut prices = HashMap::new();
+        let mut feeds = Vec::new();
+        for asset in assets {
+            if let Some(price) = self.fixed.get(asset) {
+                prices.insert(*asset, *price);
+                continue;
+            }
+            let feed = self.feeds.get(asset).ok_or_else(|| {
+                TaskError::PriceUnavailable(format!("{asset} has no price or feed"))
+            })?;
+            feeds.push((*asset, *feed));
+        }
+        Ok((prices, feeds))
+    }
+
+    /// ETH price of one whole unit of each of `assets`, in wei. Feeds are
+    /// read in one batch at `block`.
+    async fn prices(
+        &self,
+        multicall: &Multicall,
+        assets: &[Address],
+        block: u64,
+    ) -> Result<HashMap<Address, U256>, TaskError> {
+        let (mut prices, feeds) = self.fixed_prices(assets)?;
+        if feeds.is_empty() {
+            return Ok(prices);
+        }
+
+        let decimals = multicall
+            .call(
+                "decimals",
+                feeds
+                    .iter()
+                    .map(|(_, feed)| (*feed, ChainlinkAggregator::decimalsCall {}))
+                    .collect(),
+                block,
+            )
+            .await?;
+        let rounds = multicall
+            .call(
+                "latestRoundData",
+                feeds
+                    .iter()
+                    .map(|(_, feed)| (*feed, ChainlinkAggregator::latestRoundDataCall {}))
+                    .collect(),
+                block,
+            )
+            .await?;
+
+        let now = Utc::now().timestamp().max(0) as u64;
+        for (((asset, feed), decimals), round) in feeds.into_iter().zip(decimals).zip(rounds) {
+            let age = now.saturating_sub(round.updatedAt.saturating_to::<u64>());
+            if age > self.max_age.as_secs() {
+                return Err(TaskError::PriceUnavailable(format!(
+                    "feed {feed} of {asset} was last updated {age}s ago"
+                )));
+            }
+            if round.answer <= I256::ZERO {
+                return Err(TaskError::PriceUnavailable(format!(
+                    "feed {feed} of {asset} answered {}",
+                    round.answer
+                )));
+            }
+            prices.insert(asset, feed_price(round.answer, decimals._0));
+        }
+        Ok(prices)
+    }
+}
+
+/// ETH price in wei of one whole unit of an asset, from a positive feed
+/// `answer` with `decimals` decimals.
+pub fn feed_price(answer: I256, decimals: u8) -> U256 {
+    answer.into_raw() * U256::from(10).pow(U256::from(18))
+        / U256::from(10).pow(U256::from(decimals))
+}
+
+/// ETH value in wei of `amount` base units of an asset with `decimals`
+/// decimals, priced at `price` wei per whole unit.
+pub fn eth_value(amount: U256, price: U256, decimals: u8) -> U256 {
+    amount.saturating_mul(price) / U256::from(10).pow(U256::from(decimals))
+}
+
+/// Underlying asset of a vault; does not change once deployed.
+#[derive(Clone, Copy, Debug)]
+struct VaultAsset {
+    asset: Address,
+    decimals: u8,
+}
+
+/// Figures of one block, dropped when a later block is asked for.
+#[derive(Debug, Default)]
+struct BlockCache {
+    block: u64,
+    prices: HashMap<Address, U256>,
+    vaults: HashMap<Address, U256>,
+}
+
+/// Values vault assets in ETH so that stake in stETH, USDC and other assets
+/// can be weighed together.
+pub struct StakeNormalizer {
+    multicall: Multicall,
+    prices: PriceSource,
+    assets: Mutex<HashMap<Address, VaultAsset>>,
+    cache: Mutex<BlockCache>,
+}
+
+impl StakeNormalizer {
+    pub fn new(multicall: Multicall, prices: PriceSource) -> Self {
+        Self {
+            multicall,
+            prices,
+            assets: Mutex::default(),
+            cache: Mutex::default(),
+        }
+    }
+
+    /// `totalAssets()` of each of `vaults` at `block`, in wei of ETH. Figures
+    /// not cached for `block` are read in batches pinned to it, so that every
+    /// vault is valued against the same state.
+    #[instrument(skip(self, vaults), fields(vaults = vaults.len()), err)]
+    pub async fn vaults_eth(
+        &self,
+        vaults: &[Address],
+        block: u64,
+    ) -> Result<HashMap<Address, U256>, TaskError> {
+        let mut values = HashMap::new();
+        let mut missing = Vec::new();
+        for vault in vaults {
+            match self.cached(block, |cache| cache.vaults.get(vault).copied()) {
+                Some(eth) => {
+                    values.insert(*vault, eth);
+                }
+                None if !missing.contains(vault) => missing.push(*vault),
+                None => {}
+            }
+        }
+        if missing.is_empty() {
+            return Ok(values);
+        }
+
+        let vault_assets = self.vault_assets(&missing, block).await?;
+        let total_assets = self
+            .multicall
+            .call(
+                "totalAssets",
+                missing
+                    .iter()
+                    .map(|vault| (*vault, VaultAbi::totalAssetsCall {}))
+                    .collect(),
+                block,
+            )
+            .await?;
+
+        let mut prices = HashMap::new();
+        let mut unpriced = Vec::new();
+        for VaultAsset { asset, .. } in vault_assets.values() {
+            match self.cached(block, |cache| cache.prices.get(asset).copied()) {
+                Some(price) => {
+                    prices.insert(*asset, price);
+                }
+                None if !unpriced.contains(asset) => unpriced.push(*asset),
+                None => {}
+            }
+        }
+        let fetched = self.prices.prices(&self.multicall, &unpriced, block).await?;
+        self.cache(block, |cache| cache.prices.extend(&fetched));
+        prices.extend(fetched);
+
+        for (vault, total_assets) in missing.into_iter().zip(total_assets) {
+            let VaultAsset { asset, decimals } = vault_assets[&vault];
+            let total_assets = total_assets._0;
+            let eth = eth_value(total_assets, prices[&asset], decimals);
+            info!(
+                "Vault {vault} holds {total_assets} of {asset} worth {} ETH at block {block}",
+                format_ether(eth)
+            );
+            self.cache(block, |cache| {
+                cache.vaults.insert(vault, eth);
+            });
+            values.insert(vault, eth);
+        }
+        Ok(values)
+    }
+
+    // Assets and their decimals are read once per vault, in two batches
+    async fn vault_assets(
+        &self,
+        vaults: &[Address],
+        block: u64,
+    ) -> Result<HashMap<Address, VaultAsset>, TaskError> {
+        let mut known = HashMap::new();
+        let mut unknown = Vec::new();
+        {
+            let assets = self.lock_assets();
+            for vault in vaults {
+                match assets.get(vault) {
+                    Some(asset) => {
+                        known.insert(*vault, *asset);
+                    }
+                    None => unknown.push(*vault),
+                }
+            }
+        }
+        if unknown.is_empty() {
+            return Ok(known);
+        }
+
+        let assets: Vec<Address> = self
+            .multicall
+            .call(
+                "asset",
+                unknown
+                    .iter()
+                    .map(|vault| (*vault, VaultAbi::assetCall {}))
+                    .collect(),
+                block,
+            )
+            .await?
+            .into_iter()
+            .map(|result| result._0)
+            .collect();
+        let decimals = self
+            .multicall
+            .call(
+                "decimals",
+                assets
+                    .iter()
+                    .map(|asset| (*asset, Erc20::decimalsCall {}))
+                    .collect(),
+                block,
+            )
+            .await?;
+
+        let mut cached = self.lock_assets();
+        for ((vault, asset), decimals) in unknown.into_iter().zip(assets).zip(decimals) {
+            let vault_asset = VaultAsset {
+                asset,
+                decimals: decimals._0,
+            };
+            cached.insert(vault, vault_asset);
+            known.insert(vault, vault_asset);
+        }
+        Ok(known)
+    }
+
+    fn lock_assets(&self) -> std::sync::MutexGuard<'_, HashMap<Address, VaultAsset>> {
+        self.assets.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn cached<T>(&self, block: u64, get: impl FnOnce(&BlockCache) -> Option<T>) -> Option<T> {
+        let cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        if cache.block != block {
+            return None;
+        }
+        get(&cache)
+    }
+
+    // Figures of an older block than the cached one are not kept
+    fn cache(&self, block: u64, update: impl FnOnce(&mut BlockCache)) {
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        if block > cache.block {
+            *cache = BlockCache {
+                block,
+                ..BlockCache::default()
+            };
+        }
+        if block == cache.block {
+            update(&mut cache);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::address;
+
+    use super::*;
+
+    const WETH: Address = address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2");
+    const STETH: Address = address!("ae7ab96520DE3A18E5e111B5EaAb095312D7fE84");
+    const USDC: Address = address!("A0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48");
+    const USDC_FEED: Address = address!("986b5E1e1755e3C2440e960477f25201B0a8bbD4");
+
+    fn eth(amount: &str) -> U256 {
+        parse_ether(amount).unwrap()
+    }
+
+    // The static price table of the tests: WETH and stETH fixed, USDC by feed
+    fn price_table() -> PriceSource {
+        PriceSource {
+            fixed: [(WETH, eth("1")), (STETH, eth("0.999"))].into(),
+            feeds: [(USDC, USDC_FEED)].into(),
+            max_age: Duration::from_secs(3600),
+        }
+    }
+
+    #[test]
+    fn parses_asset_prices() {
+        let entry: AssetPrice = format!(" {WETH}:1.5 ").parse().unwrap();
+        assert_eq!(entry.asset, WETH);
+        assert_eq!(entry.price, eth("1.5"));
+        assert_eq!(entry.to_string().parse::<AssetPrice>().unwrap(), entry);
+
+        assert!("1.5".parse::<AssetPrice>().is_err());
+        assert!("0x1234:1".parse::<AssetPrice>().is_err());
+        assert!(format!("{WETH}:one").parse::<AssetPrice>().is_err());
+    }
+
+    #[test]
+    fn parses_asset_price_feeds() {
+        let entry: AssetPriceFeed = format!("{USDC}:{USDC_FEED}").parse().unwrap();
+        assert_eq!(entry.asset, USDC);
+        assert_eq!(entry.feed, USDC_FEED);
+        assert_eq!(entry.to_string().parse::<AssetPriceFeed>().unwrap(), entry);
+
+        assert!(USDC.to_string().parse::<AssetPriceFeed>().is_err());
+        assert!(format!("{USDC}:feed").parse::<AssetPriceFeed>().is_err());
+    }
+
+    #[test]
+    fn parses_eth_amounts() {
+        assert_eq!("32".parse::<EthAmount>().unwrap().0, eth("32"));
+        let half: EthAmount = "0.5".parse().unwrap();
+        assert_eq!(half.0, eth("0.5"));
+        assert_eq!(half.to_string().parse::<EthAmount>().unwrap(), half);
+        assert!("half".parse::<EthAmount>().is_err());
+    }
+
+    #[test]
+    fn scales_feed_answers_to_wei() {
+        // 0.0004 ETH per USDC, quoted with 18 and 8 decimals
+        let answer = |raw: u64| I256::from_raw(U256::from(raw));
+        assert_eq!(feed_price(answer(400_000_000_000_000), 18), eth("0.0004"));
+        assert_eq!(feed_price(answer(40_000), 8), eth("0.0004"));
+    }
+
+    #[test]
+    fn values_amounts_with_their_decimals() {
+        // 2500 USDC (6 decimals) at 0.0004 ETH
+        assert_eq!(
+            eth_value(U256::from(2_500_000_000u64), eth("0.0004"), 6),
+            eth("1")
+        );
+        // 10 stETH (18 decimals) at 0.999 ETH
+        assert_eq!(eth_value(eth("10"), eth("0.999"), 18), eth("9.99"));
+        assert_eq!(eth_value(U256::MAX, eth("2"), 0), U256::MAX);
+    }
+
+    #[test]
+    fn fixed_prices_come_before_feeds() {
+        let (prices, feeds) = price_table().fixed_prices(&[WETH, USDC, STETH]).unwrap();
+        assert_eq!(
+            prices,
+            HashMap::from([(WETH, eth("1")), (STETH, eth("0.999"))])
+        );
+        assert_eq!(feeds, vec![(USDC, USDC_FEED)]);
+    }
+
+    #[test]
+    fn assets_without_a_price_are_refused() {
+        let unknown = Address::repeat_byte(0x42);
+        assert!(matches!(
+            price_table().fixed_prices(&[WETH, unknown]),
+            Err(TaskError::PriceUnavailable(_))
+        ));
+    }
+}
@@ -1,30 +1,44 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     fs,
-    sync::{Arc, PoisonError},
+    sync::{Arc, Mutex},
     time::Duration,
 };
 
 use alloy::{
-    primitives::{Address, Uint, U256},
+    primitives::{utils::format_ether, Address, Bytes, Signature, Uint, B256, U256},
     providers::Provider,
-    rpc::types::{BlockNumberOrTag, Filter},
-    sol_types::SolEvent,
     transports::http::Client,
 };
-use chrono::{DateTime, Utc};
+use axum::{
+    extract::{FromRef, State},
+    http::{HeaderMap, StatusCode},
+    routing::get,
+    Json, Router,
+};
+use chrono::Utc;
 use eyre::Result;
 use serde::{Deserialize, Serialize};
 use tokio::{
     signal,
+    task::JoinSet,
     time::{self},
 };
-use tracing::{error, info};
-use url::Url;
+use tracing::{debug, error, info, info_span, instrument, warn, Instrument};
 
 use crate::{
-    aggregator::{Operator, OperatorState},
-    contract::{ContractManager, VaultContract,TxnVerifier},
+    aggregator::{check_timestamp, Operator, OperatorState},
+    contract::ContractManager,
+    dss::{task_key, Dss, DssResponse, DssTask},
+    error::AppError,
+    history::{self, OperatorResult, TaskHistory, TaskRecord, TaskSource, TaskStatus},
+    liveness::Observation,
+    metrics::metrics,
+    outbox::Outbox,
+    scanner::LogScanner,
+    stake::{PriceSource, StakeNormalizer},
+    telemetry,
+    tls::{ClientTls, OperatorClient},
     Config, TaskError,
 };
 
@@ -32,6 +46,9 @@ use crate::{
 pub struct Task {
     pub transaction_hash: String,
     pub block_number: String,
+    /// Validator the transaction is expected from, for tasks submitted over HTTP
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pubkey: Option<String>,
 }
 
 // Struct for operator verification response
@@ -49,14 +66,7 @@ pub struct TaskRequest {
     pub block_number: u64,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct CompletedTask {
-    pub value: U256,
-    pub response: U256,
-    pub completed_at: DateTime<Utc>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct OperatorResponse {
     pub is_included: bool,
     pub proposer_index: Option<u64>,
@@ -65,214 +75,479 @@ pub struct OperatorResponse {
 
 #[derive(Serialize, Deserialize)]
 pub struct BlockNumberData {
+    /// Block the scan of a deployment without a cursor of its own starts from
     pub block_number: u64,
+    /// Next block to scan for task events, by DSS deployment address
+    #[serde(default)]
+    pub deployments: BTreeMap<Address, u64>,
+}
+
+impl BlockNumberData {
+    /// Next block to scan for the events of `dss`.
+    pub fn next_block(&self, dss: Address) -> u64 {
+        self.deployments
+            .get(&dss)
+            .copied()
+            .unwrap_or(self.block_number)
+    }
+}
+
+/// Claim on a task being processed, released when dropped.
+pub struct TaskClaim {
+    key: B256,
+    in_flight: Arc<Mutex<HashSet<B256>>>,
+}
+
+impl Drop for TaskClaim {
+    fn drop(&mut self) {
+        let mut in_flight = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+        in_flight.remove(&self.key);
+    }
+}
+
+/// Most tasks of one DSS waiting in the retry queue.
+pub const MAX_QUEUED_RETRIES: usize = 1000;
+
+/// Event tasks to process again on the next tick of their DSS, with the
+/// number of times they failed.
+#[derive(Debug, Default)]
+pub struct RetryQueue {
+    tasks: Mutex<HashMap<Address, Vec<(TaskRecord, u32)>>>,
+}
+
+impl RetryQueue {
+    /// Queue a task, or return `false` when `MAX_QUEUED_RETRIES` tasks of the
+    /// DSS are already waiting.
+    pub fn push(&self, dss: Address, record: TaskRecord, failures: u32) -> bool {
+        let mut tasks = self.tasks.lock().unwrap_or_else(|e| e.into_inner());
+        let queued = tasks.entry(dss).or_default();
+        if queued.len() >= MAX_QUEUED_RETRIES {
+            return false;
+        }
+        queued.push((record, failures));
+        true
+    }
+
+    /// Take the tasks of `dss`, oldest first.
+    pub fn take(&self, dss: Address) -> Vec<(TaskRecord, u32)> {
+        let mut tasks = self.tasks.lock().unwrap_or_else(|e| e.into_inner());
+        tasks.remove(&dss).unwrap_or_default()
+    }
 }
 
 pub struct TaskService {
     contract_manager: ContractManager,
     operator_state: Arc<OperatorState>,
-    square_number_address: Address,
-    dss_address: Address,
+    history: Arc<TaskHistory>,
+    outbox: Arc<Outbox>,
+    /// DSS the tasks submitted over HTTP are verified for
+    txn_verifier_address: Address,
     block_number_store: String,
-    block_number: u64,
-    rpc_url: Url,
-    private_key: alloy::signers::local::PrivateKeySigner,
-    client: Client,
-    txn_verifier_address:Address,
+    /// Event cursors of the deployments, advanced as their logs are processed
+    block_numbers: Mutex<BlockNumberData>,
+    stake: StakeNormalizer,
+    /// Stake in wei of ETH an operator needs in a DSS to be sent its tasks
+    min_operator_stake: Option<U256>,
+    client: OperatorClient,
+    task_deadline: Duration,
+    task_quorum: usize,
+    task_retries: u32,
     heartbeat_interval: Duration,
+    /// Tasks being processed, by task key
+    in_flight: Arc<Mutex<HashSet<B256>>>,
+    retries: RetryQueue,
 }
 
 impl TaskService {
-    pub fn new(operator_state: Arc<OperatorState>, config: Config) -> Result<Self> {
+    pub fn new(
+        operator_state: Arc<OperatorState>,
+        history: Arc<TaskHistory>,
+        outbox: Arc<Outbox>,
+        tls: Arc<ClientTls>,
+        config: Config,
+    ) -> Result<Self> {
         let contract_manager = ContractManager::new(&config)?;
-        let square_number_address = config.txn_verifier_address;
-        let dss_address = config.txn_verifier_address;
         let block_number_store = config.block_number_store.clone();
-        let block_number: u64 = config.load_block_number()?;
-        let rpc_url = config.get_rpc_url()?;
-        let private_key = config.get_private_key()?;
-        let txn_verifier_address=config.txn_verifier_address;
+        let block_numbers = config.load_block_numbers()?;
+        let stake = StakeNormalizer::new(
+            contract_manager.multicall.clone(),
+            PriceSource::from_config(&config),
+        );
+        let txn_verifier_address = config.txn_verifier_address;
         let heartbeat_interval = Duration::from_millis(config.heartbeat);
-        let client = Client::new();
+        let request_timeout = Duration::from_millis(config.operator_request_timeout);
+        let connect_timeout = Duration::from_millis(config.operator_connect_timeout);
+        let pool_max_idle = config.operator_pool_max_idle;
+        let pool_idle_timeout = Duration::from_secs(config.operator_pool_idle_secs);
+        let keepalive = Duration::from_secs(config.operator_keepalive_secs);
+        let client = OperatorClient::new(tls, move |builder| {
+            builder
+                .timeout(request_timeout)
+                .connect_timeout(connect_timeout)
+                .pool_max_idle_per_host(pool_max_idle)
+                .pool_idle_timeout(pool_idle_timeout)
+                .tcp_keepalive(keepalive)
+        })?;
         Ok(Self {
             contract_manager,
             operator_state,
-            square_number_address,
+            history,
+            outbox,
             txn_verifier_address,
-            dss_address,
             block_number_store,
-            block_number,
-            rpc_url,
-            private_key,
+            block_numbers: Mutex::new(block_numbers),
+            stake,
+            min_operator_stake: config.min_operator_stake.map(|amount| amount.0),
             client,
+            task_deadline: Duration::from_millis(config.task_deadline),
+            task_quorum: config.task_quorum,
+            task_retries: config.task_retries,
             heartbeat_interval,
+            in_flight: Arc::new(Mutex::new(HashSet::new())),
+            retries: RetryQueue::default(),
         })
     }
 
-    pub async fn start(self: Arc<Self>) {
-        info!("Listening for task request events");
+    /// Whether the response of the task with `key` is waiting in the outbox.
+    pub fn is_queued(&self, key: &B256) -> Result<bool> {
+        self.outbox.contains(key)
+    }
 
-        let heartbeat_interval = self.heartbeat_interval;
+    /// Claim the task with `key` for processing, or `None` when it is already
+    /// in flight.
+    pub fn claim(&self, key: B256) -> Option<TaskClaim> {
+        let mut in_flight = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+        if !in_flight.insert(key) {
+            metrics().tasks_skipped.with_label_values(&["in_flight"]).inc();
+            return None;
+        }
+        Some(TaskClaim {
+            key,
+            in_flight: self.in_flight.clone(),
+        })
+    }
 
-        tokio::spawn(async move {
-            let mut interval = time::interval(heartbeat_interval);
-            loop {
-                tokio::select! {
-                    _ = interval.tick() => {
-                        if let Err(e) = self.watch_for_task_events().await {
-                            error!("Failed to watch for task events: {e}");
+    /// Watches every served DSS for task events, each on its own loop.
+    pub async fn start(self: Arc<Self>) {
+        for index in 0..self.contract_manager.deployments.len() {
+            let service = self.clone();
+            tokio::spawn(async move {
+                let dss = &service.contract_manager.deployments[index];
+                info!("Listening for task request events of {}", dss.deployment);
+                let scanner = dss.scanner();
+                let mut interval = time::interval(service.heartbeat_interval);
+                loop {
+                    tokio::select! {
+                        _ = interval.tick() => {
+                            if let Err(e) = service.watch_for_task_events(dss, &scanner).await {
+                                error!("Failed to watch for task events of {}: {e}", dss.deployment);
+                            }
+                        }
+                        _ = signal::ctrl_c() => {
+                            info!("Received shutdown signal. Stopping the aggregator...");
+                            break;
                         }
-                    }
-                    _ = signal::ctrl_c() => {
-                        info!("Received shutdown signal. Stopping the aggregator...");
-                        break;
                     }
                 }
-            }
 
-            info!("Aggregator service stopped gracefully.");
-        });
+                info!("Stopped watching {}.", dss.deployment);
+            });
+        }
     }
 
-    async fn watch_for_task_events(&self) -> Result<()> {
-        let txn_verifier_address = self.txn_verifier_address;
-        let next_block_to_check: u64 = self.block_number;
-        let filter = Filter::new()
-            .address(txn_verifier_address)
-            .from_block(BlockNumberOrTag::Number(next_block_to_check));
-    
-        let operators = match self.operator_state.operators.read() {
-            Ok(guard) => guard.clone(),
-            Err(PoisonError { .. }) => {
-                error!("Failed to acquire read lock on operator state");
-                return Err(eyre::anyhow!(
-                    "Failed to acquire read lock on operator state"
-                ));
-            }
-        };
-        
-        let logs = self.contract_manager.provider.get_logs(&filter).await?;
-        let mut new_last_checked_block = next_block_to_check;
-
-        info!("logs for mine {:?}",logs);
-
-        for log in logs {
-            if let Some(&TxnVerifier::TxnVerificationResult::SIGNATURE_HASH) = log.topic0() {
-                let TxnVerifier::TxnVerificationResult {
-                    txnHash,
-                    blockNumber,
-                } = log.log_decode()?.inner.data;
-
-                info!("logs for individial  {:?}",log);
-
-                info!("txnHash_workd   {:?}",txnHash);
-                info!("blockNumber_world   {:?}",blockNumber);
-
-
-
-                let task = Task {
-                    transaction_hash: txnHash.to_string(),
-                    block_number:blockNumber.to_string()
-
-                };
-
-                info!("operators   {:?}",operators);
-
-
-                if !operators.is_empty() {
-                    let response = self
-                        .send_task_to_all_operators(task, &operators)
-                        .await?;
-
-
-                        info!("response_operators     {:?}",response);
-
-
+    async fn watch_for_task_events(&self, dss: &Dss, scanner: &LogScanner) -> Result<()> {
+        for (record, failures) in self.retries.take(dss.deployment.address) {
+            self.run_event_task(dss, record, failures).await;
+        }
 
-                    let task_response = TxnVerifier::OperatorResponse {
-                        is_included: response.is_included,
-                        proposer_index: response.proposer_index.unwrap_or(0) as u64,
-                        block_number: response.block_number,
-                    };
-                    
-                    let dss_task_request = TxnVerifier::Task {
-                        transaction_hash: txnHash.to_string(),
-                        block_number:blockNumber.to_string()
+        let next_block_to_check = self
+            .lock_block_numbers()
+            .next_block(dss.deployment.address);
+        let latest_block = self
+            .contract_manager
+            .provider
+            .get_block_number()
+            .await
+            .inspect_err(|_| metrics().rpc_error("eth_blockNumber"))?;
+        if next_block_to_check > latest_block {
+            return Ok(());
+        }
     
+        let outcome = scanner
+            .scan(
+                &self.contract_manager.provider,
+                next_block_to_check,
+                latest_block,
+                |progress| {
+                    info!(
+                        "Scanned blocks {}..={} of {} for {} ({} logs, next range {})",
+                        progress.from_block,
+                        progress.scanned_to,
+                        progress.to_block,
+                        dss.deployment,
+                        progress.logs_found,
+                        progress.range
+                    )
+                },
+            )
+            .await;
+        // An interrupted scan only covered the blocks before `next_block`: the
+        // cursor stops there and the next tick resumes from it
+        if let Some(e) = &outcome.interrupted {
+            warn!(
+                "Log scan of {} interrupted, resuming from block {}: {e}",
+                dss.deployment, outcome.next_block
+            );
+        }
+        let label = dss.deployment.to_string();
+        let processed_block = outcome.next_block.saturating_sub(1);
+        metrics()
+            .last_processed_block
+            .with_label_values(&[&label])
+            .set(processed_block as i64);
+        metrics()
+            .chain_head_lag
+            .with_label_values(&[&label])
+            .set(latest_block.saturating_sub(processed_block) as i64);
 
-                    };
-
-                    // info!("response_operators  {:?}",task_response);
+        let event = dss.deployment.kind.event();
+        for log in outcome.logs {
+            let task = match dss.decode_task(&log) {
+                Ok(Some(task)) => task,
+                Ok(None) => continue,
+                Err(e) => {
+                    error!("Failed to decode task event of {}: {e}", dss.deployment);
+                    continue;
+                }
+            };
+            metrics().events_ingested.with_label_values(&[event]).inc();
+            info!("Task {task} from {} at block {:?}", dss.deployment, log.block_number);
 
-                    match self
-                        .contract_manager
-                        .submit_task_response(dss_task_request, task_response)
-                        .await
-                    {
-                        Ok(tx) => info!("Transaction sent: {:?}", tx),
-                        Err(e) => error!("Failed to send transaction: {:?}", e),
-                    }
-                    // new_last_checked_block =
-                        // new_last_checked_block.max(task_request.block_number + 1);
-                } else {
-                    info!("No operators are registered or no task requests were found.");
+            let task_id = match task.id() {
+                Ok(task_id) => task_id,
+                Err(e) => {
+                    error!("Failed to derive the id of task {task}: {e}");
+                    continue;
                 }
-            }
+            };
+            let record = TaskRecord::new(
+                task_key(dss.deployment.address, task_id),
+                task,
+                dss.deployment.address,
+                TaskSource::from_log(&log, event),
+            );
+            self.run_event_task(dss, record, 0).await;
         }
- 
- 
 
-        
-        let _ = self
-            .write_block_number_to_file(&self.block_number_store, new_last_checked_block)
-            .await;
+        if let Err(e) = self.store_block_number(dss.deployment.address, outcome.next_block) {
+            error!("Failed to store the event cursor of {}: {e}", dss.deployment);
+        }
     
         Ok(())
     }
 
+    // The cursor moves past the log of a task, so a task already in flight,
+    // e.g. submitted over HTTP or still processed by an earlier tick, is
+    // processed again on the next tick, and so is a failed task or one no
+    // operator was available for, up to `task_retries` times. A task out of
+    // retries stays in the history with its error.
+    async fn run_event_task(&self, dss: &Dss, record: TaskRecord, failures: u32) {
+        let Some(claim) = self.claim(record.key) else {
+            info!("Task {} is already in flight, retrying on the next tick", record.task);
+            self.retry(dss, record, failures);
+            return;
+        };
+        let failed = match self.process_task(claim, record.clone(), true).await {
+            Ok(processed) => processed.status == TaskStatus::NoOperators,
+            Err(e) => {
+                error!("Failed to process task {}: {e}", record.task);
+                self.record_error(record.clone(), &e);
+                true
+            }
+        };
+        if !failed {
+            return;
+        }
+        if failures < self.task_retries {
+            self.retry(dss, record, failures + 1);
+        } else {
+            warn!("Task {} failed {} times, giving up", record.task, failures + 1);
+        }
+    }
+
+    fn retry(&self, dss: &Dss, record: TaskRecord, failures: u32) {
+        let task = record.task.to_string();
+        if !self.retries.push(dss.deployment.address, record, failures) {
+            warn!("Retry queue of {} is full, dropping task {task}", dss.deployment);
+        }
+    }
 
+    // Keeps the error of a task whose failure was not recorded by
+    // `process_task`, e.g. one that failed before its record was saved
+    fn record_error(&self, record: TaskRecord, error: &eyre::Report) {
+        let mut record = match self.history.get(&record.key) {
+            Ok(Some(saved)) if saved.error.is_some() => return,
+            Ok(saved) => saved.unwrap_or(record),
+            Err(e) => {
+                error!("Failed to read task {}: {e}", record.task);
+                record
+            }
+        };
+        record.error = Some(error.to_string());
+        self.save_record(&record);
+    }
 
-    async fn get_operator_stake_normalized_eth(
+    /// Send a task to the available operators and, when `submit` is set,
+    /// submit the answer to the DSS unless it already holds one. The record is
+    /// saved once the task settles; fan-out failures are returned after being
+    /// recorded.
+    #[instrument(skip_all, fields(task = %record.task, submit))]
+    pub async fn process_task(
         &self,
-        operator: Address,
-    ) -> Result<U256, TaskError> {
-        let vaults = self
-            .contract_manager
-            .fetch_vaults_staked_in_dss(operator, self.dss_address)
-            .await?;
+        _claim: TaskClaim,
+        mut record: TaskRecord,
+        submit: bool,
+    ) -> Result<TaskRecord> {
+        let task_id = record.task.id()?;
+        let dss = self.contract_manager.dss(record.dss)?;
+        let key = task_key(dss.deployment.address, task_id);
+        if submit {
+            if self.outbox.contains(&key)? {
+                info!("Task {task_id} is already in the outbox, skipping");
+                metrics().tasks_skipped.with_label_values(&["queued"]).inc();
+                return Ok(self.history.get(&record.key)?.unwrap_or(record));
+            }
 
-        let mut stake = Uint::from(0u64);
+            match dss.stored_response(&record.task).await {
+                Ok(Some(stored)) => {
+                    info!("Task {task_id} already has a response on chain, skipping");
+                    metrics().tasks_skipped.with_label_values(&["completed"]).inc();
+                    return Ok(self.record_stored_response(record, stored));
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    record.error = Some(e.to_string());
+                    record.set_status(TaskStatus::SubmissionFailed);
+                    self.save_record(&record);
+                    return Err(e.into());
+                }
+            }
+        }
 
-        for vault in vaults {
-            let total_assets =
-                VaultContract::new(self.rpc_url.clone(), self.private_key.clone(), vault)?
-                    .vault_instance
-                    .totalAssets()
-                    .call()
+        let operators = self.operator_state.available_operators()?;
+        debug!(operators = ?operators, "Available operators");
+        let operators = match self.min_operator_stake {
+            Some(min_stake) if !operators.is_empty() => {
+                match self
+                    .staked_operators(dss.deployment.address, operators, min_stake)
                     .await
-                    .map_err(|_| TaskError::ContractCallError)?
-                    ._0;
+                {
+                    Ok(operators) => operators,
+                    Err(e) => {
+                        record.error = Some(e.to_string());
+                        record.set_status(TaskStatus::NoOperators);
+                        self.save_record(&record);
+                        return Err(e.into());
+                    }
+                }
+            }
+            _ => operators,
+        };
 
-            // TODO: Normalize total assets to ETH
-            stake += total_assets;
+        if operators.is_empty() {
+            info!("No operators are registered or no task requests were found.");
+            record.set_status(TaskStatus::NoOperators);
+            self.save_record(&record);
+            return Ok(record);
         }
 
-        Ok(stake)
+        let task = record.task.clone();
+        let response = match self
+            .send_task_to_all_operators(task.clone(), &operators, &mut record.responses)
+            .await
+        {
+            Ok(response) => response,
+            Err(e) => {
+                record.error = Some(e.to_string());
+                record.set_status(TaskStatus::OperatorsFailed);
+                self.save_record(&record);
+                return Err(e.into());
+            }
+        };
+
+        debug!(?response, "Operators settled the task");
+        record.result = Some(response.clone());
+        if !submit {
+            record.set_status(TaskStatus::Answered);
+            self.save_record(&record);
+            return Ok(record);
+        }
+
+        // Written to the outbox before anything is sent, so the submission
+        // survives restarts and is retried until it is confirmed. The record
+        // is saved first since the submitter updates it from then on.
+        record.set_status(TaskStatus::Queued);
+        self.save_record(&record);
+        if let Err(e) = self
+            .outbox
+            .enqueue(key, record.key, task_id, dss.deployment.address, task, response)
+        {
+            error!("Failed to queue the response of task {task_id}: {e}");
+            record.error = Some(e.to_string());
+            record.set_status(TaskStatus::SubmissionFailed);
+            self.save_record(&record);
+        }
+        Ok(record)
+    }
+
+    // Operators with more than `min_stake` staked in `dss`, valued in ETH
+    async fn staked_operators(
+        &self,
+        dss: Address,
+        operators: HashSet<Operator>,
+        min_stake: U256,
+    ) -> Result<HashSet<Operator>, TaskError> {
+        let addresses = operators.iter().map(|o| *o.public_key()).collect();
+        let (stakes, total_stake) = self
+            .get_operator_stake_mapping(dss, addresses, min_stake)
+            .await?;
+        info!(
+            "{} of {} operators have more than {} ETH staked in {dss}, {} ETH in total",
+            stakes.len(),
+            operators.len(),
+            format_ether(min_stake),
+            format_ether(total_stake)
+        );
+        Ok(operators
+            .into_iter()
+            .filter(|operator| stakes.contains_key(operator.public_key()))
+            .collect())
     }
 
+    // Vaults are read and valued in batches pinned to one block, so that the
+    // stakes of every operator come from the same snapshot and are priced alike
     async fn get_operator_stake_mapping(
         &self,
+        dss_address: Address,
         operators: Vec<Address>,
         min_acceptable_stake: U256,
     ) -> Result<(HashMap<Address, U256>, U256), TaskError> {
         let mut stake_mapping = HashMap::new();
         let mut total_stake = Uint::from(0u64);
+        let block = self.contract_manager.block_number().await?;
 
-        for operator in operators {
-            let stake = self
-                .get_operator_stake_normalized_eth(operator)
-                .await
-                .map_err(TaskError::from)?;
+        let operator_vaults = self
+            .contract_manager
+            .fetch_vaults_staked_in_dss_at(&operators, dss_address, block)
+            .await?;
+        let vaults: Vec<Address> = operator_vaults.iter().flatten().copied().collect();
+        let values = self.stake.vaults_eth(&vaults, block).await?;
+
+        for (operator, vaults) in operators.into_iter().zip(operator_vaults) {
+            let mut stake = Uint::from(0u64);
+            for vault in vaults {
+                stake += values[&vault];
+            }
 
             if stake > min_acceptable_stake {
                 stake_mapping.insert(operator, stake);
@@ -285,53 +560,20 @@ impl TaskService {
 
     async fn send_task_to_all_operators(
         &self,
-        task: Task,
+        task: DssTask,
         operators: &HashSet<Operator>,
-    ) -> Result<OperatorResponse, TaskError> {
-        // Store any error to return if no operator succeeds
-        let mut last_error: Option<TaskError> = None;
-    
-        for operator in operators.iter() {
-            let operator = operator.clone();
-    
-            match self
-                .client
-                .post(format!("{}operator/verify", operator.url()))
-                .header("Content-Type", "application/json")
-                .json(&task)
-                .send()
-                .await
-            {
-                Ok(response) => {
-                    match response.text().await {
-                        Ok(body) => {
-                            match serde_json::from_str::<OperatorResponse>(&body) {
-                                Ok(operator_response) => {
-
-                                    info!("operator_response {:?}",operator_response);
-                                    return Ok(operator_response);
-                                }
-                                Err(e) => {
-                                    error!("Failed to parse operator response: {:?}", e);
-                                    // last_error = Some(TaskError::ParseError(e.to_string()));
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            error!("Failed to get response body: {:?}", e);
-                            // last_error = Some(TaskError::ResponseError(e.to_string()));
-                        }
-                    }
-                }
-                Err(e) => {
-                    error!("Failed to get response: {:?}", e);
-                    // last_error = Some(TaskError::RequestError(e.to_string()));
-                }
-            }
-        }
-    
-        // If we got here, no operator succeeded
-        Err(last_error.unwrap_or(TaskError::NoOperatorAvailable))
+        results: &mut Vec<OperatorResult>,
+    ) -> Result<DssResponse, TaskError> {
+        fan_out(
+            self.client.get(),
+            task,
+            operators,
+            self.task_quorum,
+            self.task_deadline,
+            results,
+            |operator, success, latency| self.record_task_result(operator, success, latency),
+        )
+        .await
     }
 
     // async fn verify_message(&self, task_response: &OperatorResponse) -> Result<bool> {
@@ -342,12 +584,705 @@ impl TaskService {
     //     Ok(recovered_address == address)
     // }
 
-    async fn write_block_number_to_file(&self, file: &str, val: u64) -> Result<()> {
-        let data = BlockNumberData { block_number: val };
+    // Keep the record of the run that submitted the response, which has the
+    // operators' answers and the transaction
+    fn record_stored_response(&self, mut record: TaskRecord, stored: DssResponse) -> TaskRecord {
+        if let Ok(Some(existing)) = self.history.get(&record.key) {
+            if matches!(
+                existing.status,
+                TaskStatus::Submitted | TaskStatus::Confirmed | TaskStatus::AlreadySubmitted
+            ) {
+                return existing;
+            }
+        }
+        record.result = Some(stored);
+        record.set_status(TaskStatus::AlreadySubmitted);
+        self.save_record(&record);
+        record
+    }
 
-        let json_data = serde_json::to_string_pretty(&data)?;
-        fs::write(file, json_data)?;
+    fn save_record(&self, record: &TaskRecord) {
+        metrics()
+            .tasks_processed
+            .with_label_values(&[record.status.as_str()])
+            .inc();
+        if let Err(e) = self.history.put(record) {
+            error!("Failed to record task {}: {e}", record.task);
+        }
+    }
+
+    fn record_task_result(&self, operator: &Operator, success: bool, latency: Duration) {
+        let public_key = *operator.public_key();
+        let recorded = if success {
+            self.operator_state
+                .record_success(public_key, Observation::Task, latency)
+        } else {
+            self.operator_state
+                .record_failure(public_key, Observation::Task)
+        };
+        if let Err(e) = recorded {
+            error!("Failed to record task result of {public_key}: {e}");
+        }
+    }
+
+    fn lock_block_numbers(&self) -> std::sync::MutexGuard<'_, BlockNumberData> {
+        self.block_numbers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // The file is written under the lock so that the watchers of different
+    // deployments do not overwrite each other's cursor
+    fn store_block_number(&self, dss: Address, next_block: u64) -> Result<()> {
+        let mut block_numbers = self.lock_block_numbers();
+        block_numbers.deployments.insert(dss, next_block);
+
+        let json_data = serde_json::to_string_pretty(&*block_numbers)?;
+        fs::write(&self.block_number_store, json_data)?;
 
         Ok(())
     }
 }
+
+// Requests every operator at once and settles on the first response given
+// by `quorum` of them. Requests still in flight when the quorum is reached
+// or the deadline passes are cancelled. `record_result` is told whether each
+// operator answered, and how fast.
+async fn fan_out(
+    client: Client,
+    task: DssTask,
+    operators: &HashSet<Operator>,
+    quorum: usize,
+    deadline: Duration,
+    results: &mut Vec<OperatorResult>,
+    record_result: impl Fn(&Operator, bool, Duration),
+) -> Result<DssResponse, TaskError> {
+    // Fewer operators than the quorum could never settle the task
+    let quorum = quorum.max(1);
+    if operators.len() < quorum {
+        warn!(
+            "Task {task} needs {quorum} matching responses but only {} operators are available",
+            operators.len()
+        );
+        metrics()
+            .quorum_outcomes
+            .with_label_values(&["too_few_operators"])
+            .inc();
+        return Err(TaskError::MajorityNotReached);
+    }
+    let started = time::Instant::now();
+    let deadline = started + deadline;
+
+    let mut requests = JoinSet::new();
+    for operator in operators.iter().cloned() {
+        let client = client.clone();
+        let task = task.clone();
+        let span = info_span!(
+            "operator_request",
+            operator = %operator.public_key(),
+            url = %operator.url()
+        );
+        requests.spawn(
+            async move {
+                let started = time::Instant::now();
+                let outcome = request_response(&client, &operator, &task).await;
+                (operator, started.elapsed(), outcome)
+            }
+            .instrument(span),
+        );
+    }
+
+    let mut pending = operators.clone();
+    let mut votes: Vec<(DssResponse, usize)> = Vec::new();
+    let mut settled = None;
+    let mut timed_out = false;
+    while settled.is_none() {
+        let joined = match time::timeout_at(deadline, requests.join_next()).await {
+            Ok(Some(joined)) => joined,
+            Ok(None) => break,
+            Err(_) => {
+                warn!(
+                    "Task {task} deadline reached with {} operators pending",
+                    pending.len()
+                );
+                timed_out = true;
+                break;
+            }
+        };
+        let (operator, latency, outcome) = match joined {
+            Ok(joined) => joined,
+            Err(e) => {
+                error!("Operator request failed to complete: {e}");
+                continue;
+            }
+        };
+        pending.remove(&operator);
+
+        match &outcome {
+            Ok(response) => {
+                debug!(operator = %operator.public_key(), ?response, "Operator answered")
+            }
+            Err(e) => error!("Operator {} failed: {e}", operator.public_key()),
+        }
+        record_result(&operator, outcome.is_ok(), latency);
+        let label = operator.public_key().to_string();
+        if outcome.is_ok() {
+            metrics()
+                .operator_latency
+                .with_label_values(&[&label])
+                .observe(latency.as_secs_f64());
+        }
+        metrics()
+            .operator_requests
+            .with_label_values(&[&label, if outcome.is_ok() { "ok" } else { "error" }])
+            .inc();
+        results.push(OperatorResult {
+            operator: *operator.public_key(),
+            url: operator.url().clone(),
+            latency_ms: latency.as_millis() as u64,
+            response: outcome.as_ref().ok().cloned(),
+            error: outcome.as_ref().err().cloned(),
+        });
+
+        if let Ok(response) = outcome {
+            let count = match votes.iter_mut().find(|(r, _)| *r == response) {
+                Some((_, count)) => {
+                    *count += 1;
+                    *count
+                }
+                None => {
+                    votes.push((response.clone(), 1));
+                    1
+                }
+            };
+            if count >= quorum {
+                settled = Some(response);
+            }
+        }
+    }
+    requests.abort_all();
+
+    // Operators that missed the deadline count as failed; those cancelled
+    // after the quorum was reached are only recorded
+    let elapsed = started.elapsed();
+    for operator in pending {
+        let (outcome, error) = if timed_out {
+            record_result(&operator, false, elapsed);
+            ("timeout", "Task deadline exceeded")
+        } else {
+            ("cancelled", "Cancelled after quorum was reached")
+        };
+        metrics()
+            .operator_requests
+            .with_label_values(&[&operator.public_key().to_string(), outcome])
+            .inc();
+        results.push(OperatorResult {
+            operator: *operator.public_key(),
+            url: operator.url().clone(),
+            latency_ms: elapsed.as_millis() as u64,
+            response: None,
+            error: Some(error.to_string()),
+        });
+    }
+
+    let (outcome, result) = match settled {
+        Some(response) => ("reached", Ok(response)),
+        None if votes.is_empty() => ("no_responses", Err(TaskError::NoOperatorAvailable)),
+        None => ("not_reached", Err(TaskError::MajorityNotReached)),
+    };
+    metrics()
+        .quorum_outcomes
+        .with_label_values(&[outcome])
+        .inc();
+    result
+}
+
+async fn request_response(
+    client: &Client,
+    operator: &Operator,
+    task: &DssTask,
+) -> Result<DssResponse, String> {
+    let response = client
+        .post(format!("{}{}", operator.url(), task.kind().operator_path()))
+        .headers(telemetry::trace_headers())
+        .header("Content-Type", "application/json")
+        .json(task)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to get response: {e}"))?;
+    let body = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to get response body: {e}"))?;
+    task.parse_response(&body)
+        .map_err(|e| format!("Failed to parse operator response: {e}"))
+}
+
+/// Body of `POST /aggregator/tasks`. Clients authenticate with an API key in
+/// the `x-api-key` header, or sign `task_message` with an allowed key and
+/// send `timestamp` and `signature`.
+#[derive(Debug, Deserialize)]
+pub struct TaskSubmission {
+    pub transaction_hash: String,
+    pub block_number: String,
+    pub pubkey: String,
+    /// Also submit the operators' answer to the DSS
+    #[serde(default)]
+    pub submit_on_chain: bool,
+    /// Unix time of signing, in seconds
+    pub timestamp: Option<u64>,
+    pub signature: Option<Bytes>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskAccepted {
+    /// Poll `GET /aggregator/tasks/{key}` for the result
+    pub key: B256,
+    pub status: TaskStatus,
+}
+
+/// Message a client signs (EIP-191) to submit a task.
+pub fn task_message(
+    submission: &TaskSubmission,
+    chain_id: u64,
+    aggregator: &Address,
+    timestamp: u64,
+) -> String {
+    format!(
+        "Verify transaction {} in block {} for {} with aggregator {aggregator} on chain {chain_id} at {timestamp}",
+        submission.transaction_hash, submission.block_number, submission.pubkey
+    )
+}
+
+/// Who may submit tasks over HTTP. With neither API keys nor submitters
+/// configured the endpoint rejects every request.
+#[derive(Debug)]
+pub struct TaskAuth {
+    api_keys: Vec<String>,
+    submitters: HashSet<Address>,
+    chain_id: u64,
+    aggregator: Address,
+    max_age: u64,
+}
+
+impl TaskAuth {
+    pub fn new(
+        api_keys: Vec<String>,
+        submitters: HashSet<Address>,
+        chain_id: u64,
+        aggregator: Address,
+        max_age: u64,
+    ) -> Self {
+        Self {
+            api_keys,
+            submitters,
+            chain_id,
+            aggregator,
+            max_age,
+        }
+    }
+
+    /// Name of the authenticated client, recorded as the task source.
+    pub fn authenticate(&self, headers: &HeaderMap, submission: &TaskSubmission) -> Result<String> {
+        if let Some(key) = headers.get("x-api-key") {
+            return match api_key_index(&self.api_keys, key.as_bytes()) {
+                Some(i) => Ok(format!("api key #{}", i + 1)),
+                None => Err(eyre::eyre!("Unknown API key")),
+            };
+        }
+
+        let (Some(timestamp), Some(signature)) = (submission.timestamp, &submission.signature) else {
+            eyre::bail!("Missing x-api-key header or timestamp and signature");
+        };
+        let now = Utc::now().timestamp().max(0) as u64;
+        check_timestamp("Submission", timestamp, now, self.max_age)?;
+        let signature = Signature::try_from(signature.as_ref())
+            .map_err(|e| eyre::eyre!("Invalid submission signature: {e}"))?;
+        let signer = signature
+            .recover_address_from_msg(task_message(
+                submission,
+                self.chain_id,
+                &self.aggregator,
+                timestamp,
+            ))
+            .map_err(|e| eyre::eyre!("Invalid submission signature: {e}"))?;
+        if !self.submitters.contains(&signer) {
+            eyre::bail!("{signer} may not submit tasks");
+        }
+        Ok(signer.to_string())
+    }
+}
+
+/// Position of `key` in `api_keys`. Every key is compared in full so timing
+/// does not reveal a prefix.
+pub fn api_key_index(api_keys: &[String], key: &[u8]) -> Option<usize> {
+    api_keys
+        .iter()
+        .enumerate()
+        .fold(None, |found, (i, candidate)| {
+            let equal = candidate.len() == key.len()
+                && candidate
+                    .as_bytes()
+                    .iter()
+                    .zip(key)
+                    .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+                    == 0;
+            found.or(equal.then_some(i))
+        })
+}
+
+/// State of the task routes.
+#[derive(Clone)]
+pub struct TaskApi {
+    pub service: Arc<TaskService>,
+    pub history: Arc<TaskHistory>,
+    pub auth: Arc<TaskAuth>,
+}
+
+impl FromRef<TaskApi> for Arc<TaskHistory> {
+    fn from_ref(api: &TaskApi) -> Self {
+        api.history.clone()
+    }
+}
+
+// Record the task as pending and process it in the background, so clients
+// poll the history for the result instead of holding the request open
+pub async fn submit_task(
+    State(api): State<TaskApi>,
+    headers: HeaderMap,
+    Json(submission): Json<TaskSubmission>,
+) -> Result<(StatusCode, Json<TaskAccepted>), AppError> {
+    let client = api
+        .auth
+        .authenticate(&headers, &submission)
+        .map_err(AppError::unauthorized)?;
+    let transaction_hash =
+        history::normalize_hash(&submission.transaction_hash).map_err(AppError::bad_request)?;
+
+    let key = task_key(api.service.txn_verifier_address, transaction_hash);
+    let claim = api.service.claim(key).ok_or_else(|| {
+        AppError::conflict(eyre::eyre!(
+            "Task {transaction_hash} is already being processed"
+        ))
+    })?;
+
+    if submission.submit_on_chain && api.service.is_queued(&key)? {
+        return Err(AppError::conflict(eyre::eyre!(
+            "Task {transaction_hash} is already queued for submission"
+        )));
+    }
+
+    let task = Task {
+        transaction_hash: transaction_hash.to_string(),
+        block_number: submission.block_number,
+        pubkey: Some(submission.pubkey),
+    };
+    // Kept apart from the record of the task's event, which is processed once
+    // this claim is released
+    let record = TaskRecord::api(
+        key,
+        DssTask::TxnVerifier(task),
+        api.service.txn_verifier_address,
+        client,
+    );
+    api.history.put(&record)?;
+    let record_key = record.key;
+    info!("Task {transaction_hash} submitted over HTTP");
+
+    let service = api.service.clone();
+    let submit = submission.submit_on_chain;
+    tokio::spawn(async move {
+        if let Err(e) = service.process_task(claim, record, submit).await {
+            error!("Failed to process task {transaction_hash}: {e}");
+        }
+    });
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(TaskAccepted {
+            key: record_key,
+            status: TaskStatus::Pending,
+        }),
+    ))
+}
+
+pub fn task_router(api: TaskApi) -> Router {
+    Router::new()
+        .route("/tasks", get(history::list_tasks).post(submit_task))
+        .route("/tasks/:key", get(history::get_task))
+        .with_state(api)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::address;
+
+    use super::*;
+    use crate::dss::{SquareResponse, SquareTask};
+
+    const TXN_VERIFIER: Address = address!("1111111111111111111111111111111111111111");
+    const SQUARE_NUMBER: Address = address!("2222222222222222222222222222222222222222");
+
+    #[test]
+    fn deployments_start_from_the_configured_block() {
+        let block_numbers: BlockNumberData =
+            serde_json::from_str(r#"{ "block_number": 100 }"#).unwrap();
+        assert_eq!(block_numbers.next_block(TXN_VERIFIER), 100);
+        assert_eq!(block_numbers.next_block(SQUARE_NUMBER), 100);
+    }
+
+    #[test]
+    fn cursors_advance_per_deployment() {
+        let mut block_numbers = BlockNumberData {
+            block_number: 100,
+            deployments: BTreeMap::new(),
+        };
+        block_numbers.deployments.insert(TXN_VERIFIER, 250);
+        assert_eq!(block_numbers.next_block(TXN_VERIFIER), 250);
+        assert_eq!(block_numbers.next_block(SQUARE_NUMBER), 100);
+
+        block_numbers.deployments.insert(SQUARE_NUMBER, 180);
+        block_numbers.deployments.insert(TXN_VERIFIER, 300);
+
+        let stored: BlockNumberData =
+            serde_json::from_str(&serde_json::to_string(&block_numbers).unwrap()).unwrap();
+        assert_eq!(stored.block_number, 100);
+        assert_eq!(stored.next_block(TXN_VERIFIER), 300);
+        assert_eq!(stored.next_block(SQUARE_NUMBER), 180);
+    }
+
+    fn event_record(dss: Address, block_number: u64) -> TaskRecord {
+        let task = DssTask::TxnVerifier(Task {
+            transaction_hash: format!("{:#x}", B256::with_last_byte(block_number as u8)),
+            block_number: block_number.to_string(),
+            pubkey: None,
+        });
+        let key = task_key(dss, task.id().unwrap());
+        TaskRecord::new(
+            key,
+            task,
+            dss,
+            TaskSource::Api {
+                client: "test".to_string(),
+            },
+        )
+    }
+
+    #[test]
+    fn skipped_tasks_are_retried_per_deployment() {
+        let retries = RetryQueue::default();
+        assert!(retries.push(TXN_VERIFIER, event_record(TXN_VERIFIER, 1), 0));
+        assert!(retries.push(SQUARE_NUMBER, event_record(SQUARE_NUMBER, 2), 0));
+        assert!(retries.push(TXN_VERIFIER, event_record(TXN_VERIFIER, 3), 2));
+
+        let blocks = |records: Vec<(TaskRecord, u32)>| {
+            records
+                .into_iter()
+                .map(|(record, failures)| match record.task {
+                    DssTask::TxnVerifier(task) => (task.block_number, failures),
+                    DssTask::SquareNumber(_) => unreachable!(),
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            blocks(retries.take(TXN_VERIFIER)),
+            [("1".to_string(), 0), ("3".to_string(), 2)]
+        );
+        assert!(retries.take(TXN_VERIFIER).is_empty());
+        assert_eq!(blocks(retries.take(SQUARE_NUMBER)), [("2".to_string(), 0)]);
+    }
+
+    #[test]
+    fn retry_queue_is_bounded_per_deployment() {
+        let retries = RetryQueue::default();
+        for _ in 0..MAX_QUEUED_RETRIES {
+            assert!(retries.push(TXN_VERIFIER, event_record(TXN_VERIFIER, 1), 1));
+        }
+        assert!(!retries.push(TXN_VERIFIER, event_record(TXN_VERIFIER, 2), 0));
+        assert!(retries.push(SQUARE_NUMBER, event_record(SQUARE_NUMBER, 2), 0));
+
+        assert_eq!(retries.take(TXN_VERIFIER).len(), MAX_QUEUED_RETRIES);
+        assert!(retries.push(TXN_VERIFIER, event_record(TXN_VERIFIER, 2), 0));
+    }
+
+    #[test]
+    fn api_submissions_keep_their_own_record() {
+        let event = event_record(TXN_VERIFIER, 1);
+        let first = TaskRecord::api(
+            event.key,
+            event.task.clone(),
+            TXN_VERIFIER,
+            "test".to_string(),
+        );
+        std::thread::sleep(Duration::from_millis(1));
+        let second = TaskRecord::api(
+            event.key,
+            event.task.clone(),
+            TXN_VERIFIER,
+            "test".to_string(),
+        );
+        assert_ne!(first.key, event.key);
+        assert_ne!(first.key, second.key);
+        assert_eq!(first.task.id().unwrap(), event.task.id().unwrap());
+    }
+
+    // An operator squaring numbers, answering `answer` after `delay` or
+    // failing when there is none
+    async fn operator(n: u8, answer: Option<u64>, delay: Duration) -> Operator {
+        let app = Router::new().route(
+            "/operator/square",
+            axum::routing::post(move || async move {
+                time::sleep(delay).await;
+                match answer {
+                    Some(response) => Ok(Json(SquareResponse {
+                        response: U256::from(response),
+                    })),
+                    None => Err(StatusCode::INTERNAL_SERVER_ERROR),
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Operator::new(Address::with_last_byte(n), url.parse().unwrap())
+    }
+
+    struct FanOut {
+        result: Result<DssResponse, TaskError>,
+        results: Vec<OperatorResult>,
+        recorded: HashMap<Address, bool>,
+    }
+
+    async fn square(operators: Vec<Operator>, quorum: usize, deadline: Duration) -> FanOut {
+        let recorded = Mutex::new(HashMap::new());
+        let mut results = Vec::new();
+        let result = fan_out(
+            Client::new(),
+            DssTask::SquareNumber(SquareTask {
+                value: U256::from(4),
+            }),
+            &operators.into_iter().collect(),
+            quorum,
+            deadline,
+            &mut results,
+            |operator, success, _| {
+                recorded
+                    .lock()
+                    .unwrap()
+                    .insert(*operator.public_key(), success);
+            },
+        )
+        .await;
+        FanOut {
+            result,
+            results,
+            recorded: recorded.into_inner().unwrap(),
+        }
+    }
+
+    fn squared(value: u64) -> DssResponse {
+        DssResponse::SquareNumber(SquareResponse {
+            response: U256::from(value),
+        })
+    }
+
+    fn error_of(results: &[OperatorResult], n: u8) -> Option<&str> {
+        results
+            .iter()
+            .find(|result| result.operator == Address::with_last_byte(n))
+            .and_then(|result| result.error.as_deref())
+    }
+
+    #[tokio::test]
+    async fn too_few_operators_are_not_asked() {
+        let fan_out = square(
+            vec![operator(1, Some(16), Duration::ZERO).await],
+            2,
+            Duration::from_secs(5),
+        )
+        .await;
+        assert!(matches!(fan_out.result, Err(TaskError::MajorityNotReached)));
+        assert!(fan_out.results.is_empty());
+        assert!(fan_out.recorded.is_empty());
+    }
+
+    #[tokio::test]
+    async fn the_quorum_settles_despite_failed_operators() {
+        let answer_after = Duration::from_millis(200);
+        let fan_out = square(
+            vec![
+                operator(1, Some(16), answer_after).await,
+                operator(2, Some(16), answer_after).await,
+                operator(3, None, Duration::ZERO).await,
+            ],
+            2,
+            Duration::from_secs(5),
+        )
+        .await;
+        assert_eq!(fan_out.result.unwrap(), squared(16));
+        assert_eq!(
+            fan_out.recorded,
+            HashMap::from([
+                (Address::with_last_byte(1), true),
+                (Address::with_last_byte(2), true),
+                (Address::with_last_byte(3), false),
+            ])
+        );
+        assert!(error_of(&fan_out.results, 1).is_none());
+        assert!(error_of(&fan_out.results, 3).is_some());
+    }
+
+    #[tokio::test]
+    async fn disagreeing_operators_do_not_reach_the_quorum() {
+        let fan_out = square(
+            vec![
+                operator(1, Some(16), Duration::ZERO).await,
+                operator(2, Some(17), Duration::ZERO).await,
+                operator(3, None, Duration::ZERO).await,
+            ],
+            2,
+            Duration::from_secs(5),
+        )
+        .await;
+        assert!(matches!(fan_out.result, Err(TaskError::MajorityNotReached)));
+        assert_eq!(fan_out.results.len(), 3);
+        assert_eq!(fan_out.recorded.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn no_answers_leave_no_operator_available() {
+        let fan_out = square(
+            vec![
+                operator(1, None, Duration::ZERO).await,
+                operator(2, None, Duration::ZERO).await,
+            ],
+            1,
+            Duration::from_secs(5),
+        )
+        .await;
+        assert!(matches!(
+            fan_out.result,
+            Err(TaskError::NoOperatorAvailable)
+        ));
+        assert!(fan_out.recorded.values().all(|success| !success));
+    }
+
+    #[tokio::test]
+    async fn operators_missing_the_deadline_count_as_failed() {
+        let fan_out = square(
+            vec![
+                operator(1, Some(16), Duration::ZERO).await,
+                operator(2, Some(16), Duration::from_secs(5)).await,
+            ],
+            2,
+            Duration::from_millis(200),
+        )
+        .await;
+        assert!(matches!(fan_out.result, Err(TaskError::MajorityNotReached)));
+        assert!(fan_out.recorded[&Address::with_last_byte(1)]);
+        assert!(!fan_out.recorded[&Address::with_last_byte(2)]);
+        assert_eq!(
+            error_of(&fan_out.results, 2),
+            Some("Task deadline exceeded")
+        );
+    }
+}
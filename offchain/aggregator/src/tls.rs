@@ -0,0 +1,424 @@
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::{Duration, SystemTime},
+};
+
+use alloy::transports::http::reqwest::{Certificate, Client, ClientBuilder, Identity};
+use axum_server::tls_rustls::RustlsConfig;
+use eyre::{eyre, Result};
+use rustls::{
+    crypto::ring,
+    pki_types::{CertificateDer, PrivateKeyDer},
+    ServerConfig,
+};
+use tokio::{signal, time};
+use tracing::{error, info};
+
+use crate::Config;
+
+/// Modification times of `paths`, compared to detect rotated certificates.
+fn modified(paths: &[&str]) -> Vec<Option<SystemTime>> {
+    paths
+        .iter()
+        .map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let file = File::open(path).map_err(|e| eyre!("Failed to open {path}: {e}"))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| eyre!("Failed to parse certificates in {path}: {e}"))?;
+    if certs.is_empty() {
+        eyre::bail!("No certificate in {path}");
+    }
+    Ok(certs)
+}
+
+fn load_key(path: &str) -> Result<PrivateKeyDer<'static>> {
+    let file = File::open(path).map_err(|e| eyre!("Failed to open {path}: {e}"))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .map_err(|e| eyre!("Failed to parse private key in {path}: {e}"))?
+        .ok_or_else(|| eyre!("No private key in {path}"))
+}
+
+/// Certificate the HTTP server is served with. The files are checked every
+/// `TLS_RELOAD_INTERVAL_SECS` and a rotated certificate is served to new
+/// connections without a restart.
+pub struct ServerTls {
+    cert_path: String,
+    key_path: String,
+    rustls: RustlsConfig,
+    modified: Mutex<Vec<Option<SystemTime>>>,
+    reload_interval: Duration,
+}
+
+impl ServerTls {
+    /// `None` when `TLS_CERT_PATH` and `TLS_KEY_PATH` are not set.
+    pub fn from_config(config: &Config) -> Result<Option<Self>> {
+        let (cert_path, key_path) = match (&config.tls_cert_path, &config.tls_key_path) {
+            (Some(cert), Some(key)) => (cert.clone(), key.clone()),
+            (None, None) => return Ok(None),
+            _ => eyre::bail!("TLS_CERT_PATH and TLS_KEY_PATH must be set together"),
+        };
+        let modified = modified(&[&cert_path, &key_path]);
+        let server_config = server_config(&cert_path, &key_path)?;
+        Ok(Some(Self {
+            cert_path,
+            key_path,
+            rustls: RustlsConfig::from_config(Arc::new(server_config)),
+            modified: Mutex::new(modified),
+            reload_interval: Duration::from_secs(config.tls_reload_interval_secs),
+        }))
+    }
+
+    pub fn rustls_config(&self) -> RustlsConfig {
+        self.rustls.clone()
+    }
+
+    pub async fn start(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut interval = time::interval(self.reload_interval);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {
+                        if let Err(e) = self.reload_if_changed() {
+                            error!("Failed to reload the server certificate: {e}");
+                        }
+                    }
+                    _ = signal::ctrl_c() => break,
+                }
+            }
+        });
+    }
+
+    fn reload_if_changed(&self) -> Result<()> {
+        let current = modified(&[&self.cert_path, &self.key_path]);
+        let mut modified = self.modified.lock().map_err(|e| eyre!("{e}"))?;
+        if *modified == current {
+            return Ok(());
+        }
+        let server_config = server_config(&self.cert_path, &self.key_path)?;
+        self.rustls.reload_from_config(Arc::new(server_config));
+        *modified = current;
+        info!("Reloaded the server certificate from {}", self.cert_path);
+        Ok(())
+    }
+}
+
+fn server_config(cert_path: &str, key_path: &str) -> Result<ServerConfig> {
+    let mut server_config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(load_certs(cert_path)?, load_key(key_path)?)?;
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(server_config)
+}
+
+/// Client certificate the aggregator presents to operators and the CA their
+/// certificates are verified against, on top of the system roots. Reloaded
+/// like [`ServerTls`]; each reload bumps the generation so that
+/// [`OperatorClient`]s rebuild their connection pool.
+pub struct ClientTls {
+    cert_path: Option<String>,
+    key_path: Option<String>,
+    ca_path: Option<String>,
+    identity: RwLock<Option<Identity>>,
+    roots: RwLock<Vec<Certificate>>,
+    modified: Mutex<Vec<Option<SystemTime>>>,
+    generation: AtomicU64,
+    reload_interval: Duration,
+}
+
+impl ClientTls {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        if config.tls_client_cert_path.is_some() != config.tls_client_key_path.is_some() {
+            eyre::bail!("TLS_CLIENT_CERT_PATH and TLS_CLIENT_KEY_PATH must be set together");
+        }
+        let tls = Self {
+            cert_path: config.tls_client_cert_path.clone(),
+            key_path: config.tls_client_key_path.clone(),
+            ca_path: config.tls_operator_ca_path.clone(),
+            identity: RwLock::new(None),
+            roots: RwLock::new(Vec::new()),
+            modified: Mutex::new(Vec::new()),
+            generation: AtomicU64::new(0),
+            reload_interval: Duration::from_secs(config.tls_reload_interval_secs),
+        };
+        tls.reload_if_changed()?;
+        Ok(tls)
+    }
+
+    fn paths(&self) -> Vec<&str> {
+        [&self.cert_path, &self.key_path, &self.ca_path]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect()
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Adds the client certificate and operator CA to `builder`.
+    pub fn configure(&self, mut builder: ClientBuilder) -> Result<ClientBuilder> {
+        if let Some(identity) = self.identity.read().map_err(|e| eyre!("{e}"))?.clone() {
+            builder = builder.use_rustls_tls().identity(identity);
+        }
+        for root in self.roots.read().map_err(|e| eyre!("{e}"))?.iter() {
+            builder = builder.add_root_certificate(root.clone());
+        }
+        Ok(builder)
+    }
+
+    pub async fn start(self: Arc<Self>) {
+        if self.paths().is_empty() {
+            return;
+        }
+        tokio::spawn(async move {
+            let mut interval = time::interval(self.reload_interval);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {
+                        if let Err(e) = self.reload_if_changed() {
+                            error!("Failed to reload the operator client certificate: {e}");
+                        }
+                    }
+                    _ = signal::ctrl_c() => break,
+                }
+            }
+        });
+    }
+
+    fn reload_if_changed(&self) -> Result<()> {
+        let current = modified(&self.paths());
+        let mut modified = self.modified.lock().map_err(|e| eyre!("{e}"))?;
+        if *modified == current {
+            return Ok(());
+        }
+
+        let identity = match (&self.cert_path, &self.key_path) {
+            (Some(cert), Some(key)) => {
+                let mut pem = fs::read(key).map_err(|e| eyre!("Failed to read {key}: {e}"))?;
+                pem.extend(fs::read(cert).map_err(|e| eyre!("Failed to read {cert}: {e}"))?);
+                Some(Identity::from_pem(&pem)?)
+            }
+            _ => None,
+        };
+        let roots = match &self.ca_path {
+            Some(ca) => Certificate::from_pem_bundle(
+                &fs::read(ca).map_err(|e| eyre!("Failed to read {ca}: {e}"))?,
+            )?,
+            None => Vec::new(),
+        };
+
+        *self.identity.write().map_err(|e| eyre!("{e}"))? = identity;
+        *self.roots.write().map_err(|e| eyre!("{e}"))? = roots;
+        *modified = current;
+        if self.generation.fetch_add(1, Ordering::AcqRel) > 0 {
+            info!("Reloaded the operator client certificate");
+        }
+        Ok(())
+    }
+}
+
+/// HTTP client towards operators, rebuilt with the current [`ClientTls`]
+/// after certificates rotate. `settings` applies the timeouts and pooling of
+/// the caller.
+pub struct OperatorClient {
+    tls: Arc<ClientTls>,
+    settings: Box<dyn Fn(ClientBuilder) -> ClientBuilder + Send + Sync>,
+    client: RwLock<(u64, Client)>,
+}
+
+impl OperatorClient {
+    pub fn new(
+        tls: Arc<ClientTls>,
+        settings: impl Fn(ClientBuilder) -> ClientBuilder + Send + Sync + 'static,
+    ) -> Result<Self> {
+        let generation = tls.generation();
+        let client = tls.configure(settings(Client::builder()))?.build()?;
+        Ok(Self {
+            tls,
+            settings: Box::new(settings),
+            client: RwLock::new((generation, client)),
+        })
+    }
+
+    /// The current client. Keeps the previous one when the rebuild fails so
+    /// a bad rotation does not stop task distribution.
+    pub fn get(&self) -> Client {
+        let generation = self.tls.generation();
+        {
+            let client = self.client.read().unwrap_or_else(|e| e.into_inner());
+            if client.0 == generation {
+                return client.1.clone();
+            }
+        }
+        let mut client = self.client.write().unwrap_or_else(|e| e.into_inner());
+        match self
+            .tls
+            .configure((self.settings)(Client::builder()))
+            .and_then(|builder| Ok(builder.build()?))
+        {
+            Ok(rebuilt) => *client = (generation, rebuilt),
+            Err(e) => {
+                error!("Failed to rebuild the operator client: {e}");
+                client.0 = generation;
+            }
+        }
+        client.1.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rcgen::{BasicConstraints, Certificate, CertificateParams, DnType, IsCa, KeyPair};
+    use rustls::{
+        pki_types::ServerName, ClientConfig, ClientConnection, RootCertStore, ServerConnection,
+    };
+
+    use super::*;
+
+    // A certificate for `name`, issued by `issuer` or self-signed
+    fn issue(name: &str, issuer: Option<&(Certificate, KeyPair)>) -> (Certificate, KeyPair) {
+        let key = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::new(vec![name.to_string()]).unwrap();
+        params.distinguished_name.push(DnType::CommonName, name);
+        let cert = match issuer {
+            Some((issuer, issuer_key)) => params.signed_by(&key, issuer, issuer_key),
+            None => {
+                params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+                params.self_signed(&key)
+            }
+        }
+        .unwrap();
+        (cert, key)
+    }
+
+    // Writes `contents` to a file of this test run, modified at `at` so that
+    // rewrites are seen even within the file system's time resolution
+    fn write(name: &str, contents: &str, at: SystemTime) -> String {
+        let path = std::env::temp_dir().join(format!("aggregator-{}-{name}", std::process::id()));
+        fs::write(&path, contents).unwrap();
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(at)
+            .unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    // Runs a handshake in memory and returns the certificate the server
+    // presented
+    fn handshake(server: &ServerTls, ca: &Certificate) -> CertificateDer<'static> {
+        let mut roots = RootCertStore::empty();
+        roots.add(ca.der().clone()).unwrap();
+        let client_config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        let mut client = ClientConnection::new(
+            Arc::new(client_config),
+            ServerName::try_from("localhost").unwrap(),
+        )
+        .unwrap();
+        let mut server = ServerConnection::new(server.rustls_config().get_inner()).unwrap();
+        while client.is_handshaking() || server.is_handshaking() {
+            let mut flight = Vec::new();
+            client.write_tls(&mut flight).unwrap();
+            server.read_tls(&mut flight.as_slice()).unwrap();
+            server.process_new_packets().unwrap();
+
+            let mut flight = Vec::new();
+            server.write_tls(&mut flight).unwrap();
+            client.read_tls(&mut flight.as_slice()).unwrap();
+            client.process_new_packets().unwrap();
+        }
+        client.peer_certificates().unwrap()[0].clone().into_owned()
+    }
+
+    #[test]
+    fn rotated_server_certificates_are_reloaded() {
+        let ca = issue("aggregator-ca", None);
+        let (first, first_key) = issue("localhost", Some(&ca));
+        let (second, second_key) = issue("localhost", Some(&ca));
+
+        let created = SystemTime::now();
+        let cert_path = write("server-cert.pem", &first.pem(), created);
+        let key_path = write("server-key.pem", &first_key.serialize_pem(), created);
+        let tls = ServerTls {
+            rustls: RustlsConfig::from_config(Arc::new(
+                server_config(&cert_path, &key_path).unwrap(),
+            )),
+            modified: Mutex::new(modified(&[&cert_path, &key_path])),
+            reload_interval: Duration::from_secs(1),
+            cert_path,
+            key_path,
+        };
+        assert_eq!(handshake(&tls, &ca.0), *first.der());
+
+        // Unchanged files keep the served configuration
+        let served = tls.rustls_config().get_inner();
+        tls.reload_if_changed().unwrap();
+        assert!(Arc::ptr_eq(&served, &tls.rustls_config().get_inner()));
+
+        let rotated = created + Duration::from_secs(60);
+        write("server-cert.pem", &second.pem(), rotated);
+        write("server-key.pem", &second_key.serialize_pem(), rotated);
+        tls.reload_if_changed().unwrap();
+        assert_eq!(handshake(&tls, &ca.0), *second.der());
+
+        fs::remove_file(&tls.cert_path).unwrap();
+        fs::remove_file(&tls.key_path).unwrap();
+    }
+
+    #[test]
+    fn rotated_client_certificates_rebuild_operator_clients() {
+        let ca = issue("operator-ca", None);
+        let (first, first_key) = issue("aggregator", Some(&ca));
+        let (second, second_key) = issue("aggregator", Some(&ca));
+
+        let created = SystemTime::now();
+        let tls = Arc::new(ClientTls {
+            cert_path: Some(write("client-cert.pem", &first.pem(), created)),
+            key_path: Some(write("client-key.pem", &first_key.serialize_pem(), created)),
+            ca_path: Some(write("operator-ca.pem", &ca.0.pem(), created)),
+            identity: RwLock::new(None),
+            roots: RwLock::new(Vec::new()),
+            modified: Mutex::new(Vec::new()),
+            generation: AtomicU64::new(0),
+            reload_interval: Duration::from_secs(1),
+        });
+        tls.reload_if_changed().unwrap();
+        assert_eq!(tls.generation(), 1);
+        assert!(tls.identity.read().unwrap().is_some());
+        assert_eq!(tls.roots.read().unwrap().len(), 1);
+        let client = OperatorClient::new(tls.clone(), |builder| builder).unwrap();
+        assert_eq!(client.client.read().unwrap().0, 1);
+
+        tls.reload_if_changed().unwrap();
+        assert_eq!(tls.generation(), 1);
+
+        let rotated = created + Duration::from_secs(60);
+        write("client-cert.pem", &second.pem(), rotated);
+        write("client-key.pem", &second_key.serialize_pem(), rotated);
+        tls.reload_if_changed().unwrap();
+        assert_eq!(tls.generation(), 2);
+        client.get();
+        assert_eq!(client.client.read().unwrap().0, 2);
+
+        for path in tls.paths() {
+            fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -1,32 +1,199 @@
+use std::{future::Future, sync::Arc, time::Duration};
+
+use alloy::signers::SignerSync;
 use axum::{
+    extract::State,
     http::StatusCode,
     response::{IntoResponse, Response},
     Json,
 };
+use ethers::{providers::Middleware, types::SyncingStatus};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::{
+    operator::{AppState, BEACON_API_URL},
+    register::RegistrationService,
+};
+
+/// Time each readiness check may take before it fails.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum Status {
     Ok,
     Warn,
     Fail,
 }
 
+/// Outcome of one dependency check.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Check {
+    pub name: String,
+    pub status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl Check {
+    fn new(name: &str, status: Status, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            detail: Some(detail.into()),
+        }
+    }
+
+    fn ok(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            status: Status::Ok,
+            detail: None,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct HealthCheck {
     status: Status,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    checks: Vec<Check>,
+}
+
+impl HealthCheck {
+    /// Status of the worst check.
+    pub fn from_checks(checks: Vec<Check>) -> Self {
+        let status = checks
+            .iter()
+            .map(|check| check.status)
+            .max()
+            .unwrap_or(Status::Ok);
+        Self { status, checks }
+    }
 }
 
 impl IntoResponse for HealthCheck {
     fn into_response(self) -> Response {
         let code = match self.status {
             Status::Ok | Status::Warn => StatusCode::OK,
-            Status::Fail => StatusCode::INTERNAL_SERVER_ERROR,
+            Status::Fail => StatusCode::SERVICE_UNAVAILABLE,
         };
         (code, Json(self)).into_response()
     }
 }
 
+/// Liveness: the process is up and serving requests.
 pub async fn health_check() -> HealthCheck {
-    HealthCheck { status: Status::Ok }
+    HealthCheck {
+        status: Status::Ok,
+        checks: Vec::new(),
+    }
+}
+
+/// Dependencies the operator needs to answer tasks.
+pub struct Readiness {
+    state: AppState,
+    registration: Arc<RegistrationService>,
+}
+
+impl Readiness {
+    pub fn new(state: AppState, registration: Arc<RegistrationService>) -> Self {
+        Self {
+            state,
+            registration,
+        }
+    }
+
+    async fn check_rpc(&self) -> Check {
+        let name = "execution_rpc";
+        match self.state.provider.syncing().await {
+            Ok(SyncingStatus::IsFalse) => Check::ok(name),
+            Ok(SyncingStatus::IsSyncing(progress)) => Check::new(
+                name,
+                Status::Warn,
+                format!(
+                    "Syncing, at block {} of {}",
+                    progress.current_block, progress.highest_block
+                ),
+            ),
+            Err(e) => Check::new(name, Status::Fail, e.to_string()),
+        }
+    }
+
+    async fn check_beacon_api(&self) -> Check {
+        let name = "beacon_api";
+        match self.state.client.get(BEACON_API_URL).send().await {
+            Ok(response) if response.status().is_server_error() => Check::new(
+                name,
+                Status::Fail,
+                format!("Answered {}", response.status()),
+            ),
+            Ok(_) => Check::ok(name),
+            Err(e) => Check::new(name, Status::Fail, e.to_string()),
+        }
+    }
+
+    fn check_signer(&self) -> Check {
+        let name = "signer";
+        match self
+            .registration
+            .signer()
+            .sign_message_sync(b"readiness check")
+        {
+            Ok(_) => Check::ok(name),
+            Err(e) => Check::new(name, Status::Fail, e.to_string()),
+        }
+    }
+
+    // Not being registered in the DSS means no tasks are routed here; a missing
+    // aggregator registration is redone on the next heartbeat
+    async fn check_registration(&self) -> Check {
+        let name = "registration";
+        let (in_dss, with_aggregator) = tokio::join!(
+            self.registration.is_registered_in_dss(),
+            self.registration.is_registered_with_aggregator(),
+        );
+        match (in_dss, with_aggregator) {
+            (Err(e), _) => Check::new(
+                name,
+                Status::Fail,
+                format!("Could not read the DSS registration: {e}"),
+            ),
+            (Ok(false), _) => Check::new(name, Status::Fail, "Not registered in the DSS"),
+            (Ok(true), Err(e)) => Check::new(
+                name,
+                Status::Warn,
+                format!("Could not reach the aggregator: {e}"),
+            ),
+            (Ok(true), Ok(false)) => {
+                Check::new(name, Status::Warn, "Not registered with the aggregator")
+            }
+            (Ok(true), Ok(true)) => Check::ok(name),
+        }
+    }
+}
+
+async fn within_timeout(name: &str, check: impl Future<Output = Check>) -> Check {
+    match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+        Ok(check) => check,
+        Err(_) => Check::new(
+            name,
+            Status::Fail,
+            format!("No answer within {CHECK_TIMEOUT:?}"),
+        ),
+    }
+}
+
+/// Readiness: whether the operator can answer tasks.
+pub async fn readiness_check(State(readiness): State<Arc<Readiness>>) -> HealthCheck {
+    let (rpc, beacon_api, registration) = tokio::join!(
+        within_timeout("execution_rpc", readiness.check_rpc()),
+        within_timeout("beacon_api", readiness.check_beacon_api()),
+        within_timeout("registration", readiness.check_registration()),
+    );
+    HealthCheck::from_checks(vec![
+        rpc,
+        beacon_api,
+        readiness.check_signer(),
+        registration,
+    ])
 }
@@ -1,29 +1,106 @@
 use alloy::{primitives::Address, signers::local::PrivateKeySigner};
 use axum::{routing::get, Router};
-use serde::Deserialize;
-use std::{net::IpAddr, str::FromStr};
+use serde::{Deserialize, Serialize};
+use register::RegistrationService;
+use std::{net::IpAddr, str::FromStr, sync::Arc};
 use thiserror::Error;
 use url::Url;
 
 pub mod contract;
 pub mod error;
 pub mod health;
+pub mod limits;
+pub mod metrics;
 pub mod operator;
 pub mod register;
+pub mod settings;
+pub mod telemetry;
+pub mod tls;
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct Config {
     pub host: IpAddr,
     pub port: u16,
     pub rpc_url: Url,
-    #[serde(deserialize_with = "deserialize_private_key")]
+    #[serde(
+        deserialize_with = "deserialize_private_key",
+        serialize_with = "settings::redact"
+    )]
     pub private_key: alloy::signers::local::PrivateKeySigner,
     pub domain_url: Url,
     pub aggregator_url: Url,
-    pub square_number_dss_address: Address,
+    /// TxnVerifier DSS the operator registers with
      pub txn_verifier_address:Address,
     pub core_address: Address,
     pub heartbeat: u64,
+    /// OTLP gRPC endpoint spans are exported to, e.g. `http://localhost:4317`
+    pub otlp_endpoint: Option<String>,
+    #[serde(default = "default_otel_service_name")]
+    pub otel_service_name: String,
+    /// `text` or `json`
+    #[serde(default)]
+    pub log_format: telemetry::LogFormat,
+    /// Window of the rate limits, in milliseconds
+    #[serde(default = "default_common_rate_limit_window_ms")]
+    pub common_rate_limit_window_ms: u64,
+    /// Requests a client IP may make to any route per window
+    #[serde(default = "default_common_rate_limit_max_requests")]
+    pub common_rate_limit_max_requests: u32,
+    /// Requests a client IP may make to `POST /operator/verify` and
+    /// `POST /operator/square` together per window (defaults to the common
+    /// limit)
+    pub verify_rate_limit_max_requests: Option<u32>,
+    /// Origins allowed to call the API from a browser, `*` for any; no CORS
+    /// headers are sent when empty
+    #[serde(default)]
+    pub cors_origin: Vec<String>,
+    /// Largest accepted request body, in bytes
+    #[serde(default = "default_max_body_bytes")]
+    pub max_body_bytes: usize,
+    /// Time a request may take before it is answered with 408, in milliseconds
+    #[serde(default = "default_request_timeout_ms")]
+    pub request_timeout_ms: u64,
+    /// Certificate chain and key the HTTP server is served with over TLS;
+    /// plain HTTP when unset. `DOMAIN_URL` should then use `https`
+    pub tls_cert_path: Option<String>,
+    pub tls_key_path: Option<String>,
+    /// CA bundle that must have issued the aggregator's client certificate;
+    /// enables mutual TLS
+    pub tls_client_ca_path: Option<String>,
+    /// SHA-256 fingerprints of the accepted aggregator client certificates;
+    /// enables mutual TLS
+    #[serde(default)]
+    pub tls_allowed_client_fingerprints: Vec<String>,
+    /// CA bundle the aggregator's certificate is verified against, on top of
+    /// the system roots
+    pub aggregator_ca_path: Option<String>,
+    /// Interval between checks for rotated certificates, in seconds
+    #[serde(default = "default_tls_reload_interval_secs")]
+    pub tls_reload_interval_secs: u64,
+}
+
+fn default_otel_service_name() -> String {
+    "operator".to_string()
+}
+
+fn default_common_rate_limit_window_ms() -> u64 {
+    1000
+}
+
+fn default_common_rate_limit_max_requests() -> u32 {
+    100
+}
+
+fn default_max_body_bytes() -> usize {
+    16 * 1024
+}
+
+fn default_request_timeout_ms() -> u64 {
+    10000
+}
+
+fn default_tls_reload_interval_secs() -> u64 {
+    30
 }
 
 fn deserialize_private_key<'de, D>(deserializer: D) -> Result<PrivateKeySigner, D::Error>
@@ -31,7 +108,8 @@ where
     D: serde::Deserializer<'de>,
 {
     let s = String::deserialize(deserializer)?;
-    PrivateKeySigner::from_str(&s).map_err(serde::de::Error::custom)
+    PrivateKeySigner::from_str(&s)
+        .map_err(|e| serde::de::Error::custom(format!("invalid private_key: {e}")))
 }
 
 #[derive(Debug, Deserialize)]
@@ -77,8 +155,22 @@ pub enum TaskError {
     ProposerLookupError(String),
 }
 
-pub fn routes(wallet: PrivateKeySigner) -> Router {
+pub fn routes(
+    wallet: PrivateKeySigner,
+    registration: Arc<RegistrationService>,
+    limits: &limits::HttpLimits,
+) -> Router {
+    let state =
+        operator::AppState::new(operator::EXECUTION_RPC_URL).expect("Failed to create app state");
+    let readiness = Arc::new(health::Readiness::new(state.clone(), registration));
+
     Router::new()
         .route("/health", get(health::health_check))
-        .nest("/operator", operator::operator_router(wallet))
+        .route("/health/live", get(health::health_check))
+        .route(
+            "/health/ready",
+            get(health::readiness_check).with_state(readiness),
+        )
+        .route("/metrics", get(metrics::metrics_handler))
+        .nest("/operator", operator::operator_router(wallet, state, limits))
 }
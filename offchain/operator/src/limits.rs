@@ -0,0 +1,108 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    extract::DefaultBodyLimit,
+    http::{header, HeaderName, HeaderValue, Method},
+    Router,
+};
+use eyre::{eyre, Result};
+use governor::middleware::NoOpMiddleware;
+use tower_governor::{
+    governor::{GovernorConfig, GovernorConfigBuilder},
+    key_extractor::PeerIpKeyExtractor,
+    GovernorLayer,
+};
+use tower_http::{
+    cors::{AllowOrigin, Any, CorsLayer},
+    timeout::TimeoutLayer,
+};
+
+use crate::Config;
+
+type Governor = Arc<GovernorConfig<PeerIpKeyExtractor, NoOpMiddleware>>;
+
+/// Rate limits, CORS policy, body size limit and request timeout of the HTTP
+/// routes, built from the configuration
+pub struct HttpLimits {
+    common: Governor,
+    verify: Governor,
+    cors: Option<CorsLayer>,
+    max_body_bytes: usize,
+    request_timeout: Duration,
+}
+
+impl HttpLimits {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let window = config.common_rate_limit_window_ms;
+        let common = config.common_rate_limit_max_requests;
+        Ok(Self {
+            common: governor(window, common)?,
+            verify: governor(
+                window,
+                config.verify_rate_limit_max_requests.unwrap_or(common),
+            )?,
+            cors: cors_layer(&config.cors_origin)?,
+            max_body_bytes: config.max_body_bytes,
+            request_timeout: Duration::from_millis(config.request_timeout_ms),
+        })
+    }
+
+    /// Rate limit of the task routes `POST /operator/verify` and
+    /// `POST /operator/square`, per client IP and shared between them
+    pub fn verify_layer(&self) -> GovernorLayer<PeerIpKeyExtractor, NoOpMiddleware> {
+        GovernorLayer {
+            config: self.verify.clone(),
+        }
+    }
+
+    /// Applies the limits shared by every route
+    pub fn apply(&self, router: Router) -> Router {
+        let router = router
+            .layer(DefaultBodyLimit::max(self.max_body_bytes))
+            .layer(TimeoutLayer::new(self.request_timeout))
+            .layer(GovernorLayer {
+                config: self.common.clone(),
+            });
+        match &self.cors {
+            Some(cors) => router.layer(cors.clone()),
+            None => router,
+        }
+    }
+}
+
+/// Allows `max_requests` per `window_ms` with the whole allowance available as
+/// a burst
+fn governor(window_ms: u64, max_requests: u32) -> Result<Governor> {
+    let period = (window_ms / u64::from(max_requests.max(1))).max(1);
+    GovernorConfigBuilder::default()
+        .per_millisecond(period)
+        .burst_size(max_requests)
+        .finish()
+        .map(Arc::new)
+        .ok_or_else(|| eyre!("Rate limit of {max_requests} requests per {window_ms}ms is invalid"))
+}
+
+/// No layer when no origin is configured so browsers keep the same-origin
+/// policy; `*` allows any origin.
+fn cors_layer(origins: &[String]) -> Result<Option<CorsLayer>> {
+    if origins.is_empty() {
+        return Ok(None);
+    }
+    let allow_origin = if origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::from(Any)
+    } else {
+        let origins = origins
+            .iter()
+            .map(|origin| {
+                HeaderValue::from_str(origin).map_err(|_| eyre!("Invalid CORS origin {origin}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        AllowOrigin::list(origins)
+    };
+    Ok(Some(
+        CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_methods([Method::GET, Method::POST])
+            .allow_headers([header::CONTENT_TYPE, HeaderName::from_static("traceparent")]),
+    ))
+}
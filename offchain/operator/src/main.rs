@@ -1,51 +1,72 @@
-use square_number_dss_operator::register::RegistrationService;
-use std::{net::SocketAddr, sync::Arc};
+use clap::Parser;
+use square_number_dss_operator::{
+    limits::HttpLimits, register::RegistrationService, settings::Cli, telemetry, tls::ServerTls,
+};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 use tokio::net::TcpListener;
 use tokio::signal;
-use tower::ServiceBuilder;
-use tower_governor::{governor::GovernorConfig, GovernorLayer};
 use tower_http::trace::{self, TraceLayer};
 use tracing::{warn, Level};
-use tracing_subscriber::EnvFilter;
 
 #[tokio::main]
 async fn main() -> eyre::Result<()> {
     if dotenvy::dotenv().is_err() {
         warn!("No .env file found");
     }
-    let config = envy::from_env::<square_number_dss_operator::Config>()?;
-    tracing_subscriber::fmt()
-        .with_target(false)
-        .with_env_filter(EnvFilter::from_default_env())
-        .compact()
-        .init();
+    let cli = Cli::parse();
+    let config = cli.load()?;
+    if cli.print_config {
+        print!("{}", config.redacted()?);
+        return Ok(());
+    }
+    telemetry::init(&config)?;
+
+    let limits = HttpLimits::from_config(&config)?;
+    let address = SocketAddr::new(config.host, config.port);
+    let server_tls = ServerTls::from_config(&config)?.map(Arc::new);
 
-    let governor_config = Arc::new(GovernorConfig::default());
-    let aggregator_app = square_number_dss_operator::routes(config.private_key.clone());
-    let app = aggregator_app
+    let wallet = config.private_key.clone();
+    let registration_service = Arc::new(RegistrationService::new(config)?);
+    let aggregator_app =
+        square_number_dss_operator::routes(wallet, registration_service.clone(), &limits);
+    let app = limits
+        .apply(aggregator_app)
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(trace::DefaultMakeSpan::new().level(Level::INFO))
                 .on_request(trace::DefaultOnRequest::new().level(Level::INFO))
                 .on_response(trace::DefaultOnResponse::new().level(Level::INFO))
                 .on_failure(trace::DefaultOnFailure::new().level(Level::ERROR)),
-        )
-        .layer(ServiceBuilder::new().layer(GovernorLayer {
-            config: governor_config.clone(),
-        }));
+        );
 
-    let listener = TcpListener::bind((config.host, config.port)).await?;
-
-    let registration_service = RegistrationService::new(config)?;
     tokio::spawn(async move { registration_service.start().await });
 
-    axum::serve(
-        listener,
-        app.into_make_service_with_connect_info::<SocketAddr>(),
-    )
-    .with_graceful_shutdown(shutdown_signal())
-    .await?;
+    match server_tls {
+        Some(tls) => {
+            tls.clone().start().await;
+            let handle = axum_server::Handle::new();
+            let shutdown = handle.clone();
+            tokio::spawn(async move {
+                shutdown_signal().await;
+                shutdown.graceful_shutdown(Some(Duration::from_secs(10)));
+            });
+            axum_server::bind_rustls(address, tls.rustls_config())
+                .handle(handle)
+                .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                .await?;
+        }
+        None => {
+            let listener = TcpListener::bind(address).await?;
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(shutdown_signal())
+            .await?;
+        }
+    }
 
+    telemetry::shutdown();
     Ok(())
 }
 
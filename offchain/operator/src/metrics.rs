@@ -0,0 +1,85 @@
+use std::sync::OnceLock;
+
+use axum::{
+    http::header,
+    response::{IntoResponse, Response},
+};
+use prometheus::{Histogram, HistogramOpts, IntCounterVec, Opts, Registry, TextEncoder};
+
+use crate::error::AppError;
+
+/// Prometheus metrics of the operator, served on `GET /metrics`.
+pub struct Metrics {
+    registry: Registry,
+    /// Verification requests, by outcome
+    pub verifications: IntCounterVec,
+    pub verification_latency: Histogram,
+    /// Failed RPC calls, by method
+    pub rpc_errors: IntCounterVec,
+    /// Failed beaconcha.in proposer lookups, by reason
+    pub beacon_lookup_failures: IntCounterVec,
+}
+
+impl Metrics {
+    fn new() -> prometheus::Result<Self> {
+        let registry = Registry::new_custom(Some("operator".to_string()), None)?;
+
+        let verifications = IntCounterVec::new(
+            Opts::new("verifications_total", "Verification requests, by outcome"),
+            &["outcome"],
+        )?;
+        let verification_latency = Histogram::with_opts(HistogramOpts::new(
+            "verification_seconds",
+            "Time to answer a verification request",
+        ))?;
+        let rpc_errors = IntCounterVec::new(
+            Opts::new("rpc_errors_total", "Failed RPC calls, by method"),
+            &["method"],
+        )?;
+        let beacon_lookup_failures = IntCounterVec::new(
+            Opts::new(
+                "beacon_lookup_failures_total",
+                "Failed block proposer lookups, by reason",
+            ),
+            &["reason"],
+        )?;
+
+        registry.register(Box::new(verifications.clone()))?;
+        registry.register(Box::new(verification_latency.clone()))?;
+        registry.register(Box::new(rpc_errors.clone()))?;
+        registry.register(Box::new(beacon_lookup_failures.clone()))?;
+
+        Ok(Self {
+            registry,
+            verifications,
+            verification_latency,
+            rpc_errors,
+            beacon_lookup_failures,
+        })
+    }
+
+    pub fn rpc_error(&self, method: &str) {
+        self.rpc_errors.with_label_values(&[method]).inc();
+    }
+
+    pub fn beacon_lookup_failure(&self, reason: &str) {
+        self.beacon_lookup_failures
+            .with_label_values(&[reason])
+            .inc();
+    }
+
+    pub fn render(&self) -> prometheus::Result<String> {
+        TextEncoder::new().encode_to_string(&self.registry.gather())
+    }
+}
+
+/// Metrics of this process.
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(|| Metrics::new().expect("metrics are registered once"))
+}
+
+pub async fn metrics_handler() -> Result<Response, AppError> {
+    let body = metrics().render().map_err(|e| eyre::eyre!(e))?;
+    Ok(([(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)], body).into_response())
+}
@@ -1,5 +1,6 @@
 use axum::{
     extract::State,
+    http::HeaderMap,
     routing::{get, post},
     Json, Router,
 };
@@ -12,7 +13,15 @@ use ethers::{
 };
 use crate::PrivateKeySigner;
 use k256::ecdsa::SigningKey;
-use tracing::info;
+use tracing::{info, info_span, instrument, Instrument};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::{health, limits::HttpLimits, metrics::metrics, telemetry};
+
+/// Execution RPC the verified transactions are looked up on.
+pub const EXECUTION_RPC_URL: &str = "https://eth.llamarpc.com";
+/// beaconcha.in API the block proposers are looked up on.
+pub const BEACON_API_URL: &str = "https://beaconcha.in/api/v1";
 
 // Request and response types
 #[derive(Debug, Serialize, Deserialize)]
@@ -28,6 +37,17 @@ pub struct VerificationResponse {
     pub block_number: String,
 }
 
+/// Task of the SquareNumber DSS
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SquareRequest {
+    pub value: U256,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SquareResponse {
+    pub response: U256,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct BeaconApiResponse {
     status: String,
@@ -54,8 +74,8 @@ struct PosConsensus {
 // Application state
 #[derive(Clone)]
 pub struct AppState {
-    provider: Arc<Provider<Http>>,
-    client: Arc<reqwest::Client>,
+    pub(crate) provider: Arc<Provider<Http>>,
+    pub(crate) client: Arc<reqwest::Client>,
 }
 
 impl AppState {
@@ -71,6 +91,7 @@ impl AppState {
 }
 
 // Transaction verification functions
+#[instrument(skip(provider), err)]
 async fn is_transaction_in_block(
     provider: &Provider<Http>,
     tx_hash: &str,
@@ -80,7 +101,8 @@ async fn is_transaction_in_block(
     
     let tx = provider
         .get_transaction(tx_hash)
-        .await?;
+        .await
+        .inspect_err(|_| metrics().rpc_error("eth_getTransactionByHash"))?;
         info!("tx     {:?}",tx);
         info!("block_number     {:?}",block_number);
         info!("tx_hash     {:?}",tx_hash);
@@ -98,28 +120,31 @@ async fn is_transaction_in_block(
     }
 }
 
+#[instrument(skip(client), err)]
 async fn get_block_proposer(
     client: &reqwest::Client,
     block_number: &str,
 ) -> Result<Option<u64>> {
-    let url = format!(
-        "https://beaconcha.in/api/v1/execution/block/{}",
-        block_number
-    );
+    let url = format!("{}/execution/block/{}", BEACON_API_URL, block_number);
 
     info!("url {:?}", url);
 
     let response = client
         .get(&url)
         .send()
-        .await?;
+        .await
+        .inspect_err(|_| metrics().beacon_lookup_failure("request"))?;
 
     if !response.status().is_success() {
         info!("Failed to get response from beaconcha.in: {}", response.status());
+        metrics().beacon_lookup_failure("status");
         return Ok(None);
     }
 
-    let response_text = response.text().await?;
+    let response_text = response
+        .text()
+        .await
+        .inspect_err(|_| metrics().beacon_lookup_failure("request"))?;
     info!("Response text: {}", response_text);
 
     match serde_json::from_str::<BeaconApiResponse>(&response_text) {
@@ -135,6 +160,7 @@ async fn get_block_proposer(
         }
         Err(e) => {
             info!("Failed to parse beacon response: {}", e);
+            metrics().beacon_lookup_failure("parse");
             Ok(None)
         }
     }
@@ -142,9 +168,32 @@ async fn get_block_proposer(
 
 
 // API handlers
+// Continues the aggregator's trace when the request carries one
 async fn verify_transaction(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Json(request): Json<VerificationRequest>,
+) -> Result<Json<VerificationResponse>, String> {
+    let _timer = metrics().verification_latency.start_timer();
+    let span = info_span!(
+        "verify_transaction",
+        transaction_hash = %request.transaction_hash,
+        block_number = %request.block_number
+    );
+    span.set_parent(telemetry::remote_context(&headers));
+    let response = answer_verification(&state, request).instrument(span).await;
+    let outcome = match &response {
+        Ok(Json(response)) if response.is_included => "included",
+        Ok(_) => "not_included",
+        Err(_) => "error",
+    };
+    metrics().verifications.with_label_values(&[outcome]).inc();
+    response
+}
+
+async fn answer_verification(
+    state: &AppState,
+    request: VerificationRequest,
 ) -> Result<Json<VerificationResponse>, String> {
     let is_included = is_transaction_in_block(
         &state.provider,
@@ -178,21 +227,33 @@ async fn verify_transaction(
     }))
 }
 
-// Health check endpoint
-async fn health_check() -> &'static str {
-    "OK"
+async fn square_number(
+    headers: HeaderMap,
+    Json(request): Json<SquareRequest>,
+) -> Result<Json<SquareResponse>, String> {
+    let span = info_span!("square_number", value = %request.value);
+    span.set_parent(telemetry::remote_context(&headers));
+    let _entered = span.enter();
+    let response = request
+        .value
+        .checked_mul(request.value)
+        .ok_or_else(|| format!("The square of {} overflows uint256", request.value))?;
+    info!("Squared {} to {}", request.value, response);
+    Ok(Json(SquareResponse { response }))
 }
 
-
-
 // Router setup
-pub fn operator_router(wallet: PrivateKeySigner) -> Router {
-    let state = AppState::new("https://eth.llamarpc.com")
-        .expect("Failed to create app state");
-        
+pub fn operator_router(wallet: PrivateKeySigner, state: AppState, limits: &HttpLimits) -> Router {
     Router::new()
-        .route("/verify", post(verify_transaction))
-        .route("/health", get(health_check))
+        .route(
+            "/verify",
+            post(verify_transaction).layer(limits.verify_layer()),
+        )
+        .route(
+            "/square",
+            post(square_number).layer(limits.verify_layer()),
+        )
+        .route("/health", get(health::health_check))
         .with_state(state)
 }
 
@@ -1,14 +1,16 @@
-use crate::{contract::TxnVerifier::TxnVerifierInstance, Config};
+use crate::{contract::TxnVerifier::TxnVerifierInstance, metrics::metrics, Config};
 use alloy::{
     network::{Ethereum, EthereumWallet},
-    primitives::Address,
+    primitives::{Address, Bytes},
     providers::{
         fillers::{FillProvider, JoinFill, RecommendedFiller, WalletFiller},
-        ProviderBuilder, ReqwestProvider,
+        Provider, ProviderBuilder, ReqwestProvider,
     },
     rpc::types::TransactionReceipt,
+    signers::{local::PrivateKeySigner, SignerSync},
     transports::http::{reqwest, ReqwestTransport},
 };
+use chrono::Utc;
 use eyre::Result;
 use karak_rs::contracts::Core::CoreInstance;
 use serde::Serialize;
@@ -23,6 +25,29 @@ struct AddressPayload {
     url: Url,
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RegistrationPayload {
+    public_key: Address,
+    url: Url,
+    timestamp: u64,
+    signature: Bytes,
+}
+
+/// Message the operator signs (EIP-191) to announce its endpoint; must match
+/// the aggregator's `registration_message`.
+fn registration_message(
+    public_key: &Address,
+    url: &Url,
+    chain_id: u64,
+    aggregator: &Address,
+    timestamp: u64,
+) -> String {
+    format!(
+        "Register operator {public_key} at {url} with aggregator {aggregator} on chain {chain_id} at {timestamp}"
+    )
+}
+
 pub type RecommendedProvider = FillProvider<
     JoinFill<RecommendedFiller, WalletFiller<EthereumWallet>>,
     ReqwestProvider,
@@ -34,6 +59,7 @@ pub struct RegistrationService {
     dss_instance: TxnVerifierInstance<ReqwestTransport, RecommendedProvider>,
     core_instance: CoreInstance<ReqwestTransport, RecommendedProvider>,
     operator_address: Address,
+    signer: PrivateKeySigner,
     aggregator_url: Url,
     domain_url: Url,
     reqwest_client: reqwest::Client,
@@ -50,13 +76,21 @@ impl RegistrationService {
         TxnVerifierInstance::new(config.txn_verifier_address, provider.clone());
         let core_instance = CoreInstance::new(config.core_address, provider);
         let heartbeat_interval = Duration::from_millis(config.heartbeat);
+        let mut reqwest_client = reqwest::Client::builder();
+        if let Some(path) = &config.aggregator_ca_path {
+            let pem = std::fs::read(path).map_err(|e| eyre::eyre!("Failed to read {path}: {e}"))?;
+            for cert in reqwest::Certificate::from_pem_bundle(&pem)? {
+                reqwest_client = reqwest_client.add_root_certificate(cert);
+            }
+        }
         Ok(Self {
             dss_instance,
             core_instance,
             operator_address: config.private_key.address(),
+            signer: config.private_key,
             aggregator_url: config.aggregator_url,
             domain_url: config.domain_url,
-            reqwest_client: reqwest::Client::new(),
+            reqwest_client: reqwest_client.build()?,
             heartbeat_interval,
         })
     }
@@ -117,12 +151,17 @@ impl RegistrationService {
         Ok(())
     }
 
-    async fn is_registered_in_dss(&self) -> Result<bool> {
+    pub(crate) fn signer(&self) -> &PrivateKeySigner {
+        &self.signer
+    }
+
+    pub(crate) async fn is_registered_in_dss(&self) -> Result<bool> {
         Ok(self
             .dss_instance
             .isOperatorRegistered(self.operator_address)
             .call()
-            .await?
+            .await
+            .inspect_err(|_| metrics().rpc_error("isOperatorRegistered"))?
             ._0)
     }
 
@@ -131,14 +170,15 @@ impl RegistrationService {
             .core_instance
             .registerOperatorToDSS(*self.dss_instance.address(), "0x".into())
             .send()
-            .await?
+            .await
+            .inspect_err(|_| metrics().rpc_error("registerOperatorToDSS"))?
             .get_receipt()
             .await?;
 
         Ok(receipt)
     }
 
-    async fn is_registered_with_aggregator(&self) -> Result<bool> {
+    pub(crate) async fn is_registered_with_aggregator(&self) -> Result<bool> {
         let url = self
             .aggregator_url
             .join("aggregator/isOperatorRegistered")?;
@@ -159,14 +199,47 @@ impl RegistrationService {
             .await?)
     }
 
+    // Announce the endpoint with a signature binding it to the chain and to the
+    // aggregator account the DSS trusts, timestamped so it cannot be replayed
     pub async fn register_operator_with_aggregator(&self) -> Result<()> {
         let url = self.aggregator_url.join("aggregator/registerOperator")?;
-        let operator = AddressPayload {
+        let chain_id = self
+            .dss_instance
+            .provider()
+            .get_chain_id()
+            .await
+            .inspect_err(|_| metrics().rpc_error("eth_chainId"))?;
+        let aggregator = self
+            .dss_instance
+            .aggregator()
+            .call()
+            .await
+            .inspect_err(|_| metrics().rpc_error("aggregator"))?
+            ._0;
+        let timestamp = Utc::now().timestamp().max(0) as u64;
+
+        let message = registration_message(
+            &self.operator_address,
+            &self.domain_url,
+            chain_id,
+            &aggregator,
+            timestamp,
+        );
+        let signature = self.signer.sign_message_sync(message.as_bytes())?;
+
+        let payload = RegistrationPayload {
             public_key: self.operator_address,
             url: self.domain_url.clone(),
+            timestamp,
+            signature: Bytes::from(signature.as_bytes().to_vec()),
         };
 
-        self.reqwest_client.post(url).json(&operator).send().await?;
+        self.reqwest_client
+            .post(url)
+            .json(&payload)
+            .send()
+            .await?
+            .error_for_status()?;
 
         Ok(())
     }
@@ -0,0 +1,226 @@
+use std::{fmt, path::PathBuf, str::FromStr};
+
+use alloy::primitives::{Address, B256};
+use clap::Parser;
+use config::{Environment, File, FileFormat};
+use serde::Serializer;
+use thiserror::Error;
+use url::Url;
+
+use crate::Config;
+
+/// Settings holding lists, given comma separated in the environment and in
+/// `--set`.
+const LIST_KEYS: [&str; 2] = ["cors_origin", "tls_allowed_client_fingerprints"];
+
+/// Settings still accepted but no longer read, with the replacement.
+const DEPRECATED_KEYS: [(&str, &str); 1] = [("square_number_dss_address", "txn_verifier_address")];
+
+/// Command line of the operator. Settings are read from the config file,
+/// then the environment, then `--set`, each overriding the previous one.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// TOML file of settings keyed by their snake_case name
+    #[arg(long, env = "CONFIG_FILE")]
+    pub config: Option<PathBuf>,
+    /// Overrides a setting, e.g. `--set port=3001`; may be repeated
+    #[arg(long = "set", value_name = "KEY=VALUE", value_parser = parse_setting)]
+    pub settings: Vec<(String, String)>,
+    /// Prints the resolved settings with secrets redacted, then exits
+    #[arg(long)]
+    pub print_config: bool,
+}
+
+fn parse_setting(setting: &str) -> Result<(String, String), String> {
+    let (key, value) = setting
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got `{setting}`"))?;
+    Ok((
+        key.trim().to_lowercase().replace('-', "_"),
+        value.to_string(),
+    ))
+}
+
+#[derive(Debug, Error)]
+pub enum SettingsError {
+    #[error("Invalid configuration: {0}")]
+    Source(#[from] config::ConfigError),
+
+    #[error("Invalid configuration:{}", .0.iter().map(|e| format!("\n  {e}")).collect::<String>())]
+    Invalid(Vec<InvalidSetting>),
+}
+
+#[derive(Debug)]
+pub struct InvalidSetting {
+    pub key: &'static str,
+    pub reason: String,
+}
+
+impl fmt::Display for InvalidSetting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` ({}) {}",
+            self.key,
+            self.key.to_uppercase(),
+            self.reason
+        )
+    }
+}
+
+impl Cli {
+    /// Merges the config file, the environment and `--set`, then validates
+    /// the result.
+    pub fn load(&self) -> Result<Config, SettingsError> {
+        let mut builder = config::Config::builder();
+        if let Some(path) = &self.config {
+            builder = builder.add_source(File::from(path.as_path()).format(FileFormat::Toml));
+        }
+        let mut environment = Environment::default()
+            .ignore_empty(true)
+            .try_parsing(true)
+            .list_separator(",");
+        for key in LIST_KEYS {
+            environment = environment.with_list_parse_key(key);
+        }
+        builder = builder.add_source(environment);
+        for (key, value) in &self.settings {
+            builder = if LIST_KEYS.contains(&key.as_str()) {
+                builder.set_override(key.as_str(), value.split(',').collect::<Vec<_>>())?
+            } else {
+                builder.set_override(key.as_str(), value.as_str())?
+            };
+        }
+
+        let settings = builder.build()?;
+        for (key, replacement) in DEPRECATED_KEYS {
+            if settings.get_string(key).is_ok() {
+                eprintln!(
+                    "warning: `{key}` ({}) is deprecated and ignored, use `{replacement}` ({})",
+                    key.to_uppercase(),
+                    replacement.to_uppercase()
+                );
+            }
+        }
+        let config: Config = settings.try_deserialize()?;
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+impl Config {
+    /// Checks addresses, URLs, files and intervals up front so that a bad
+    /// value stops startup with the offending key.
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        let mut invalid = Vec::new();
+        let mut check = |valid: bool, key: &'static str, reason: &str| {
+            if !valid {
+                invalid.push(InvalidSetting {
+                    key,
+                    reason: reason.to_string(),
+                });
+            }
+        };
+
+        let urls = [
+            ("rpc_url", &self.rpc_url),
+            ("domain_url", &self.domain_url),
+            ("aggregator_url", &self.aggregator_url),
+        ];
+        for (key, url) in urls {
+            check(
+                matches!(url.scheme(), "http" | "https"),
+                key,
+                "must be an http or https URL",
+            );
+        }
+
+        let addresses = [
+            ("txn_verifier_address", self.txn_verifier_address),
+            ("core_address", self.core_address),
+        ];
+        for (key, address) in addresses {
+            check(
+                address != Address::ZERO,
+                key,
+                "must not be the zero address",
+            );
+        }
+
+        let positive = [
+            ("heartbeat", self.heartbeat),
+            (
+                "common_rate_limit_window_ms",
+                self.common_rate_limit_window_ms,
+            ),
+            (
+                "common_rate_limit_max_requests",
+                self.common_rate_limit_max_requests.into(),
+            ),
+            (
+                "verify_rate_limit_max_requests",
+                self.verify_rate_limit_max_requests.map_or(1, u64::from),
+            ),
+            ("max_body_bytes", self.max_body_bytes as u64),
+            ("request_timeout_ms", self.request_timeout_ms),
+            ("tls_reload_interval_secs", self.tls_reload_interval_secs),
+        ];
+        for (key, value) in positive {
+            check(value > 0, key, "must be greater than zero");
+        }
+
+        if let Some(endpoint) = self.otlp_endpoint.as_deref().filter(|e| !e.is_empty()) {
+            check(
+                Url::parse(endpoint).is_ok(),
+                "otlp_endpoint",
+                "must be a URL",
+            );
+        }
+        check(
+            self.cors_origin
+                .iter()
+                .all(|origin| origin == "*" || Url::parse(origin).is_ok()),
+            "cors_origin",
+            "must be `*` or a list of origins such as `https://example.com`",
+        );
+        check(
+            self.tls_allowed_client_fingerprints
+                .iter()
+                .all(|pin| B256::from_str(&pin.replace(':', "")).is_ok()),
+            "tls_allowed_client_fingerprints",
+            "must be a list of hex encoded SHA-256 fingerprints",
+        );
+
+        let files = [
+            ("tls_cert_path", &self.tls_cert_path),
+            ("tls_key_path", &self.tls_key_path),
+            ("tls_client_ca_path", &self.tls_client_ca_path),
+            ("aggregator_ca_path", &self.aggregator_ca_path),
+        ];
+        for (key, path) in files {
+            if let Some(path) = path {
+                check(
+                    std::path::Path::new(path).is_file(),
+                    key,
+                    "must be an existing file",
+                );
+            }
+        }
+
+        if invalid.is_empty() {
+            Ok(())
+        } else {
+            Err(SettingsError::Invalid(invalid))
+        }
+    }
+
+    /// The settings as TOML with secrets redacted, for `--print-config`.
+    pub fn redacted(&self) -> Result<String, toml::ser::Error> {
+        toml::to_string(self)
+    }
+}
+
+pub(crate) fn redact<T, S: Serializer>(_: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str("<redacted>")
+}
@@ -0,0 +1,67 @@
+use axum::http::HeaderMap;
+use eyre::Result;
+use opentelemetry::{global, trace::TracerProvider as _, Context, KeyValue};
+use opentelemetry_http::HeaderExtractor;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{propagation::TraceContextPropagator, runtime, trace, Resource};
+use serde::{Deserialize, Serialize};
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+use crate::Config;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// Install the log subscriber and, when `OTLP_ENDPOINT` is set, export spans
+/// to it. Trace context is read from W3C `traceparent` headers.
+pub fn init(config: &Config) -> Result<()> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let endpoint = config.otlp_endpoint.as_deref().filter(|e| !e.is_empty());
+    let otel_layer = match endpoint {
+        Some(endpoint) => {
+            let provider = opentelemetry_otlp::new_pipeline()
+                .tracing()
+                .with_exporter(
+                    opentelemetry_otlp::new_exporter()
+                        .tonic()
+                        .with_endpoint(endpoint),
+                )
+                .with_trace_config(trace::Config::default().with_resource(Resource::new(vec![
+                    KeyValue::new("service.name", config.otel_service_name.clone()),
+                ])))
+                .install_batch(runtime::Tokio)?;
+            let tracer = provider.tracer("square-number-dss-operator");
+            global::set_tracer_provider(provider);
+            Some(tracing_opentelemetry::layer().with_tracer(tracer))
+        }
+        None => None,
+    };
+
+    let fmt_layer = match config.log_format {
+        LogFormat::Text => fmt::layer().with_target(false).compact().boxed(),
+        LogFormat::Json => fmt::layer().json().with_current_span(true).boxed(),
+    };
+
+    tracing_subscriber::registry()
+        .with(otel_layer)
+        .with(fmt_layer)
+        .with(EnvFilter::from_default_env())
+        .init();
+    Ok(())
+}
+
+/// Flush the spans not exported yet.
+pub fn shutdown() {
+    global::shutdown_tracer_provider();
+}
+
+/// Trace context of an incoming request, to parent its span.
+pub fn remote_context(headers: &HeaderMap) -> Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)))
+}
@@ -0,0 +1,449 @@
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use alloy::primitives::B256;
+use axum_server::tls_rustls::RustlsConfig;
+use eyre::{eyre, Result};
+use rustls::{
+    client::danger::HandshakeSignatureValid,
+    crypto::{ring, verify_tls12_signature, verify_tls13_signature, WebPkiSupportedAlgorithms},
+    pki_types::{CertificateDer, PrivateKeyDer, UnixTime},
+    server::{
+        danger::{ClientCertVerified, ClientCertVerifier},
+        WebPkiClientVerifier,
+    },
+    CertificateError, DigitallySignedStruct, DistinguishedName, RootCertStore, ServerConfig,
+    SignatureScheme,
+};
+use sha2::{Digest, Sha256};
+use tokio::{signal, time};
+use tracing::{error, info};
+
+use crate::Config;
+
+/// Modification times of `paths`, compared to detect rotated certificates.
+fn modified(paths: &[&str]) -> Vec<Option<SystemTime>> {
+    paths
+        .iter()
+        .map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let file = File::open(path).map_err(|e| eyre!("Failed to open {path}: {e}"))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| eyre!("Failed to parse certificates in {path}: {e}"))?;
+    if certs.is_empty() {
+        eyre::bail!("No certificate in {path}");
+    }
+    Ok(certs)
+}
+
+fn load_key(path: &str) -> Result<PrivateKeyDer<'static>> {
+    let file = File::open(path).map_err(|e| eyre!("Failed to open {path}: {e}"))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .map_err(|e| eyre!("Failed to parse private key in {path}: {e}"))?
+        .ok_or_else(|| eyre!("No private key in {path}"))
+}
+
+/// SHA-256 fingerprint of a DER certificate, as printed by
+/// `openssl x509 -noout -fingerprint -sha256`.
+fn fingerprint(cert: &CertificateDer<'_>) -> B256 {
+    B256::from_slice(&Sha256::digest(cert.as_ref()))
+}
+
+/// Certificate the HTTP server is served with, and the aggregator identity
+/// clients must present when mutual TLS is enabled. The files are checked
+/// every `TLS_RELOAD_INTERVAL_SECS` and rotated certificates apply to new
+/// connections without a restart.
+pub struct ServerTls {
+    cert_path: String,
+    key_path: String,
+    client_ca_path: Option<String>,
+    client_pins: Vec<B256>,
+    rustls: RustlsConfig,
+    modified: Mutex<Vec<Option<SystemTime>>>,
+    reload_interval: Duration,
+}
+
+impl ServerTls {
+    /// `None` when `TLS_CERT_PATH` and `TLS_KEY_PATH` are not set.
+    pub fn from_config(config: &Config) -> Result<Option<Self>> {
+        let mutual = config.tls_client_ca_path.is_some()
+            || !config.tls_allowed_client_fingerprints.is_empty();
+        let (cert_path, key_path) = match (&config.tls_cert_path, &config.tls_key_path) {
+            (Some(cert), Some(key)) => (cert.clone(), key.clone()),
+            (None, None) if mutual => {
+                eyre::bail!("Mutual TLS requires TLS_CERT_PATH and TLS_KEY_PATH")
+            }
+            (None, None) => return Ok(None),
+            _ => eyre::bail!("TLS_CERT_PATH and TLS_KEY_PATH must be set together"),
+        };
+        let client_pins = config
+            .tls_allowed_client_fingerprints
+            .iter()
+            .map(|pin| {
+                B256::from_str(&pin.replace(':', ""))
+                    .map_err(|_| eyre!("Invalid certificate fingerprint {pin}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let client_ca_path = config.tls_client_ca_path.clone();
+        let server_config = server_config(
+            &cert_path,
+            &key_path,
+            client_ca_path.as_deref(),
+            &client_pins,
+        )?;
+        let mut tls = Self {
+            cert_path,
+            key_path,
+            client_ca_path,
+            client_pins,
+            rustls: RustlsConfig::from_config(Arc::new(server_config)),
+            modified: Mutex::default(),
+            reload_interval: Duration::from_secs(config.tls_reload_interval_secs),
+        };
+        tls.modified = Mutex::new(modified(&tls.paths()));
+        Ok(Some(tls))
+    }
+
+    fn paths(&self) -> Vec<&str> {
+        [
+            Some(&self.cert_path),
+            Some(&self.key_path),
+            self.client_ca_path.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(String::as_str)
+        .collect()
+    }
+
+    pub fn rustls_config(&self) -> RustlsConfig {
+        self.rustls.clone()
+    }
+
+    pub async fn start(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut interval = time::interval(self.reload_interval);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {
+                        if let Err(e) = self.reload_if_changed() {
+                            error!("Failed to reload the server certificates: {e}");
+                        }
+                    }
+                    _ = signal::ctrl_c() => break,
+                }
+            }
+        });
+    }
+
+    fn reload_if_changed(&self) -> Result<()> {
+        let current = modified(&self.paths());
+        let mut modified = self.modified.lock().map_err(|e| eyre!("{e}"))?;
+        if *modified == current {
+            return Ok(());
+        }
+        let server_config = server_config(
+            &self.cert_path,
+            &self.key_path,
+            self.client_ca_path.as_deref(),
+            &self.client_pins,
+        )?;
+        self.rustls.reload_from_config(Arc::new(server_config));
+        *modified = current;
+        info!("Reloaded the server certificates from {}", self.cert_path);
+        Ok(())
+    }
+}
+
+fn server_config(
+    cert_path: &str,
+    key_path: &str,
+    client_ca_path: Option<&str>,
+    client_pins: &[B256],
+) -> Result<ServerConfig> {
+    let provider = Arc::new(ring::default_provider());
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?;
+    let builder = if client_ca_path.is_none() && client_pins.is_empty() {
+        builder.with_no_client_auth()
+    } else {
+        let ca = match client_ca_path {
+            Some(path) => {
+                let mut roots = RootCertStore::empty();
+                for cert in load_certs(path)? {
+                    roots.add(cert)?;
+                }
+                Some(
+                    WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+                        .build()?,
+                )
+            }
+            None => None,
+        };
+        builder.with_client_cert_verifier(Arc::new(PinnedClientVerifier {
+            ca,
+            pins: client_pins.to_vec(),
+            algorithms: provider.signature_verification_algorithms,
+        }))
+    };
+    let mut server_config =
+        builder.with_single_cert(load_certs(cert_path)?, load_key(key_path)?)?;
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(server_config)
+}
+
+/// Requires a client certificate issued by `TLS_CLIENT_CA_PATH` when set,
+/// and among `TLS_ALLOWED_CLIENT_FINGERPRINTS` when set. A pin without a CA
+/// accepts the pinned certificate alone, self-signed or not.
+#[derive(Debug)]
+struct PinnedClientVerifier {
+    ca: Option<Arc<dyn ClientCertVerifier>>,
+    pins: Vec<B256>,
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl ClientCertVerifier for PinnedClientVerifier {
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        self.ca.as_ref().map_or(&[], |ca| ca.root_hint_subjects())
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        now: UnixTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        if let Some(ca) = &self.ca {
+            ca.verify_client_cert(end_entity, intermediates, now)?;
+        }
+        if !self.pins.is_empty() && !self.pins.contains(&fingerprint(end_entity)) {
+            return Err(rustls::Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure,
+            ));
+        }
+        Ok(ClientCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rcgen::{
+        BasicConstraints, Certificate, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa,
+        KeyPair,
+    };
+    use rustls::{
+        pki_types::{PrivatePkcs8KeyDer, ServerName},
+        ClientConfig, ClientConnection, ServerConnection,
+    };
+
+    use super::*;
+
+    // A certificate for `name`, issued by `issuer` or self-signed
+    fn issue(
+        name: &str,
+        is_ca: bool,
+        issuer: Option<&(Certificate, KeyPair)>,
+    ) -> (Certificate, KeyPair) {
+        let key = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::new(vec![name.to_string()]).unwrap();
+        params.distinguished_name.push(DnType::CommonName, name);
+        if is_ca {
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        } else {
+            params.extended_key_usages = vec![
+                ExtendedKeyUsagePurpose::ServerAuth,
+                ExtendedKeyUsagePurpose::ClientAuth,
+            ];
+        }
+        let cert = match issuer {
+            Some((issuer, issuer_key)) => params.signed_by(&key, issuer, issuer_key),
+            None => params.self_signed(&key),
+        }
+        .unwrap();
+        (cert, key)
+    }
+
+    fn verifier(ca: Option<&Certificate>, pins: Vec<B256>) -> PinnedClientVerifier {
+        let provider = Arc::new(ring::default_provider());
+        let ca = ca.map(|ca| {
+            let mut roots = RootCertStore::empty();
+            roots.add(ca.der().clone()).unwrap();
+            WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+                .build()
+                .unwrap()
+        });
+        PinnedClientVerifier {
+            ca,
+            pins,
+            algorithms: provider.signature_verification_algorithms,
+        }
+    }
+
+    fn verify(verifier: &PinnedClientVerifier, cert: &Certificate) -> Result<(), rustls::Error> {
+        verifier
+            .verify_client_cert(cert.der(), &[], UnixTime::now())
+            .map(|_| ())
+    }
+
+    #[test]
+    fn pinned_client_certificates_are_accepted() {
+        let ca = issue("aggregator-ca", true, None);
+        let aggregator = issue("aggregator", false, Some(&ca));
+        let pins = vec![fingerprint(aggregator.0.der())];
+        assert!(verify(&verifier(Some(&ca.0), pins), &aggregator.0).is_ok());
+
+        // Without a CA the pin alone is enough, self-signed or not
+        let self_signed = issue("aggregator", false, None);
+        let pins = vec![fingerprint(self_signed.0.der())];
+        assert!(verify(&verifier(None, pins.clone()), &self_signed.0).is_ok());
+        assert!(verify(&verifier(None, pins), &aggregator.0).is_err());
+    }
+
+    #[test]
+    fn unpinned_certificates_of_the_ca_are_rejected() {
+        let ca = issue("aggregator-ca", true, None);
+        let aggregator = issue("aggregator", false, Some(&ca));
+        let other = issue("other", false, Some(&ca));
+        assert!(verify(&verifier(Some(&ca.0), Vec::new()), &other.0).is_ok());
+
+        let pins = vec![fingerprint(aggregator.0.der())];
+        assert!(matches!(
+            verify(&verifier(Some(&ca.0), pins), &other.0),
+            Err(rustls::Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure
+            ))
+        ));
+    }
+
+    // Writes `contents` to a file of this test run, modified at `at` so that
+    // rewrites are seen even within the file system's time resolution
+    fn write(name: &str, contents: &str, at: SystemTime) -> String {
+        let path = std::env::temp_dir().join(format!("operator-{}-{name}", std::process::id()));
+        fs::write(&path, contents).unwrap();
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(at)
+            .unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn server_tls(cert_path: String, key_path: String, client_ca_path: String) -> ServerTls {
+        let server_config = server_config(&cert_path, &key_path, Some(&client_ca_path), &[]);
+        let mut tls = ServerTls {
+            cert_path,
+            key_path,
+            client_ca_path: Some(client_ca_path),
+            client_pins: Vec::new(),
+            rustls: RustlsConfig::from_config(Arc::new(server_config.unwrap())),
+            modified: Mutex::default(),
+            reload_interval: Duration::from_secs(1),
+        };
+        tls.modified = Mutex::new(modified(&tls.paths()));
+        tls
+    }
+
+    // Runs a handshake in memory and returns the certificate the server
+    // presented
+    fn handshake(
+        server: &ServerTls,
+        server_ca: &Certificate,
+        client: &(Certificate, KeyPair),
+    ) -> Result<CertificateDer<'static>, rustls::Error> {
+        let mut roots = RootCertStore::empty();
+        roots.add(server_ca.der().clone()).unwrap();
+        let key = PrivatePkcs8KeyDer::from(client.1.serialize_der());
+        let client_config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(roots)
+            .with_client_auth_cert(vec![client.0.der().clone()], key.into())?;
+
+        let mut client = ClientConnection::new(
+            Arc::new(client_config),
+            ServerName::try_from("localhost").unwrap(),
+        )?;
+        let mut server = ServerConnection::new(server.rustls_config().get_inner())?;
+        while client.is_handshaking() || server.is_handshaking() {
+            let mut flight = Vec::new();
+            client.write_tls(&mut flight).unwrap();
+            server.read_tls(&mut flight.as_slice()).unwrap();
+            server.process_new_packets()?;
+
+            let mut flight = Vec::new();
+            server.write_tls(&mut flight).unwrap();
+            client.read_tls(&mut flight.as_slice()).unwrap();
+            client.process_new_packets()?;
+        }
+        Ok(client.peer_certificates().unwrap()[0].clone().into_owned())
+    }
+
+    #[test]
+    fn rotated_certificates_are_reloaded() {
+        let server_ca = issue("operator-ca", true, None);
+        let old_client_ca = issue("old-aggregator-ca", true, None);
+        let client_ca = issue("aggregator-ca", true, None);
+        let aggregator = issue("aggregator", false, Some(&client_ca));
+        let (first, first_key) = issue("localhost", false, Some(&server_ca));
+        let (second, second_key) = issue("localhost", false, Some(&server_ca));
+
+        let created = SystemTime::now();
+        let tls = server_tls(
+            write("reload-cert.pem", &first.pem(), created),
+            write("reload-key.pem", &first_key.serialize_pem(), created),
+            write("reload-ca.pem", &old_client_ca.0.pem(), created),
+        );
+        assert!(handshake(&tls, &server_ca.0, &aggregator).is_err());
+
+        // Unchanged files keep the served configuration
+        let served = tls.rustls_config().get_inner();
+        tls.reload_if_changed().unwrap();
+        assert!(Arc::ptr_eq(&served, &tls.rustls_config().get_inner()));
+
+        let rotated = created + Duration::from_secs(60);
+        write("reload-cert.pem", &second.pem(), rotated);
+        write("reload-key.pem", &second_key.serialize_pem(), rotated);
+        write("reload-ca.pem", &client_ca.0.pem(), rotated);
+        tls.reload_if_changed().unwrap();
+        assert_eq!(
+            handshake(&tls, &server_ca.0, &aggregator).unwrap(),
+            *second.der()
+        );
+
+        for path in tls.paths() {
+            fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -7,13 +7,27 @@ use ethers::{
 use std::{env, sync::Arc};
 
  mod abi;
+pub mod log_scanner;
+pub mod multicall;
+pub mod onboarding;
+pub mod output;
+pub mod tx;
+pub mod upgrade;
 // Import the type directly from the module
-use crate::abi::SymbioticRestaking;
+use crate::abi::{SymbioticRestaking, SymbioticRestakingErrors, SymbioticRestakingEvents, ERC20};
+use crate::output::{OutputFormat, TokenAmount};
+use crate::tx::{SendMode, WriteResult, DEFAULT_CONFIRMATIONS};
+use crate::upgrade::UpgradePlan;
 
 
+type SignerClient = SignerMiddleware<Provider<Http>, LocalWallet>;
 
 pub struct SymbioticClient {
-    contract: SymbioticRestaking<SignerMiddleware<Provider<Http>, LocalWallet>>,
+    contract: SymbioticRestaking<SignerClient>,
+    confirmations: usize,
+    mode: SendMode,
+    sender: Option<Address>,
+    output: OutputFormat,
 }
 
 impl SymbioticClient {
@@ -45,11 +59,111 @@ impl SymbioticClient {
             Arc::new(client),
         );
 
-        Ok(Self { contract })
+        Ok(Self {
+            contract,
+            confirmations: DEFAULT_CONFIRMATIONS,
+            mode: SendMode::Broadcast,
+            sender: None,
+            output: OutputFormat::Plain,
+        })
     }
-    // Get whitelisted vaults
-    pub async fn get_whitelisted_vaults(&self) -> Result<Vec<Address>> {
-        Ok(self.contract.get_whitelisted_vaults().call().await?)
+
+    // Number of confirmations write calls wait for (0 returns right after submission)
+    pub fn with_confirmations(mut self, confirmations: usize) -> Self {
+        self.confirmations = confirmations;
+        self
+    }
+
+    // Whether write calls are broadcast, simulated or only encoded
+    pub fn with_mode(mut self, mode: SendMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    // Simulate or encode write calls as sent by another account (e.g. an owner Safe)
+    pub fn with_sender(mut self, sender: Option<Address>) -> Self {
+        self.sender = sender;
+        self
+    }
+
+    // How write call results are printed
+    pub fn with_output(mut self, output: OutputFormat) -> Self {
+        self.output = output;
+        self
+    }
+
+    // Look up decimals and symbol of a token amount, for table output
+    pub async fn token_amount(&self, token: Address, amount: U256) -> TokenAmount {
+        let mut value = TokenAmount::new(token, amount);
+        if self.output == OutputFormat::Table {
+            let erc20 = ERC20::new(token, self.contract.client());
+            value.decimals = erc20.decimals().call().await.ok();
+            value.symbol = erc20.symbol().call().await.ok();
+        }
+        value
+    }
+
+    // Look up decimals and symbol of several token amounts in batched calls at
+    // `block`, for table output
+    pub async fn token_amounts(
+        &self,
+        amounts: Vec<(Address, U256)>,
+        block: u64,
+    ) -> Result<Vec<TokenAmount>> {
+        let mut values: Vec<TokenAmount> = amounts
+            .into_iter()
+            .map(|(token, amount)| TokenAmount::new(token, amount))
+            .collect();
+        if self.output == OutputFormat::Table {
+            let client = self.contract.client();
+            let tokens: Vec<_> = values
+                .iter()
+                .map(|value| ERC20::new(value.token, client.clone()))
+                .collect();
+            let decimals = multicall::call_all(
+                client.clone(),
+                block,
+                tokens.iter().map(|erc20| erc20.decimals()).collect(),
+            )
+            .await?;
+            let symbols = multicall::call_all(
+                client,
+                block,
+                tokens.iter().map(|erc20| erc20.symbol()).collect(),
+            )
+            .await?;
+            for ((value, decimals), symbol) in values.iter_mut().zip(decimals).zip(symbols) {
+                value.decimals = decimals;
+                value.symbol = symbol;
+            }
+        }
+        Ok(values)
+    }
+
+    // Latest block number, which listing commands read their snapshot at
+    pub async fn block_number(&self) -> Result<u64> {
+        Ok(self.contract.client().get_block_number().await?.as_u64())
+    }
+
+    // Run a write call in the configured mode and report the result
+    async fn send<D: ethers::abi::Detokenize + std::fmt::Debug>(
+        &self,
+        call: ContractCall<SignerClient, D>,
+        action: &str,
+    ) -> Result<WriteResult> {
+        let call = call.from(self.sender.unwrap_or_else(|| self.contract.client_ref().address()));
+        let result = tx::execute::<_, _, SymbioticRestakingErrors, SymbioticRestakingEvents>(
+            call,
+            self.mode,
+            self.confirmations,
+            self.contract.client_ref().signer().chain_id(),
+        )
+        .await?;
+        tx::report(action, self.mode, self.output, result)
+    }
+    // Get whitelisted vaults as of `block`
+    pub async fn get_whitelisted_vaults(&self, block: u64) -> Result<Vec<Address>> {
+        Ok(self.contract.get_whitelisted_vaults().block(block).call().await?)
     }
 
     // Add this to your SymbioticClient implementation
@@ -61,7 +175,7 @@ pub async fn initialize(
     symbiotic_operator_registry: Address,
     symbiotic_operator_net_opt_in: Address,
     symbiotic_vault_factory: Address,
-) -> Result<()> {
+) -> Result<WriteResult> {
     let tx = self.contract.initialize(
         owner,
         parameters,
@@ -70,9 +184,7 @@ pub async fn initialize(
         symbiotic_operator_net_opt_in,
         symbiotic_vault_factory,
     );
-    let pending_tx = tx.send().await?;
-    println!("Contract initialized: {:?}", pending_tx.tx_hash());
-    Ok(())
+    self.send(tx, "Contract initialized").await
 }
 
 
@@ -94,11 +206,9 @@ pub async fn initialize(
         validator_pubkey: String,
         block_number: u64,
         tx_id: H256,
-    ) -> Result<()> {
+    ) -> Result<WriteResult> {
         let tx = self.contract.slash(validator_pubkey, block_number.into(), tx_id.into());
-        let pending_tx = tx.send().await?;
-        println!("Transaction submitted: {:?}", pending_tx.tx_hash());
-        Ok(())
+        self.send(tx, "Slash requested").await
     }
 
     // Check validator response
@@ -115,11 +225,9 @@ pub async fn initialize(
     }
 
     // Register operator
-    pub async fn register_operator(&self, operator_addr: Address, rpc: String) -> Result<()> {
+    pub async fn register_operator(&self, operator_addr: Address, rpc: String) -> Result<WriteResult> {
         let tx = self.contract.register_operator(operator_addr, rpc);
-        let pending_tx = tx.send().await?;
-        println!("Operator registered: {:?}", pending_tx.tx_hash());
-        Ok(())
+        self.send(tx, "Operator registered").await
     }
 
     // Check vault status
@@ -127,8 +235,126 @@ pub async fn initialize(
         Ok(self.contract.is_vault_enabled(vault).call().await?)
     }
 
+    // Check the status of several vaults in batched calls at `block`
+    pub async fn are_vaults_enabled(&self, vaults: &[Address], block: u64) -> Result<Vec<bool>> {
+        let calls = vaults
+            .iter()
+            .map(|vault| self.contract.is_vault_enabled(*vault))
+            .collect();
+        multicall::call_all(self.contract.client(), block, calls)
+            .await?
+            .into_iter()
+            .zip(vaults)
+            .map(|(enabled, vault)| {
+                enabled.ok_or_else(|| anyhow::anyhow!("isVaultEnabled reverted for {:?}", vault))
+            })
+            .collect()
+    }
+
     // Get current epoch
     pub async fn get_current_time(&self) -> Result<u64> {
-        Ok(self.contract.get_current_time().call().await?.into())
+        Ok(self.contract.get_current_time().call().await?)
+    }
+
+    // Register vault
+    pub async fn register_vault(&self, vault: Address) -> Result<WriteResult> {
+        let tx = self.contract.register_vault(vault);
+        self.send(tx, "Vault registered").await
+    }
+
+    // Deregister vault
+    pub async fn deregister_vault(&self, vault: Address) -> Result<WriteResult> {
+        let tx = self.contract.deregister_vault(vault);
+        self.send(tx, "Vault deregistered").await
+    }
+
+    // Pause the calling vault (the sender must be a registered vault)
+    pub async fn pause_vault(&self) -> Result<WriteResult> {
+        let tx = self.contract.pause_vault();
+        self.send(tx, "Vault paused").await
+    }
+
+    // Unpause the calling vault (the sender must be a registered vault)
+    pub async fn unpause_vault(&self) -> Result<WriteResult> {
+        let tx = self.contract.unpause_vault();
+        self.send(tx, "Vault unpaused").await
+    }
+
+    // Get collateral tokens and stake of an operator for the current epoch, as of `block`
+    pub async fn get_provider_collateral_tokens(
+        &self,
+        operator: Address,
+        block: u64,
+    ) -> Result<(Vec<Address>, Vec<U256>)> {
+        Ok(self.contract
+            .get_provider_collateral_tokens(operator)
+            .block(block)
+            .call()
+            .await?)
+    }
+
+    // Get operator stake at a specific timestamp
+    pub async fn get_operator_stake_at(
+        &self,
+        operator: Address,
+        collateral: Address,
+        timestamp: u64,
+    ) -> Result<U256> {
+        Ok(self.contract
+            .get_operator_stake_at(operator, collateral, timestamp)
+            .call()
+            .await?)
+    }
+
+    // Get the start timestamp of an epoch
+    pub async fn get_period_start_time(&self, epoch: u64) -> Result<u64> {
+        Ok(self.contract.get_period_start_time(epoch).call().await?)
+    }
+
+    // Get the epoch containing a timestamp
+    pub async fn get_period_at_time(&self, timestamp: u64) -> Result<u64> {
+        Ok(self.contract.get_period_at_time(timestamp).call().await?)
+    }
+
+    // Get the timestamp epochs are counted from
+    pub async fn get_start_timestamp(&self) -> Result<u64> {
+        Ok(self.contract.start_timestamp().call().await?)
+    }
+
+    // Check and simulate an upgrade of `proxy` (this contract by default)
+    pub async fn plan_upgrade(
+        &self,
+        proxy: Option<Address>,
+        new_implementation: Address,
+        data: Vec<u8>,
+    ) -> Result<UpgradePlan> {
+        upgrade::plan_upgrade(
+            self.contract.client(),
+            proxy.unwrap_or_else(|| self.contract.address()),
+            new_implementation,
+            Bytes::from(data),
+        )
+        .await
+    }
+
+    // Run a checked upgrade plan in the configured mode
+    pub async fn execute_upgrade(&self, plan: &UpgradePlan) -> Result<WriteResult> {
+        let result = match self.mode {
+            SendMode::Broadcast => WriteResult::Sent(
+                upgrade::execute_upgrade(self.contract.client(), plan, self.confirmations).await?,
+            ),
+            SendMode::DryRun => WriteResult::Simulated(plan.simulation()),
+            SendMode::CalldataOnly | SendMode::Safe => WriteResult::Prepared(plan.prepared()),
+        };
+        tx::report("Contract upgraded", self.mode, self.output, result)
+    }
+
+    // Derive the validator address the same way the contract's internal
+    // `getValidatorAddress` does: the last 20 bytes of keccak256(pubkey)
+    pub fn get_validator_address(pubkey: &[u8]) -> Result<Address> {
+        if pubkey.len() != 64 {
+            anyhow::bail!("Invalid pubkey length: expected 64 bytes, got {}", pubkey.len());
+        }
+        Ok(Address::from_slice(&ethers::utils::keccak256(pubkey)[12..]))
     }
 }
\ No newline at end of file
@@ -0,0 +1,47 @@
+use anyhow::Result;
+use ethers::{
+    abi::{Detokenize, Tokenizable},
+    contract::{ContractCall, Multicall, MULTICALL_ADDRESS},
+    providers::Middleware,
+    types::Address,
+};
+use std::{env, sync::Arc};
+
+/// Calls sent in one Multicall3 request.
+pub const MULTICALL_BATCH_SIZE: usize = 500;
+
+/// Multicall3 address, `MULTICALL_ADDRESS` from the environment or the address
+/// it is deployed at on mainnet, Sepolia, Holesky and most other chains.
+pub fn multicall_address() -> Result<Address> {
+    match env::var("MULTICALL_ADDRESS") {
+        Ok(address) => Ok(address.parse()?),
+        Err(_) => Ok(MULTICALL_ADDRESS),
+    }
+}
+
+/// Make view calls through Multicall3, all read at `block`, and return their
+/// results in order. Calls that revert give `None`.
+pub async fn call_all<M, D>(
+    client: Arc<M>,
+    block: u64,
+    calls: Vec<ContractCall<M, D>>,
+) -> Result<Vec<Option<D>>>
+where
+    M: Middleware + 'static,
+    D: Tokenizable + Detokenize,
+{
+    let address = multicall_address()?;
+    let mut results = Vec::with_capacity(calls.len());
+    let mut calls = calls.into_iter().peekable();
+    while calls.peek().is_some() {
+        let mut multicall =
+            Multicall::new_with_chain_id(client.clone(), Some(address), None::<u64>)?.block(block);
+        for call in calls.by_ref().take(MULTICALL_BATCH_SIZE) {
+            multicall.add_call(call, true);
+        }
+        for result in multicall.call_raw().await? {
+            results.push(result.ok().map(D::from_token).transpose()?);
+        }
+    }
+    Ok(results)
+}
@@ -0,0 +1,337 @@
+use anyhow::Result;
+use ethers::{signers::Signer, types::Address};
+use serde::Serialize;
+
+use crate::abi::symbiotic_registry::AddEntityFilter;
+use crate::abi::{
+    SymbioticDelegator, SymbioticOptInService, SymbioticOptInServiceErrors,
+    SymbioticOptInServiceEvents, SymbioticRegistry, SymbioticRegistryErrors,
+    SymbioticRestakingErrors, SymbioticVault,
+};
+use crate::output::{Render, Table};
+use crate::tx::{self, SendMode};
+use crate::SymbioticClient;
+
+/// Opt-in state of an operator for one whitelisted vault.
+#[derive(Debug, Clone, Serialize)]
+pub struct VaultOptIn {
+    pub vault: Address,
+    pub enabled: bool,
+    pub opt_in_service: Address,
+    pub opted_in: bool,
+}
+
+/// Prerequisites `registerOperator` relies on, as currently seen on chain.
+#[derive(Debug, Clone, Serialize)]
+pub struct OnboardingReport {
+    pub operator: Address,
+    /// Middleware owner, the only account allowed to call `registerOperator`
+    pub owner: Address,
+    /// Account `registerOperator` would be sent from
+    pub sender: Address,
+    pub already_registered: bool,
+    pub network: Address,
+    pub operator_registry: Address,
+    pub network_opt_in_service: Address,
+    pub registered_in_registry: bool,
+    pub opted_into_network: bool,
+    pub vaults: Vec<VaultOptIn>,
+}
+
+impl OnboardingReport {
+    /// Human-readable list of the steps the operator still has to complete.
+    pub fn missing_steps(&self) -> Vec<String> {
+        let mut steps = Vec::new();
+        if !self.registered_in_registry {
+            steps.push(format!(
+                "register the operator in the Symbiotic OperatorRegistry ({:?})",
+                self.operator_registry
+            ));
+        }
+        if !self.opted_into_network {
+            steps.push(format!(
+                "opt into network {:?} via the network opt-in service ({:?})",
+                self.network, self.network_opt_in_service
+            ));
+        }
+        if !self.vaults.iter().any(|v| v.enabled && v.opted_in) {
+            let pending: Vec<String> = self
+                .vaults
+                .iter()
+                .filter(|v| v.enabled)
+                .map(|v| format!("{:?}", v.vault))
+                .collect();
+            if pending.is_empty() {
+                steps.push("no enabled vault is whitelisted by the middleware".to_string());
+            } else {
+                steps.push(format!("opt into at least one enabled vault ({})", pending.join(", ")));
+            }
+        }
+        steps
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.missing_steps().is_empty()
+    }
+
+    /// Registration left to the owner, when the operator is not registered
+    /// yet and `registerOperator` would not be sent by the owner.
+    pub fn owner_step(&self) -> Option<String> {
+        if self.already_registered || self.sender == self.owner {
+            return None;
+        }
+        Some(format!(
+            "the middleware owner {:?} has to call registerOperator (e.g. with --safe or --calldata-only and --from {:?})",
+            self.owner, self.owner
+        ))
+    }
+}
+
+fn check_mark(done: bool) -> &'static str {
+    if done {
+        "✅"
+    } else {
+        "❌"
+    }
+}
+
+impl Render for OnboardingReport {
+    fn plain(&self) -> String {
+        let mut lines = vec![
+            format!("Operator {:?}", self.operator),
+            format!("  Middleware registration: {}", check_mark(self.already_registered)),
+            format!("  OperatorRegistry registration: {}", check_mark(self.registered_in_registry)),
+            format!("  Network opt-in: {}", check_mark(self.opted_into_network)),
+        ];
+        for vault in &self.vaults {
+            lines.push(format!(
+                "  Vault {:?} (enabled: {}) opt-in: {}",
+                vault.vault,
+                vault.enabled,
+                check_mark(vault.opted_in)
+            ));
+        }
+        for step in self.missing_steps() {
+            lines.push(format!("Missing: {}", step));
+        }
+        if let Some(step) = self.owner_step() {
+            lines.push(format!("Owner: {}", step));
+        }
+        lines.join("\n")
+    }
+
+    fn table(&self) -> Table {
+        let mut table = Table::new(&["step", "target", "done"]);
+        table.push(vec![
+            "middleware registration".to_string(),
+            format!("{:?}", self.owner),
+            self.already_registered.to_string(),
+        ]);
+        table.push(vec![
+            "operator registry".to_string(),
+            format!("{:?}", self.operator_registry),
+            self.registered_in_registry.to_string(),
+        ]);
+        table.push(vec![
+            "network opt-in".to_string(),
+            format!("{:?}", self.network),
+            self.opted_into_network.to_string(),
+        ]);
+        for vault in &self.vaults {
+            let step = if vault.enabled { "vault opt-in" } else { "vault opt-in (disabled)" };
+            table.push(vec![
+                step.to_string(),
+                format!("{:?}", vault.vault),
+                vault.opted_in.to_string(),
+            ]);
+        }
+        table
+    }
+}
+
+impl SymbioticClient {
+    // Check every Symbiotic prerequisite of `registerOperator` for an operator,
+    // and whether the middleware owner already registered it
+    pub async fn check_onboarding(&self, operator: Address) -> Result<OnboardingReport> {
+        let client = self.contract.client();
+        let owner = self.contract.owner().call().await?;
+        let sender = self.sender.unwrap_or_else(|| client.address());
+        let already_registered = self.is_operator_registered(operator, owner).await?;
+
+        let network = self.contract.symbiotic_network().call().await?;
+        let operator_registry = self.contract.operator_registry().call().await?;
+        let network_opt_in_service = self.contract.operator_net_optin().call().await?;
+
+        let registered_in_registry = SymbioticRegistry::new(operator_registry, client.clone())
+            .is_entity(operator)
+            .call()
+            .await?;
+        let opted_into_network = SymbioticOptInService::new(network_opt_in_service, client.clone())
+            .is_opted_in(operator, network)
+            .call()
+            .await?;
+
+        let mut vaults = Vec::new();
+        for vault in self.contract.get_whitelisted_vaults().call().await? {
+            let enabled = self.contract.is_vault_enabled(vault).call().await?;
+            let delegator = SymbioticVault::new(vault, client.clone())
+                .delegator()
+                .call()
+                .await?;
+            let opt_in_service = SymbioticDelegator::new(delegator, client.clone())
+                .operator_vault_opt_in_service()
+                .call()
+                .await?;
+            let opted_in = SymbioticOptInService::new(opt_in_service, client.clone())
+                .is_opted_in(operator, vault)
+                .call()
+                .await?;
+
+            vaults.push(VaultOptIn {
+                vault,
+                enabled,
+                opt_in_service,
+                opted_in,
+            });
+        }
+
+        Ok(OnboardingReport {
+            operator,
+            owner,
+            sender,
+            already_registered,
+            network,
+            operator_registry,
+            network_opt_in_service,
+            registered_in_registry,
+            opted_into_network,
+            vaults,
+        })
+    }
+
+    // Whether the middleware registered `operator`. There is no view for it, so
+    // `registerOperator` is simulated from the owner and an
+    // `OperatorAlreadyRegistered` revert read as registered
+    async fn is_operator_registered(&self, operator: Address, owner: Address) -> Result<bool> {
+        let call = self.contract.register_operator(operator, String::new()).from(owner);
+        match call.call().await {
+            Ok(()) => Ok(false),
+            Err(e) => match e.decode_contract_revert::<SymbioticRestakingErrors>() {
+                Some(SymbioticRestakingErrors::OperatorAlreadyRegistered(_)) => Ok(true),
+                Some(_) => Ok(false),
+                None => Err(e.into()),
+            },
+        }
+    }
+
+    // Perform the missing opt-ins. They are sent by the configured signer (or
+    // simulated/encoded for the configured sender), which must be the operator.
+    // Broadcast opt-ins wait for at least one confirmation since the report is
+    // re-checked afterwards.
+    pub async fn perform_opt_ins(&self, report: &OnboardingReport) -> Result<()> {
+        let client = self.contract.client();
+        let sender = self.sender.unwrap_or_else(|| client.address());
+        if sender != report.operator {
+            anyhow::bail!(
+                "Opt-ins must be sent by the operator {:?}, but the sender is {:?}",
+                report.operator,
+                sender
+            );
+        }
+        let chain_id = client.signer().chain_id();
+        let confirmations = self.confirmations.max(1);
+
+        if !report.registered_in_registry {
+            let registry = SymbioticRegistry::new(report.operator_registry, client.clone());
+            let result = tx::execute::<_, _, SymbioticRegistryErrors, AddEntityFilter>(
+                registry.register_operator().from(sender),
+                self.mode,
+                confirmations,
+                chain_id,
+            )
+            .await?;
+            tx::report("Operator registered in OperatorRegistry", self.mode, self.output, result)?;
+        }
+
+        if !report.opted_into_network {
+            let service = SymbioticOptInService::new(report.network_opt_in_service, client.clone());
+            let result = tx::execute::<
+                _,
+                _,
+                SymbioticOptInServiceErrors,
+                SymbioticOptInServiceEvents,
+            >(
+                service.opt_in(report.network).from(sender),
+                self.mode,
+                confirmations,
+                chain_id,
+            )
+            .await?;
+            tx::report("Operator opted into network", self.mode, self.output, result)?;
+        }
+
+        for vault in report.vaults.iter().filter(|v| v.enabled && !v.opted_in) {
+            let service = SymbioticOptInService::new(vault.opt_in_service, client.clone());
+            let result = tx::execute::<
+                _,
+                _,
+                SymbioticOptInServiceErrors,
+                SymbioticOptInServiceEvents,
+            >(
+                service.opt_in(vault.vault).from(sender),
+                self.mode,
+                confirmations,
+                chain_id,
+            )
+            .await?;
+            tx::report(
+                &format!("Operator opted into vault {:?}", vault.vault),
+                self.mode,
+                self.output,
+                result,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    // Check the prerequisites, optionally complete them, and register the
+    // operator only once nothing is missing. `registerOperator` is owner-only,
+    // so when the sender is not the owner (e.g. the operator sending its own
+    // opt-ins) registration is reported as left to the owner instead
+    pub async fn onboard(
+        &self,
+        operator: Address,
+        rpc: String,
+        perform_opt_ins: bool,
+    ) -> Result<OnboardingReport> {
+        let mut report = self.check_onboarding(operator).await?;
+        if report.already_registered {
+            return Ok(report);
+        }
+
+        if !report.is_ready() && perform_opt_ins {
+            self.perform_opt_ins(&report).await?;
+            if self.mode != SendMode::Broadcast {
+                // Nothing was sent, so registration would still fail the checks
+                return Ok(report);
+            }
+            report = self.check_onboarding(operator).await?;
+        }
+
+        let missing = report.missing_steps();
+        if !missing.is_empty() {
+            anyhow::bail!(
+                "Operator {:?} is not ready to register:\n  - {}",
+                operator,
+                missing.join("\n  - ")
+            );
+        }
+
+        if report.owner_step().is_none() {
+            self.register_operator(operator, rpc).await?;
+            report.already_registered = self.mode == SendMode::Broadcast;
+        }
+        Ok(report)
+    }
+}
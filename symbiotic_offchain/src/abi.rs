@@ -1,16 +1,73 @@
 use ethers::prelude::*;
 
+// Generated from the SymbioticRestaking forge artifact, so every external
+// function, event and custom error of the middleware is bound.
+abigen!(SymbioticRestaking, "./src/abii.json");
+
+// Symbiotic core contracts the middleware checks before registering an operator.
+abigen!(
+    SymbioticRegistry,
+    r#"[
+        function isEntity(address entity) external view returns (bool)
+        function registerOperator() external
+        event AddEntity(address indexed entity)
+        error EntityNotExist()
+        error OperatorAlreadyRegistered()
+    ]"#,
+);
+
+abigen!(
+    SymbioticOptInService,
+    r#"[
+        function isOptedIn(address who, address where) external view returns (bool)
+        function optIn(address where) external
+        event OptIn(address indexed who, address indexed where)
+        event OptOut(address indexed who, address indexed where)
+        error AlreadyOptedIn()
+        error NotWhereEntity()
+        error NotWho()
+    ]"#,
+);
+
+abigen!(
+    SymbioticVault,
+    r#"[
+        function collateral() external view returns (address)
+        function delegator() external view returns (address)
+    ]"#,
+);
+
+abigen!(
+    SymbioticDelegator,
+    r#"[
+        function OPERATOR_VAULT_OPT_IN_SERVICE() external view returns (address)
+    ]"#,
+);
+
+// Token metadata used to format collateral amounts
+abigen!(
+    ERC20,
+    r#"[
+        function decimals() external view returns (uint8)
+        function symbol() external view returns (string)
+    ]"#,
+);
+
+// Minimal surface shared by every Ownable UUPS proxy of the middleware
 abigen!(
-    SymbioticRestaking,
+    UUPSUpgradeable,
     r#"[
-        function getWhitelistedVaults() external view returns (address[] memory)
-        function getProviderCollateral(address operator, address collateral) external view returns (uint256 amount)
-        function slash(string validatorPubkey, uint256 blockNumber, bytes32 txId) external
-        function get_validator_response(string validatorPubkey, uint256 blockNumber, bytes32 txId) external view returns (bool verified)
-        function registerOperator(address operatorAddr, string rpc) external
-        function isVaultEnabled(address vault) external view returns (bool)
-        function getCurrentTime() external view returns (uint48 epoch)
-        function initialize(address _owner, address _parameters, address _symbioticNetwork, address _symbioticOperatorRegistry, address _symbioticOperatorNetOptIn, address _symbioticVaultFactory) external
-        function verified_txn(bool result, string validatorPubkey, uint256 blockNumber, bytes32 txId) external
+        function proxiableUUID() external view returns (bytes32)
+        function upgradeToAndCall(address newImplementation, bytes data) external payable
+        function owner() external view returns (address)
+        function UPGRADE_INTERFACE_VERSION() external view returns (string)
+        event Upgraded(address indexed implementation)
+        error AddressEmptyCode(address target)
+        error ERC1967InvalidImplementation(address implementation)
+        error ERC1967NonPayable()
+        error FailedCall()
+        error OwnableUnauthorizedAccount(address account)
+        error UUPSUnauthorizedCallContext()
+        error UUPSUnsupportedProxiableUUID(bytes32 slot)
     ]"#,
-);
\ No newline at end of file
+);
@@ -1,17 +1,17 @@
 use anyhow::Result;
 use ethers::{
     prelude::*,
-    types::{Address, Filter, Log, U64},
+    types::{Address, Log, U64},
     providers::{Provider, Http},
 };
 use std::{
-    str::FromStr, 
     sync::Arc, 
     time::Duration
 };
 
 // Import the ABI from abi.rs
-use crate::abi::SymbioticRestaking;
+use crate::abi::{SymbioticRestaking, TransactionVerifiedFilter};
+use symbiotic_offchain::log_scanner::LogScanner;
 
 pub struct EventFetcher {
     provider: Arc<Provider<Http>>,
@@ -28,58 +28,43 @@ impl EventFetcher {
     }
 
     pub async fn start_continuous_fetching(&self,  contract: &SymbioticRestaking<SignerMiddleware<Provider<Http>, LocalWallet>>,  rpc_url: &str) -> Result<()> {
-        let mut last_processed_block: U64 = 7799031.into();
+        let mut next_block: u64 = 7799031;
         println!("Starting continuous event fetching...");
 
+        let target_event_signature = TransactionVerifiedFilter::signature();
+        let scanner = LogScanner::new(self.provider.clone(), self.contract_address)
+            .with_topic0(target_event_signature);
+
         loop {
-            let latest_block = self.get_latest_block().await?;
+            let latest_block = self.get_latest_block().await?.as_u64();
             println!("Latest block: {}", latest_block);
 
-            if last_processed_block >= latest_block {
-                last_processed_block = 0.into();
-            }
-
-            // Fetch events in batches of 900 blocks to stay under 1000-block limit
-            let batch_size: u64 = 900;
-            let mut current_from_block = last_processed_block.as_u64();
-            let mut total_logs = Vec::new();
-
-            while current_from_block < latest_block.as_u64() {
-                let current_to_block = std::cmp::min(
-                    current_from_block + batch_size, 
-                    latest_block.as_u64()
-                );
+            if next_block <= latest_block {
+                let outcome = scanner
+                    .scan(next_block, latest_block, |progress| {
+                        println!(
+                            "Scanned blocks {} to {} of {} ({} events, next range {})",
+                            progress.from_block,
+                            progress.scanned_to,
+                            progress.to_block,
+                            progress.logs_found,
+                            progress.range
+                        );
+                    })
+                    .await;
 
-                println!("Fetching events from block {} to {}", current_from_block, current_to_block);
-                
-                match self.fetch_events(
-                    current_from_block.into(), 
-                    current_to_block.into()
-                ).await {
-                    Ok(logs) => {
-                        println!("logs {:?}", logs);
-                
-                        total_logs.extend(logs);
-                        current_from_block = current_to_block + 1;
-                    }
-                    Err(e) => {
-                        eprintln!("Error fetching events in batch {}-{}: {}", 
-                            current_from_block, current_to_block, e);
-                        break;
-                    }
+                if let Some(e) = &outcome.interrupted {
+                    eprintln!("Error fetching events: {}", e);
                 }
-            }
 
-            // Process logs
-            if !total_logs.is_empty() {
-                println!("Found {} total events", total_logs.len());
-                self.parse_custom_events(total_logs.clone(),contract,rpc_url)?;
-                
-                // Update last processed block
-                if let Some(last_log_block) = total_logs.last()
-                    .and_then(|log| log.block_number) {
-                    last_processed_block = last_log_block + 1;
+                // Process logs
+                if !outcome.logs.is_empty() {
+                    println!("Found {} total events", outcome.logs.len());
+                    self.parse_custom_events(outcome.logs, contract, rpc_url)?;
                 }
+
+                // Resume after the last block that was fully scanned
+                next_block = outcome.next_block;
             }
 
             // Wait before next iteration
@@ -91,15 +76,6 @@ impl EventFetcher {
         Ok(self.provider.get_block_number().await?)
     }
 
-    async fn fetch_events(&self, from_block: U64, to_block: U64) -> Result<Vec<Log>> {
-        let filter = Filter::new()
-            .address(self.contract_address)
-            .from_block(from_block)
-            .to_block(to_block);
-
-        let logs = self.provider.get_logs(&filter).await?;
-        Ok(logs)
-    }
     fn parse_custom_events(
         &self, 
         logs: Vec<Log>, 
@@ -107,11 +83,11 @@ impl EventFetcher {
         rpc_url: &str
     ) -> Result<()> {
         // Specific event signature we're looking for
-        let target_event_signature = H256::from_str("0x5a227abd3964ab468a622981e3cafd0f5ee720e97f16d5b249c8be49e366f870").unwrap();
+        let target_event_signature = TransactionVerifiedFilter::signature();
         let rpc_url_clone = rpc_url.to_string();
     
         for log in logs {
-            if let Some(topic) = log.topics.get(0) {
+            if let Some(topic) = log.topics.first() {
                 if *topic == target_event_signature && log.topics.len() >= 4 {
                     // Extract details from the topics
                     let validator_id = H256::from_slice(&log.topics[1][0..32]);
@@ -267,7 +243,7 @@ impl EventFetcher {
     
     //     for log in logs {
     //         // Check if this is the specific event we're interested in
-    //         if let Some(topic) = log.topics.get(0) {
+    //         if let Some(topic) = log.topics.first() {
     //             if *topic == target_event_signature {
     //                 // Extract details from the topics
     //                 if log.topics.len() >= 4 {
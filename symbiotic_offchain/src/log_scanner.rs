@@ -0,0 +1,226 @@
+use anyhow::Result;
+use ethers::{
+    prelude::*,
+    types::{Address, Filter, Log, H256},
+};
+use std::sync::Arc;
+
+/// Block range used for the first `eth_getLogs` request of a scan.
+pub const DEFAULT_INITIAL_RANGE: u64 = 900;
+/// Largest block range the scanner grows to after successful requests.
+pub const DEFAULT_MAX_RANGE: u64 = 10_000;
+
+// Error fragments returned by providers that cap the block range or result count
+// of `eth_getLogs` (geth, Infura, Alchemy, QuickNode, Ankr, publicnode, ...).
+// Rate limits share code -32005 and "limit exceeded" with some of them but a
+// smaller range does not help, so they interrupt the scan instead.
+const RANGE_LIMIT_PATTERNS: &[&str] = &[
+    "query returned more than",
+    "block range",
+    "range too large",
+    "range is too large",
+    "exceed maximum block range",
+    "response size exceeded",
+    "response size should not",
+    "too many results",
+];
+
+/// Progress of a running scan, reported after every successful request.
+#[derive(Debug, Clone, Copy)]
+pub struct ScanProgress {
+    /// First block of the scan.
+    pub from_block: u64,
+    /// Last block of the scan.
+    pub to_block: u64,
+    /// Last block covered so far.
+    pub scanned_to: u64,
+    /// Number of logs fetched so far.
+    pub logs_found: usize,
+    /// Block range that will be used for the next request.
+    pub range: u64,
+}
+
+/// Result of a scan. When `interrupted` is set, only the blocks up to
+/// `next_block - 1` were covered and the scan should be resumed from `next_block`.
+#[derive(Debug, Default)]
+pub struct ScanOutcome {
+    /// Logs of the covered blocks, in block order.
+    pub logs: Vec<Log>,
+    /// First block not covered, where the next scan starts.
+    pub next_block: u64,
+    /// Error that stopped the scan before `to_block`, if any.
+    pub interrupted: Option<String>,
+}
+
+/// Fetches logs of a single contract over arbitrary block ranges, bisecting the
+/// request range whenever the provider rejects it for returning too much data and
+/// growing it again after successful requests.
+pub struct LogScanner<M> {
+    provider: Arc<M>,
+    address: Address,
+    topic0: Option<H256>,
+    initial_range: u64,
+    max_range: u64,
+}
+
+impl<M: Middleware> LogScanner<M> {
+    pub fn new(provider: Arc<M>, address: Address) -> Self {
+        Self {
+            provider,
+            address,
+            topic0: None,
+            initial_range: DEFAULT_INITIAL_RANGE,
+            max_range: DEFAULT_MAX_RANGE,
+        }
+    }
+
+    /// Only fetch logs whose first topic matches `topic0` (filtered server side).
+    pub fn with_topic0(mut self, topic0: H256) -> Self {
+        self.topic0 = Some(topic0);
+        self
+    }
+
+    /// Override the initial and maximum block ranges of a request.
+    pub fn with_ranges(mut self, initial_range: u64, max_range: u64) -> Self {
+        self.max_range = max_range.max(1);
+        self.initial_range = initial_range.clamp(1, self.max_range);
+        self
+    }
+
+    /// Fetch all matching logs in `from_block..=to_block`.
+    ///
+    /// Range-limit errors shrink the request range down to a single block; any
+    /// other provider error interrupts the scan and is reported in the outcome
+    /// together with the logs fetched before it.
+    pub async fn scan<F>(&self, from_block: u64, to_block: u64, mut on_progress: F) -> ScanOutcome
+    where
+        F: FnMut(&ScanProgress),
+    {
+        let mut outcome = ScanOutcome {
+            logs: Vec::new(),
+            next_block: from_block,
+            interrupted: None,
+        };
+        let mut range = self.initial_range;
+
+        while outcome.next_block <= to_block {
+            let start = outcome.next_block;
+            let end = start.saturating_add(range - 1).min(to_block);
+
+            match self.fetch_logs(start, end).await {
+                Ok(logs) => {
+                    outcome.logs.extend(logs);
+                    outcome.next_block = end + 1;
+                    range = range.saturating_mul(2).min(self.max_range);
+
+                    on_progress(&ScanProgress {
+                        from_block,
+                        to_block,
+                        scanned_to: end,
+                        logs_found: outcome.logs.len(),
+                        range,
+                    });
+                }
+                Err(e) if is_range_limit_error(&e.to_string()) && end > start => {
+                    // Half of the rejected range, or the provider's suggestion if smaller
+                    let half = (end - start).div_ceil(2);
+                    range = match suggested_range(&e.to_string()) {
+                        Some((hint_start, hint_end)) if hint_end >= hint_start => {
+                            (hint_end - hint_start + 1).min(half)
+                        }
+                        _ => half,
+                    };
+                }
+                Err(e) => {
+                    outcome.interrupted = Some(format!(
+                        "eth_getLogs failed for blocks {}-{}: {}",
+                        start, end, e
+                    ));
+                    break;
+                }
+            }
+        }
+
+        outcome
+    }
+
+    async fn fetch_logs(&self, from_block: u64, to_block: u64) -> Result<Vec<Log>> {
+        let mut filter = Filter::new()
+            .address(self.address)
+            .from_block(from_block)
+            .to_block(to_block);
+        if let Some(topic0) = self.topic0 {
+            filter = filter.topic0(topic0);
+        }
+
+        self.provider
+            .get_logs(&filter)
+            .await
+            .map_err(|e| anyhow::anyhow!("{}", e))
+    }
+}
+
+/// Whether a provider error means the requested range returned too many logs
+/// or spanned too many blocks.
+pub fn is_range_limit_error(message: &str) -> bool {
+    let message = message.to_lowercase();
+    RANGE_LIMIT_PATTERNS
+        .iter()
+        .any(|pattern| message.contains(pattern))
+}
+
+/// Parse the `[0xstart, 0xend]` range some providers suggest when rejecting a request.
+pub fn suggested_range(message: &str) -> Option<(u64, u64)> {
+    let open = message.rfind('[')?;
+    let close = open + message[open..].find(']')?;
+    let (start, end) = message[open + 1..close].split_once(',')?;
+    let parse = |s: &str| {
+        let s = s.trim();
+        match s.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16).ok(),
+            None => s.parse().ok(),
+        }
+    };
+    Some((parse(start)?, parse(end)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_range_limit_errors() {
+        assert!(is_range_limit_error(
+            "query returned more than 10000 results. Try with this block range [0x10, 0x20]."
+        ));
+        assert!(is_range_limit_error("Block range is too large"));
+        assert!(is_range_limit_error(
+            "server returned an error response: error code -32005: query returned more than 10000 results"
+        ));
+        assert!(!is_range_limit_error(
+            "server returned an error response: error code -32005: limit exceeded"
+        ));
+        assert!(!is_range_limit_error(
+            "error code -32005: daily request count exceeded, request rate limited"
+        ));
+        assert!(!is_range_limit_error("connection refused"));
+        assert!(!is_range_limit_error("execution reverted"));
+    }
+
+    #[test]
+    fn parses_suggested_ranges() {
+        assert_eq!(
+            suggested_range("Try with this block range [0x10, 0x1F]."),
+            Some((16, 31))
+        );
+        assert_eq!(suggested_range("retry with [100, 199]"), Some((100, 199)));
+        // The last bracketed pair is the suggestion
+        assert_eq!(
+            suggested_range("range [1, 2] too large, use [0x1, 0x1]"),
+            Some((1, 1))
+        );
+        assert_eq!(suggested_range("block range too large"), None);
+        assert_eq!(suggested_range("use [0x10]"), None);
+        assert_eq!(suggested_range("use [a, b]"), None);
+    }
+}
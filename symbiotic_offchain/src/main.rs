@@ -3,6 +3,11 @@ mod event_fetcher;
 
 use abi::SymbioticRestaking;
 use event_fetcher::EventFetcher;
+use symbiotic_offchain::output::{
+    self, AddressList, CollateralBalance, CollateralTokens, EpochInfo, OperatorStake,
+    OutputFormat, ValidatorAddress, ValidatorResponse, VaultStatus, VaultStatuses,
+};
+use symbiotic_offchain::tx::{SendMode, DEFAULT_CONFIRMATIONS};
 use symbiotic_offchain::SymbioticClient;
 use ethers::{
     prelude::*,
@@ -20,6 +25,30 @@ use std::{str::FromStr, sync::Arc};
 #[command(name = "Symbiotic Restaking CLI")]
 #[command(version = "1.0")]
 struct Cli {
+    /// Output format of command results
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Plain)]
+    output: OutputFormat,
+
+    /// Confirmations to wait for after sending a transaction (0 to only submit it)
+    #[arg(long, global = true, default_value_t = DEFAULT_CONFIRMATIONS)]
+    confirmations: usize,
+
+    /// Simulate write commands with eth_call and eth_estimateGas instead of broadcasting
+    #[arg(long, global = true, conflicts_with_all = ["calldata_only", "safe"])]
+    dry_run: bool,
+
+    /// Print target, value and calldata of write commands instead of broadcasting
+    #[arg(long, global = true, conflicts_with = "safe")]
+    calldata_only: bool,
+
+    /// Print write commands as a Safe transaction-builder batch instead of broadcasting
+    #[arg(long, global = true)]
+    safe: bool,
+
+    /// Sender to simulate or encode write commands for (e.g. the owner Safe)
+    #[arg(long, global = true)]
+    from: Option<String>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -27,7 +56,11 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Get whitelisted vaults
-    GetVaults,
+    GetVaults {
+        /// Also show whether each vault is enabled, read at the same block
+        #[arg(long)]
+        status: bool,
+    },
 
     /// Get provider collateral
     GetCollateral {
@@ -55,6 +88,18 @@ enum Commands {
         rpc: String,
     },
 
+    /// Check the Symbiotic prerequisites of an operator and register it once they are met (registration is sent by the owner)
+    Onboard {
+        operator_addr: String,
+        rpc: String,
+        /// Send the missing registry, network and vault opt-ins (signer must be the operator)
+        #[arg(long)]
+        opt_in: bool,
+        /// Only report the missing steps, do not register
+        #[arg(long)]
+        check_only: bool,
+    },
+
     /// Check vault status
     CheckVault {
         vault: String,
@@ -63,6 +108,60 @@ enum Commands {
     /// Get current epoch
     GetCurrentEpoch,
 
+    /// Register a vault
+    RegisterVault {
+        vault: String,
+    },
+
+    /// Deregister a vault
+    DeregisterVault {
+        vault: String,
+    },
+
+    /// Pause the vault sending the transaction
+    PauseVault,
+
+    /// Unpause the vault sending the transaction
+    UnpauseVault,
+
+    /// Get provider collateral tokens and amounts for the current epoch
+    GetCollateralTokens {
+        operator: String,
+    },
+
+    /// Get operator stake at a specific timestamp
+    GetOperatorStakeAt {
+        operator: String,
+        collateral: String,
+        timestamp: u64,
+    },
+
+    /// Get the start time of an epoch
+    GetPeriodStartTime {
+        epoch: u64,
+    },
+
+    /// Get the epoch containing a timestamp
+    GetPeriodAtTime {
+        timestamp: u64,
+    },
+
+    /// Derive the validator address from a 64-byte public key
+    GetValidatorAddress {
+        pubkey: String,
+    },
+
+    /// Upgrade a UUPS proxy after checking the new implementation and simulating the call
+    Upgrade {
+        new_implementation: String,
+        /// Proxy to upgrade (defaults to CONTRACT_ADDRESS)
+        #[arg(long)]
+        proxy: Option<String>,
+        /// Calldata of the initializer to run after the upgrade
+        #[arg(long)]
+        init_data: Option<String>,
+    },
+
     FetchEvents,
 }
 
@@ -72,25 +171,61 @@ async fn main() -> Result<()> {
 
     let cli = Cli::parse();
 
-    let client = SymbioticClient::new(dotenv::var("CONTRACT_ADDRESS")?.parse()?)?;
+    let mode = if cli.dry_run {
+        SendMode::DryRun
+    } else if cli.calldata_only {
+        SendMode::CalldataOnly
+    } else if cli.safe {
+        SendMode::Safe
+    } else {
+        SendMode::Broadcast
+    };
+    let sender = cli.from.as_deref().map(Address::from_str).transpose()?;
+    if sender.is_some() && mode == SendMode::Broadcast {
+        anyhow::bail!("--from only applies with --dry-run, --calldata-only or --safe");
+    }
+
+    let client = SymbioticClient::new(dotenv::var("CONTRACT_ADDRESS")?.parse()?)?
+        .with_confirmations(cli.confirmations)
+        .with_mode(mode)
+        .with_sender(sender)
+        .with_output(cli.output);
 
     match cli.command {
-        Commands::GetVaults => {
-            let vaults = client.get_whitelisted_vaults().await?;
-            println!("Whitelisted vaults: {:?}", vaults);
+        Commands::GetVaults { status } => {
+            let block = client.block_number().await?;
+            let vaults = client.get_whitelisted_vaults(block).await?;
+            if status {
+                let enabled = client.are_vaults_enabled(&vaults, block).await?;
+                output::print(cli.output, &VaultStatuses {
+                    block,
+                    vaults: vaults
+                        .into_iter()
+                        .zip(enabled)
+                        .map(|(vault, enabled)| VaultStatus { vault, enabled })
+                        .collect(),
+                })?;
+            } else {
+                output::print(cli.output, &AddressList {
+                    title: "Whitelisted vaults",
+                    key: "vaults",
+                    column: "vault",
+                    addresses: vaults,
+                })?;
+            }
         }
 
         Commands::GetCollateral {
             operator,
             collateral,
         } => {
-            let amount = client
-                .get_provider_collateral(
-                    Address::from_str(&operator)?,
-                    Address::from_str(&collateral)?,
-                )
-                .await?;
-            println!("Collateral amount: {}", amount);
+            let operator = Address::from_str(&operator)?;
+            let collateral = Address::from_str(&collateral)?;
+            let amount = client.get_provider_collateral(operator, collateral).await?;
+            output::print(cli.output, &CollateralBalance {
+                operator,
+                collateral: client.token_amount(collateral, amount).await,
+            })?;
         }
 
         Commands::Slash {
@@ -108,14 +243,16 @@ async fn main() -> Result<()> {
             block_number,
             tx_id,
         } => {
-            let response = client
-                .get_validator_response(
-                    validator_pubkey,
-                    block_number,
-                    H256::from_str(&tx_id)?,
-                )
+            let tx_id = H256::from_str(&tx_id)?;
+            let verified = client
+                .get_validator_response(validator_pubkey.clone(), block_number, tx_id)
                 .await?;
-            println!("Validator response: {}", response);
+            output::print(cli.output, &ValidatorResponse {
+                validator_pubkey,
+                block_number,
+                tx_id,
+                verified,
+            })?;
         }
 
         Commands::RegisterOperator { operator_addr, rpc } => {
@@ -124,14 +261,137 @@ async fn main() -> Result<()> {
                 .await?;
         }
 
+        Commands::Onboard {
+            operator_addr,
+            rpc,
+            opt_in,
+            check_only,
+        } => {
+            let operator = Address::from_str(&operator_addr)?;
+            let report = if check_only {
+                client.check_onboarding(operator).await?
+            } else {
+                client.onboard(operator, rpc, opt_in).await?
+            };
+            output::print(cli.output, &report)?;
+        }
+
         Commands::CheckVault { vault } => {
-            let status = client.is_vault_enabled(Address::from_str(&vault)?).await?;
-            println!("Vault enabled status: {}", status);
+            let vault = Address::from_str(&vault)?;
+            let enabled = client.is_vault_enabled(vault).await?;
+            output::print(cli.output, &VaultStatus { vault, enabled })?;
         }
 
         Commands::GetCurrentEpoch => {
             let epoch = client.get_current_time().await?;
-            println!("Current epoch: {}", epoch);
+            output::print(cli.output, &EpochInfo {
+                epoch,
+                start_time: client.get_period_start_time(epoch).await?,
+                end_time: None,
+                timestamp: None,
+            })?;
+        }
+
+        Commands::RegisterVault { vault } => {
+            client.register_vault(Address::from_str(&vault)?).await?;
+        }
+
+        Commands::DeregisterVault { vault } => {
+            client.deregister_vault(Address::from_str(&vault)?).await?;
+        }
+
+        Commands::PauseVault => {
+            client.pause_vault().await?;
+        }
+
+        Commands::UnpauseVault => {
+            client.unpause_vault().await?;
+        }
+
+        Commands::GetCollateralTokens { operator } => {
+            let operator = Address::from_str(&operator)?;
+            let block = client.block_number().await?;
+            let (tokens, amounts) = client.get_provider_collateral_tokens(operator, block).await?;
+
+            // Vaults that were not enabled at the epoch start leave an empty slot
+            let balances = tokens
+                .into_iter()
+                .zip(amounts)
+                .filter(|(token, _)| !token.is_zero())
+                .collect();
+            output::print(cli.output, &CollateralTokens {
+                operator,
+                block,
+                tokens: client.token_amounts(balances, block).await?,
+            })?;
+        }
+
+        Commands::GetOperatorStakeAt {
+            operator,
+            collateral,
+            timestamp,
+        } => {
+            let operator = Address::from_str(&operator)?;
+            let collateral = Address::from_str(&collateral)?;
+            let amount = client
+                .get_operator_stake_at(operator, collateral, timestamp)
+                .await?;
+            output::print(cli.output, &OperatorStake {
+                operator,
+                timestamp,
+                stake: client.token_amount(collateral, amount).await,
+            })?;
+        }
+
+        Commands::GetPeriodStartTime { epoch } => {
+            output::print(cli.output, &EpochInfo {
+                epoch,
+                start_time: client.get_period_start_time(epoch).await?,
+                end_time: Some(client.get_period_start_time(epoch + 1).await?),
+                timestamp: None,
+            })?;
+        }
+
+        Commands::GetPeriodAtTime { timestamp } => {
+            let epoch = client.get_period_at_time(timestamp).await?;
+            output::print(cli.output, &EpochInfo {
+                epoch,
+                start_time: client.get_period_start_time(epoch).await?,
+                end_time: None,
+                timestamp: Some(timestamp),
+            })?;
+        }
+
+        Commands::GetValidatorAddress { pubkey } => {
+            let pubkey = hex::decode(pubkey.trim_start_matches("0x"))?;
+            let address = SymbioticClient::get_validator_address(&pubkey)?;
+            output::print(cli.output, &ValidatorAddress {
+                pubkey: pubkey.into(),
+                address,
+            })?;
+        }
+
+        Commands::Upgrade {
+            new_implementation,
+            proxy,
+            init_data,
+        } => {
+            let proxy = proxy.map(|p| Address::from_str(&p)).transpose()?;
+            let init_data = match init_data {
+                Some(data) => hex::decode(data.trim_start_matches("0x"))?,
+                None => Vec::new(),
+            };
+            let plan = client
+                .plan_upgrade(proxy, Address::from_str(&new_implementation)?, init_data)
+                .await?;
+
+            // JSON output only carries the result of the upgrade itself
+            if (mode == SendMode::Broadcast || mode == SendMode::DryRun)
+                && cli.output != OutputFormat::Json
+            {
+                output::print(cli.output, &plan)?;
+            }
+            client.execute_upgrade(&plan).await?;
         }
 
         Commands::FetchEvents => {
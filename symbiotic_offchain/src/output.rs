@@ -0,0 +1,402 @@
+use anyhow::Result;
+use ethers::{
+    types::{Address, Bytes, H256, U256},
+    utils::format_units,
+};
+use serde::{ser::SerializeMap, Serialize, Serializer};
+use std::fmt;
+
+/// How command results are printed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable lines
+    #[default]
+    Plain,
+    /// Pretty-printed JSON with stable field names
+    Json,
+    /// Aligned columns, with token amounts formatted using the token decimals
+    Table,
+}
+
+/// A command result that can be printed in every `OutputFormat`.
+pub trait Render: Serialize {
+    fn plain(&self) -> String;
+    fn table(&self) -> Table;
+}
+
+/// Print `value` in the requested format.
+pub fn print<T: Render>(format: OutputFormat, value: &T) -> Result<()> {
+    match format {
+        OutputFormat::Plain => println!("{}", value.plain()),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(value)?),
+        OutputFormat::Table => println!("{}", value.table()),
+    }
+    Ok(())
+}
+
+/// Serialize a `U256` as a decimal string instead of the default hex quantity.
+pub fn serialize_decimal<S: Serializer>(
+    value: &U256,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(&value.to_string())
+}
+
+/// Rows printed with aligned columns under a header.
+#[derive(Debug, Clone, Default)]
+pub struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(headers: &[&str]) -> Self {
+        Self {
+            headers: headers.iter().map(|h| h.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Two-column table of named fields.
+    pub fn fields(fields: Vec<(&str, String)>) -> Self {
+        let mut table = Self::new(&["field", "value"]);
+        for (name, value) in fields {
+            table.push(vec![name.to_string(), value]);
+        }
+        table
+    }
+
+    pub fn push(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut widths: Vec<usize> = self.headers.iter().map(|h| h.len()).collect();
+        for row in &self.rows {
+            for (i, cell) in row.iter().enumerate() {
+                if i < widths.len() {
+                    widths[i] = widths[i].max(cell.chars().count());
+                }
+            }
+        }
+
+        let line = |cells: &[String]| {
+            cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = *width))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        };
+
+        writeln!(f, "{}", line(&self.headers))?;
+        let separator: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+        write!(f, "{}", line(&separator))?;
+        for row in &self.rows {
+            write!(f, "\n{}", line(row))?;
+        }
+        Ok(())
+    }
+}
+
+/// An amount of a token. Decimals and symbol are only looked up for table
+/// output; JSON always carries the raw integer amount.
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenAmount {
+    pub token: Address,
+    #[serde(serialize_with = "serialize_decimal")]
+    pub amount: U256,
+    #[serde(skip)]
+    pub decimals: Option<u8>,
+    #[serde(skip)]
+    pub symbol: Option<String>,
+}
+
+impl TokenAmount {
+    pub fn new(token: Address, amount: U256) -> Self {
+        Self {
+            token,
+            amount,
+            decimals: None,
+            symbol: None,
+        }
+    }
+
+    /// The amount in whole tokens when the decimals are known, raw otherwise.
+    pub fn formatted(&self) -> String {
+        let amount = match self.decimals.map(|d| format_units(self.amount, d as u32)) {
+            Some(Ok(units)) if units.contains('.') => {
+                units.trim_end_matches('0').trim_end_matches('.').to_string()
+            }
+            Some(Ok(units)) => units,
+            _ => self.amount.to_string(),
+        };
+        match &self.symbol {
+            Some(symbol) => format!("{} {}", amount, symbol),
+            None => amount,
+        }
+    }
+}
+
+/// Balance of one collateral token held by an operator.
+#[derive(Debug, Clone, Serialize)]
+pub struct CollateralBalance {
+    pub operator: Address,
+    pub collateral: TokenAmount,
+}
+
+impl Render for CollateralBalance {
+    fn plain(&self) -> String {
+        format!("Collateral amount: {}", self.collateral.amount)
+    }
+
+    fn table(&self) -> Table {
+        Table::fields(vec![
+            ("operator", format!("{:?}", self.operator)),
+            ("collateral", format!("{:?}", self.collateral.token)),
+            ("amount", self.collateral.formatted()),
+        ])
+    }
+}
+
+/// Every collateral token of an operator with its balance.
+#[derive(Debug, Clone, Serialize)]
+pub struct CollateralTokens {
+    pub operator: Address,
+    /// Block the balances and token metadata were read at
+    pub block: u64,
+    pub tokens: Vec<TokenAmount>,
+}
+
+impl Render for CollateralTokens {
+    fn plain(&self) -> String {
+        let mut lines = vec![format!("Provider Collateral Tokens at block {}:", self.block)];
+        for (i, token) in self.tokens.iter().enumerate() {
+            lines.push(format!("  {}: {:?} - {}", i + 1, token.token, token.amount));
+        }
+        lines.join("\n")
+    }
+
+    fn table(&self) -> Table {
+        let mut table = Table::new(&["token", "amount"]);
+        for token in &self.tokens {
+            table.push(vec![format!("{:?}", token.token), token.formatted()]);
+        }
+        table
+    }
+}
+
+/// Stake of an operator in one collateral at a point in time.
+#[derive(Debug, Clone, Serialize)]
+pub struct OperatorStake {
+    pub operator: Address,
+    pub timestamp: u64,
+    pub stake: TokenAmount,
+}
+
+impl Render for OperatorStake {
+    fn plain(&self) -> String {
+        format!("Operator Stake: {}", self.stake.amount)
+    }
+
+    fn table(&self) -> Table {
+        Table::fields(vec![
+            ("operator", format!("{:?}", self.operator)),
+            ("collateral", format!("{:?}", self.stake.token)),
+            ("timestamp", self.timestamp.to_string()),
+            ("stake", self.stake.formatted()),
+        ])
+    }
+}
+
+/// A titled list of contract addresses (strategies, vaults, ...), serialized
+/// as `{ "<key>": [...] }`.
+#[derive(Debug, Clone)]
+pub struct AddressList {
+    pub title: &'static str,
+    pub key: &'static str,
+    pub column: &'static str,
+    pub addresses: Vec<Address>,
+}
+
+impl Serialize for AddressList {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(self.key, &self.addresses)?;
+        map.end()
+    }
+}
+
+impl Render for AddressList {
+    fn plain(&self) -> String {
+        let mut lines = vec![format!("{}:", self.title)];
+        for (i, address) in self.addresses.iter().enumerate() {
+            lines.push(format!("  {}: {:?}", i + 1, address));
+        }
+        lines.join("\n")
+    }
+
+    fn table(&self) -> Table {
+        let mut table = Table::new(&["#", self.column]);
+        for (i, address) in self.addresses.iter().enumerate() {
+            table.push(vec![(i + 1).to_string(), format!("{:?}", address)]);
+        }
+        table
+    }
+}
+
+/// Render a unix timestamp as `<seconds> (<UTC date>)`.
+pub fn format_timestamp(timestamp: u64) -> String {
+    match chrono::DateTime::from_timestamp(timestamp as i64, 0) {
+        Some(datetime) => format!("{} ({})", timestamp, datetime.format("%Y-%m-%d %H:%M:%S UTC")),
+        None => timestamp.to_string(),
+    }
+}
+
+/// Whether a validator response was recorded for a transaction.
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidatorResponse {
+    pub validator_pubkey: String,
+    pub block_number: u64,
+    pub tx_id: H256,
+    pub verified: bool,
+}
+
+impl Render for ValidatorResponse {
+    fn plain(&self) -> String {
+        format!("Validator response: {}", self.verified)
+    }
+
+    fn table(&self) -> Table {
+        Table::fields(vec![
+            ("validator_pubkey", self.validator_pubkey.clone()),
+            ("block_number", self.block_number.to_string()),
+            ("tx_id", format!("{:?}", self.tx_id)),
+            ("verified", self.verified.to_string()),
+        ])
+    }
+}
+
+/// Whether a vault is enabled in the middleware.
+#[derive(Debug, Clone, Serialize)]
+pub struct VaultStatus {
+    pub vault: Address,
+    pub enabled: bool,
+}
+
+impl Render for VaultStatus {
+    fn plain(&self) -> String {
+        format!("Vault enabled status: {}", self.enabled)
+    }
+
+    fn table(&self) -> Table {
+        Table::fields(vec![
+            ("vault", format!("{:?}", self.vault)),
+            ("enabled", self.enabled.to_string()),
+        ])
+    }
+}
+
+/// Whitelisted vaults with their status, all read at the same block.
+#[derive(Debug, Clone, Serialize)]
+pub struct VaultStatuses {
+    pub block: u64,
+    pub vaults: Vec<VaultStatus>,
+}
+
+impl Render for VaultStatuses {
+    fn plain(&self) -> String {
+        let mut lines = vec![format!("Whitelisted vaults at block {}:", self.block)];
+        for (i, vault) in self.vaults.iter().enumerate() {
+            let status = if vault.enabled { "enabled" } else { "disabled" };
+            lines.push(format!("  {}: {:?} ({})", i + 1, vault.vault, status));
+        }
+        lines.join("\n")
+    }
+
+    fn table(&self) -> Table {
+        let mut table = Table::new(&["#", "vault", "enabled"]);
+        for (i, vault) in self.vaults.iter().enumerate() {
+            table.push(vec![
+                (i + 1).to_string(),
+                format!("{:?}", vault.vault),
+                vault.enabled.to_string(),
+            ]);
+        }
+        table
+    }
+}
+
+/// An epoch with its bounds. `timestamp` is set when the epoch was looked up
+/// by time, `end_time` when the next epoch start was requested as well.
+#[derive(Debug, Clone, Serialize)]
+pub struct EpochInfo {
+    pub epoch: u64,
+    pub start_time: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_time: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>,
+}
+
+impl Render for EpochInfo {
+    fn plain(&self) -> String {
+        match (self.timestamp, self.end_time) {
+            (Some(timestamp), _) => format!(
+                "{} is in epoch {} (started at {})",
+                format_timestamp(timestamp),
+                self.epoch,
+                format_timestamp(self.start_time)
+            ),
+            (None, Some(end_time)) => format!(
+                "Epoch {} starts at {} and ends at {}",
+                self.epoch,
+                format_timestamp(self.start_time),
+                format_timestamp(end_time)
+            ),
+            (None, None) => format!(
+                "Current epoch: {} (started at {})",
+                self.epoch,
+                format_timestamp(self.start_time)
+            ),
+        }
+    }
+
+    fn table(&self) -> Table {
+        let mut fields = vec![
+            ("epoch", self.epoch.to_string()),
+            ("start_time", format_timestamp(self.start_time)),
+        ];
+        if let Some(end_time) = self.end_time {
+            fields.push(("end_time", format_timestamp(end_time)));
+        }
+        if let Some(timestamp) = self.timestamp {
+            fields.push(("timestamp", format_timestamp(timestamp)));
+        }
+        Table::fields(fields)
+    }
+}
+
+/// Address the middleware derives from a validator public key.
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidatorAddress {
+    pub pubkey: Bytes,
+    pub address: Address,
+}
+
+impl Render for ValidatorAddress {
+    fn plain(&self) -> String {
+        format!("Validator address: {:?}", self.address)
+    }
+
+    fn table(&self) -> Table {
+        Table::fields(vec![
+            ("pubkey", format!("0x{}", hex::encode(&self.pubkey))),
+            ("address", format!("{:?}", self.address)),
+        ])
+    }
+}
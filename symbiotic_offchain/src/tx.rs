@@ -0,0 +1,443 @@
+use anyhow::Result;
+use ethers::{
+    abi::{Detokenize, RawLog},
+    contract::{ContractCall, ContractError, ContractRevert, EthLogDecode},
+    prelude::*,
+    types::{Address, Bytes, TransactionReceipt, H256, U256},
+};
+use serde::Serialize;
+use serde_json::json;
+use std::fmt;
+
+use crate::output::{serialize_decimal, OutputFormat, Table};
+
+/// Number of confirmations write calls wait for unless configured otherwise.
+pub const DEFAULT_CONFIRMATIONS: usize = 1;
+
+/// What a write call does with the transaction it builds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SendMode {
+    /// Sign, broadcast and wait for confirmations.
+    #[default]
+    Broadcast,
+    /// `eth_call` and `eth_estimateGas` from the sender, without broadcasting.
+    DryRun,
+    /// Only print target, value and calldata.
+    CalldataOnly,
+    /// Only print a Safe transaction-builder batch.
+    Safe,
+}
+
+/// Result of a write call, depending on the `SendMode` it ran in.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum WriteResult {
+    Sent(TxOutcome),
+    Simulated(Simulation),
+    Prepared(PreparedTx),
+}
+
+/// Result of simulating a call with `eth_call` and `eth_estimateGas`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Simulation {
+    pub function: String,
+    pub from: Option<Address>,
+    pub to: Option<Address>,
+    pub returned: Option<String>,
+    #[serde(serialize_with = "serialize_optional_decimal")]
+    pub gas_estimate: Option<U256>,
+    pub revert_reason: Option<String>,
+}
+
+impl fmt::Display for Simulation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} from {:?} to {:?} ",
+            self.function,
+            self.from.unwrap_or_default(),
+            self.to.unwrap_or_default()
+        )?;
+        match &self.revert_reason {
+            Some(reason) => write!(f, "reverts: {}", reason),
+            None => write!(
+                f,
+                "succeeds (gas estimate: {}, returns: {})",
+                self.gas_estimate.unwrap_or_default(),
+                self.returned.as_deref().unwrap_or("()")
+            ),
+        }
+    }
+}
+
+/// An unsigned transaction, for submission by another account such as a Safe.
+#[derive(Debug, Clone, Serialize)]
+pub struct PreparedTx {
+    pub function: String,
+    pub chain_id: u64,
+    pub from: Option<Address>,
+    pub to: Address,
+    #[serde(serialize_with = "serialize_decimal")]
+    pub value: U256,
+    pub calldata: Bytes,
+}
+
+impl PreparedTx {
+    /// Safe transaction-builder batch containing this transaction.
+    pub fn safe_transaction_json(&self, description: &str) -> serde_json::Value {
+        let mut meta = json!({
+            "name": self.function,
+            "description": description,
+        });
+        if let Some(from) = self.from {
+            meta["createdFromSafeAddress"] = json!(format!("{:?}", from));
+        }
+        json!({
+            "version": "1.0",
+            "chainId": self.chain_id.to_string(),
+            "meta": meta,
+            "transactions": [{
+                "to": format!("{:?}", self.to),
+                "value": self.value.to_string(),
+                "data": format!("0x{}", hex::encode(&self.calldata)),
+            }],
+        })
+    }
+}
+
+impl fmt::Display for PreparedTx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "to: {:?}\nvalue: {}\ncalldata: 0x{}",
+            self.to,
+            self.value,
+            hex::encode(&self.calldata)
+        )
+    }
+}
+
+/// Outcome of a state-changing call. Only `tx_hash` is set when the call was
+/// sent without waiting for confirmations.
+#[derive(Debug, Clone, Serialize)]
+pub struct TxOutcome {
+    pub tx_hash: H256,
+    pub success: Option<bool>,
+    pub block_number: Option<u64>,
+    #[serde(serialize_with = "serialize_optional_decimal")]
+    pub gas_used: Option<U256>,
+    pub events: Vec<String>,
+    pub revert_reason: Option<String>,
+}
+
+impl fmt::Display for TxOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.success, self.block_number) {
+            (Some(success), Some(block)) => write!(
+                f,
+                "{:?} {} in block {} (gas used: {})",
+                self.tx_hash,
+                if success { "succeeded" } else { "failed" },
+                block,
+                self.gas_used.unwrap_or_default()
+            )?,
+            _ => write!(f, "{:?} submitted", self.tx_hash)?,
+        }
+        if let Some(reason) = &self.revert_reason {
+            write!(f, "\n  revert: {}", reason)?;
+        }
+        for event in &self.events {
+            write!(f, "\n  event: {}", event)?;
+        }
+        Ok(())
+    }
+}
+
+/// Describe a contract error, decoding revert data against the custom errors `R`.
+pub fn describe_error<M: Middleware, R: ContractRevert + fmt::Debug>(error: &ContractError<M>) -> String {
+    if let Some(decoded) = error.decode_contract_revert::<R>() {
+        return format!("{:?}", decoded);
+    }
+    match error.as_revert() {
+        Some(data) => format!("unknown revert 0x{}", hex::encode(data)),
+        None => error.to_string(),
+    }
+}
+
+fn serialize_optional_decimal<S: serde::Serializer>(
+    value: &Option<U256>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => serialize_decimal(value, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+impl WriteResult {
+    /// Fields of the result as a two-column table.
+    pub fn table(&self, action: &str) -> Table {
+        let mut fields = vec![("action", action.to_string())];
+        match self {
+            WriteResult::Sent(outcome) => {
+                fields.push(("tx_hash", format!("{:?}", outcome.tx_hash)));
+                fields.push(("status", match outcome.success {
+                    Some(true) => "success".to_string(),
+                    Some(false) => "failed".to_string(),
+                    None => "submitted".to_string(),
+                }));
+                if let Some(block) = outcome.block_number {
+                    fields.push(("block", block.to_string()));
+                }
+                if let Some(gas_used) = outcome.gas_used {
+                    fields.push(("gas_used", gas_used.to_string()));
+                }
+                if let Some(reason) = &outcome.revert_reason {
+                    fields.push(("revert", reason.clone()));
+                }
+                for event in &outcome.events {
+                    fields.push(("event", event.clone()));
+                }
+            }
+            WriteResult::Simulated(simulation) => {
+                fields.push(("function", simulation.function.clone()));
+                fields.push(("from", format!("{:?}", simulation.from.unwrap_or_default())));
+                fields.push(("to", format!("{:?}", simulation.to.unwrap_or_default())));
+                match &simulation.revert_reason {
+                    Some(reason) => fields.push(("revert", reason.clone())),
+                    None => {
+                        fields.push(("gas_estimate", simulation.gas_estimate.unwrap_or_default().to_string()));
+                        fields.push(("returns", simulation.returned.clone().unwrap_or_else(|| "()".to_string())));
+                    }
+                }
+            }
+            WriteResult::Prepared(prepared) => {
+                fields.push(("function", prepared.function.clone()));
+                fields.push(("to", format!("{:?}", prepared.to)));
+                fields.push(("value", prepared.value.to_string()));
+                fields.push(("calldata", format!("0x{}", hex::encode(&prepared.calldata))));
+            }
+        }
+        Table::fields(fields)
+    }
+}
+
+#[derive(Serialize)]
+struct Reported<'a> {
+    action: &'a str,
+    #[serde(flatten)]
+    result: &'a WriteResult,
+}
+
+/// Print `result` for the command line and turn failed transactions and
+/// reverting simulations into errors.
+pub fn report(
+    action: &str,
+    mode: SendMode,
+    format: OutputFormat,
+    result: WriteResult,
+) -> Result<WriteResult> {
+    match (&result, format) {
+        (WriteResult::Prepared(prepared), _) if mode == SendMode::Safe => {
+            let batch = prepared.safe_transaction_json(action);
+            println!("{}", serde_json::to_string_pretty(&batch)?);
+        }
+        (_, OutputFormat::Json) => {
+            let reported = Reported { action, result: &result };
+            println!("{}", serde_json::to_string_pretty(&reported)?);
+        }
+        (_, OutputFormat::Table) => println!("{}", result.table(action)),
+        (WriteResult::Sent(outcome), OutputFormat::Plain) => {
+            if outcome.success != Some(false) {
+                println!("{}: {}", action, outcome);
+            }
+        }
+        (WriteResult::Simulated(simulation), OutputFormat::Plain) => {
+            if simulation.revert_reason.is_none() {
+                println!("{} (dry run): {}", action, simulation);
+            }
+        }
+        (WriteResult::Prepared(prepared), OutputFormat::Plain) => println!("{}", prepared),
+    }
+
+    match &result {
+        WriteResult::Sent(outcome) if outcome.success == Some(false) => {
+            anyhow::bail!("Transaction failed: {}", outcome)
+        }
+        WriteResult::Simulated(simulation) if simulation.revert_reason.is_some() => {
+            anyhow::bail!("Simulation failed: {}", simulation)
+        }
+        _ => Ok(result),
+    }
+}
+
+/// Run `call` according to `mode`. `chain_id` is only used for prepared transactions.
+pub async fn execute<M, D, R, E>(
+    call: ContractCall<M, D>,
+    mode: SendMode,
+    confirmations: usize,
+    chain_id: u64,
+) -> Result<WriteResult>
+where
+    M: Middleware + 'static,
+    D: Detokenize + fmt::Debug,
+    R: ContractRevert + fmt::Debug,
+    E: EthLogDecode + fmt::Debug,
+{
+    Ok(match mode {
+        SendMode::Broadcast => {
+            WriteResult::Sent(send_and_confirm::<M, D, R, E>(call, confirmations).await?)
+        }
+        SendMode::DryRun => WriteResult::Simulated(simulate::<M, D, R>(&call).await?),
+        SendMode::CalldataOnly | SendMode::Safe => WriteResult::Prepared(prepare(&call, chain_id)?),
+    })
+}
+
+/// Simulate `call` from its sender. Reverts are reported in the result rather
+/// than as an error, decoded against the custom errors `R`; failures to reach
+/// the node are errors.
+pub async fn simulate<M, D, R>(call: &ContractCall<M, D>) -> Result<Simulation>
+where
+    M: Middleware + 'static,
+    D: Detokenize + fmt::Debug,
+    R: ContractRevert + fmt::Debug,
+{
+    let mut simulation = Simulation {
+        function: call.function.name.clone(),
+        from: call.tx.from().copied(),
+        to: call.tx.to_addr().copied(),
+        returned: None,
+        gas_estimate: None,
+        revert_reason: None,
+    };
+
+    match call.call().await {
+        Ok(returned) => simulation.returned = Some(format!("{:?}", returned)),
+        Err(e) if is_node_response(&e) => {
+            simulation.revert_reason = Some(describe_error::<M, R>(&e));
+            return Ok(simulation);
+        }
+        Err(e) => anyhow::bail!("Simulation of {} failed: {}", simulation.function, e),
+    }
+    match call.estimate_gas().await {
+        Ok(gas) => simulation.gas_estimate = Some(gas),
+        Err(e) if is_node_response(&e) => simulation.revert_reason = Some(describe_error::<M, R>(&e)),
+        Err(e) => anyhow::bail!("Gas estimation of {} failed: {}", simulation.function, e),
+    }
+
+    Ok(simulation)
+}
+
+// Whether the node answered the request with an error (e.g. a revert), as
+// opposed to the request not reaching it
+fn is_node_response<M: Middleware>(error: &ContractError<M>) -> bool {
+    match error {
+        ContractError::Revert(_) => true,
+        ContractError::MiddlewareError { e } => e.as_error_response().is_some(),
+        ContractError::ProviderError { e } => RpcError::as_error_response(e).is_some(),
+        _ => false,
+    }
+}
+
+/// Extract target, value and calldata of `call` without signing it.
+pub fn prepare<M, D>(call: &ContractCall<M, D>, chain_id: u64) -> Result<PreparedTx>
+where
+    M: Middleware + 'static,
+    D: Detokenize,
+{
+    Ok(PreparedTx {
+        function: call.function.name.clone(),
+        chain_id,
+        from: call.tx.from().copied(),
+        to: call
+            .tx
+            .to_addr()
+            .copied()
+            .ok_or_else(|| anyhow::anyhow!("Call has no target address"))?,
+        value: call.tx.value().copied().unwrap_or_default(),
+        calldata: call
+            .calldata()
+            .ok_or_else(|| anyhow::anyhow!("Failed to encode {}", call.function.name))?,
+    })
+}
+
+/// Send `call`, wait for `confirmations` blocks and decode the receipt logs
+/// against the contract events `E`. Reverts are decoded against `R`, both when
+/// the call fails before broadcast and when a mined transaction failed.
+pub async fn send_and_confirm<M, D, R, E>(
+    call: ContractCall<M, D>,
+    confirmations: usize,
+) -> Result<TxOutcome>
+where
+    M: Middleware + 'static,
+    D: Detokenize,
+    R: ContractRevert + fmt::Debug,
+    E: EthLogDecode + fmt::Debug,
+{
+    let pending_tx = call
+        .send()
+        .await
+        .map_err(|e| anyhow::anyhow!("Transaction reverted: {}", describe_error::<M, R>(&e)))?;
+    let tx_hash = pending_tx.tx_hash();
+
+    if confirmations == 0 {
+        return Ok(TxOutcome {
+            tx_hash,
+            success: None,
+            block_number: None,
+            gas_used: None,
+            events: Vec::new(),
+            revert_reason: None,
+        });
+    }
+
+    let receipt = pending_tx
+        .confirmations(confirmations)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Transaction {:?} was dropped", tx_hash))?;
+
+    let mut outcome = outcome_from_receipt::<E>(&receipt, call.tx.to_addr().copied());
+    if outcome.success == Some(false) {
+        // Replay the call at the block it was mined in to recover the reason
+        if let Some(block) = receipt.block_number {
+            if let Err(e) = call.block(block).call().await {
+                outcome.revert_reason = Some(describe_error::<M, R>(&e));
+            }
+        }
+    }
+
+    Ok(outcome)
+}
+
+fn outcome_from_receipt<E: EthLogDecode + fmt::Debug>(
+    receipt: &TransactionReceipt,
+    contract: Option<Address>,
+) -> TxOutcome {
+    let events = receipt
+        .logs
+        .iter()
+        .map(|log| {
+            let raw = RawLog {
+                topics: log.topics.clone(),
+                data: log.data.to_vec(),
+            };
+            match E::decode_log(&raw) {
+                Ok(event) if Some(log.address) == contract => format!("{:?}", event),
+                _ => format!(
+                    "{:?} topic0 {:?}",
+                    log.address,
+                    log.topics.first().copied().unwrap_or_default()
+                ),
+            }
+        })
+        .collect();
+
+    TxOutcome {
+        tx_hash: receipt.transaction_hash,
+        success: receipt.status.map(|status| status.as_u64() == 1),
+        block_number: receipt.block_number.map(|b| b.as_u64()),
+        gas_used: receipt.gas_used,
+        events,
+        revert_reason: None,
+    }
+}